@@ -35,7 +35,10 @@ lazy_static! {
             "Symbols",
             "IetfLanguageTags",
             "ErrorCondition",
-            "DistributionMode"
+            "DistributionMode",
+            "TargetTerminus",
+            "TxnId",
+            "GlobalTxId"
         ]
         .iter()
         .map(|s| s.to_string())
@@ -170,7 +170,9 @@ impl DecodeFormatted for char {
 impl DecodeFormatted for DateTime<Utc> {
     fn decode_with_format(input: &[u8], fmt: u8) -> Result<(&[u8], Self), AmqpParseError> {
         validate_code!(fmt, codec::FORMATCODE_TIMESTAMP);
-        be_read!(input, read_i64, 8).map(|(i, o)| (i, datetime_from_millis(o)))
+        let result: Result<(&[u8], i64), AmqpParseError> = be_read!(input, read_i64, 8);
+        let (input, millis) = result?;
+        Ok((input, datetime_from_millis(millis)?))
     }
 }
 
@@ -234,11 +236,11 @@ impl DecodeFormatted for Symbol {
         match fmt {
             codec::FORMATCODE_SYMBOL8 => {
                 let (input, bytes) = read_bytes_u8(input)?;
-                Ok((input, Symbol::from_slice(str::from_utf8(bytes)?)))
+                Ok((input, Symbol::intern(str::from_utf8(bytes)?)))
             }
             codec::FORMATCODE_SYMBOL32 => {
                 let (input, bytes) = read_bytes_u32(input)?;
-                Ok((input, Symbol::from_slice(str::from_utf8(bytes)?)))
+                Ok((input, Symbol::intern(str::from_utf8(bytes)?)))
             }
             _ => Err(AmqpParseError::InvalidFormatCode(fmt)),
         }
@@ -248,7 +250,7 @@ impl DecodeFormatted for Symbol {
 impl ArrayDecode for Symbol {
     fn array_decode(input: &[u8]) -> Result<(&[u8], Self), AmqpParseError> {
         let (input, bytes) = read_bytes_u32(input)?;
-        Ok((input, Symbol::from_slice(str::from_utf8(bytes)?)))
+        Ok((input, Symbol::intern(str::from_utf8(bytes)?)))
     }
 }
 
@@ -259,8 +261,10 @@ impl<K: Decode + Eq + Hash, V: Decode, S: BuildHasher + Default> DecodeFormatted
         let (input, header) = decode_map_header(input, fmt)?;
         let mut map_input = &input[..header.size as usize];
         let count = header.count / 2;
-        let mut map: collections::HashMap<K, V, S> =
-            collections::HashMap::with_capacity_and_hasher(count as usize, Default::default());
+        let mut map: collections::HashMap<K, V, S> = collections::HashMap::with_capacity_and_hasher(
+            header.capacity() / 2,
+            Default::default(),
+        );
         for _ in 0..count {
             let (input1, key) = K::decode(map_input)?;
             let (input2, value) = V::decode(input1)?;
@@ -275,9 +279,10 @@ impl<K: Decode + Eq + Hash, V: Decode, S: BuildHasher + Default> DecodeFormatted
 impl<T: DecodeFormatted> DecodeFormatted for Vec<T> {
     fn decode_with_format(input: &[u8], fmt: u8) -> Result<(&[u8], Self), AmqpParseError> {
         let (input, header) = decode_array_header(input, fmt)?;
+        decode_check_len!(input, 1);
         let item_fmt = input[0]; // todo: support descriptor
         let mut input = &input[1..];
-        let mut result: Vec<T> = Vec::with_capacity(header.count as usize);
+        let mut result: Vec<T> = Vec::with_capacity(header.capacity());
         for _ in 0..header.count {
             let (new_input, decoded) = T::decode_with_format(input, item_fmt)?;
             result.push(decoded);
@@ -292,7 +297,7 @@ impl DecodeFormatted for VecSymbolMap {
         let (input, header) = decode_map_header(input, fmt)?;
         let mut map_input = &input[..header.size as usize];
         let count = header.count / 2;
-        let mut map = Vec::with_capacity(count as usize);
+        let mut map = Vec::with_capacity(header.capacity() / 2);
         for _ in 0..count {
             let (input1, key) = Symbol::decode(map_input)?;
             let (input2, value) = Variant::decode(input1)?;
@@ -309,7 +314,7 @@ impl DecodeFormatted for VecStringMap {
         let (input, header) = decode_map_header(input, fmt)?;
         let mut map_input = &input[..header.size as usize];
         let count = header.count / 2;
-        let mut map = Vec::with_capacity(count as usize);
+        let mut map = Vec::with_capacity(header.capacity() / 2);
         for _ in 0..count {
             let (input1, key) = Str::decode(map_input)?;
             let (input2, value) = Variant::decode(input1)?;
@@ -339,7 +344,7 @@ impl<T: ArrayDecode + DecodeFormatted> DecodeFormatted for Multiple<T> {
 impl DecodeFormatted for List {
     fn decode_with_format(input: &[u8], fmt: u8) -> Result<(&[u8], Self), AmqpParseError> {
         let (mut input, header) = decode_list_header(input, fmt)?;
-        let mut result: Vec<Variant> = Vec::with_capacity(header.count as usize);
+        let mut result: Vec<Variant> = Vec::with_capacity(header.capacity());
         for _ in 0..header.count {
             let (new_input, decoded) = Variant::decode(input)?;
             result.push(decoded);
@@ -550,10 +555,12 @@ pub(crate) fn decode_map_header(
 
 fn decode_compound8(input: &[u8]) -> Result<(&[u8], CompoundHeader), AmqpParseError> {
     decode_check_len!(input, 2);
-    let size = input[0] - 1; // -1 for 1 byte count
+    let size = input[0].checked_sub(1).ok_or(AmqpParseError::InvalidSize)?; // -1 for 1 byte count
     let count = input[1];
+    let input = &input[2..];
+    decode_check_len!(input, size as usize);
     Ok((
-        &input[2..],
+        input,
         CompoundHeader {
             size: u32::from(size),
             count: u32::from(count),
@@ -563,23 +570,30 @@ fn decode_compound8(input: &[u8]) -> Result<(&[u8], CompoundHeader), AmqpParseEr
 
 fn decode_compound32(input: &[u8]) -> Result<(&[u8], CompoundHeader), AmqpParseError> {
     decode_check_len!(input, 8);
-    let size = BigEndian::read_u32(input) - 4; // -4 for 4 byte count
+    let size = BigEndian::read_u32(input)
+        .checked_sub(4) // -4 for 4 byte count
+        .ok_or(AmqpParseError::InvalidSize)?;
     let count = BigEndian::read_u32(&input[4..]);
-    Ok((&input[8..], CompoundHeader { size, count }))
+    let input = &input[8..];
+    decode_check_len!(input, size as usize);
+    Ok((input, CompoundHeader { size, count }))
 }
 
-fn datetime_from_millis(millis: i64) -> DateTime<Utc> {
+fn datetime_from_millis(millis: i64) -> Result<DateTime<Utc>, AmqpParseError> {
     let seconds = millis / 1000;
-    if seconds < 0 {
+    let datetime = if seconds < 0 {
         // In order to handle time before 1970 correctly, we need to subtract a second
         // and use the nanoseconds field to add it back. This is a result of the nanoseconds
         // parameter being u32
         let nanoseconds = ((1000 + (millis - (seconds * 1000))) * 1_000_000).abs() as u32;
-        Utc.timestamp(seconds - 1, nanoseconds)
+        Utc.timestamp_opt(seconds - 1, nanoseconds)
     } else {
         let nanoseconds = ((millis - (seconds * 1000)) * 1_000_000).abs() as u32;
-        Utc.timestamp(seconds, nanoseconds)
-    }
+        Utc.timestamp_opt(seconds, nanoseconds)
+    };
+    datetime
+        .single()
+        .ok_or(AmqpParseError::InvalidTimestamp(millis))
 }
 
 #[cfg(test)]
@@ -735,6 +749,24 @@ mod tests {
         assert_eq!(expected, unwrap_value(DateTime::<Utc>::decode(b1)));
     }
 
+    #[test]
+    fn test_symbol_intern() {
+        let b1 = &mut BytesMut::with_capacity(0);
+        Symbol::from_slice("apache.org:selector-filter:string").encode(b1);
+        let sym = unwrap_value(Symbol::decode(b1));
+        assert_eq!(
+            sym,
+            Symbol::from_static("apache.org:selector-filter:string")
+        );
+        assert!(matches!(sym.0, Str::Static(_)));
+
+        let b1 = &mut BytesMut::with_capacity(0);
+        Symbol::from_slice("custom").encode(b1);
+        let sym = unwrap_value(Symbol::decode(b1));
+        assert_eq!(sym, Symbol::from_static("custom"));
+        assert!(matches!(sym.0, Str::ByteStr(_)));
+    }
+
     #[test]
     fn variant_null() {
         let mut b = BytesMut::with_capacity(0);
@@ -109,7 +109,11 @@ mod tests {
     use crate::codec::{Decode, Encode};
     use crate::error::AmqpCodecError;
     use crate::framing::{AmqpFrame, SaslFrame};
-    use crate::protocol::SaslFrameBody;
+    use crate::protocol::{
+        Accepted, AmqpError, DeliveryState, Disposition, Error, ErrorCondition, Fields, Modified,
+        Outcome, Rejected, Role, SaslFrameBody, TransactionalState,
+    };
+    use crate::types::{Symbol, Variant};
 
     #[test]
     fn test_sasl_mechanisms() -> Result<(), AmqpCodecError> {
@@ -147,4 +151,338 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_transactional_disposition() -> Result<(), AmqpCodecError> {
+        let frame = AmqpFrame::new(
+            0,
+            Disposition {
+                role: Role::Receiver,
+                first: 1,
+                last: None,
+                settled: true,
+                state: Some(DeliveryState::TransactionalState(TransactionalState {
+                    txn_id: Bytes::from_static(b"txn-1"),
+                    outcome: Some(Outcome::Accepted(Accepted {})),
+                })),
+                batchable: false,
+            }
+            .into(),
+        );
+
+        let mut buf = BytesMut::new();
+        buf.reserve(frame.encoded_size());
+        frame.encode(&mut buf);
+        let _ = buf.split_to(4);
+
+        let (remainder, decoded) = AmqpFrame::decode(&buf)?;
+        assert!(remainder.is_empty());
+        assert_eq!(frame, decoded);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_modified_disposition() -> Result<(), AmqpCodecError> {
+        let mut annotations = Fields::default();
+        annotations.insert(
+            Symbol::from_static("x-opt-delivery-count"),
+            Variant::from(3u32),
+        );
+
+        // annotations must be encoded without delivery-failed and undeliverable-here flags
+        let frame = AmqpFrame::new(
+            0,
+            Disposition {
+                role: Role::Receiver,
+                first: 1,
+                last: None,
+                settled: true,
+                state: Some(DeliveryState::Modified(Modified {
+                    delivery_failed: None,
+                    undeliverable_here: None,
+                    message_annotations: Some(annotations),
+                })),
+                batchable: false,
+            }
+            .into(),
+        );
+
+        let mut buf = BytesMut::new();
+        buf.reserve(frame.encoded_size());
+        frame.encode(&mut buf);
+        let _ = buf.split_to(4);
+
+        let (remainder, decoded) = AmqpFrame::decode(&buf)?;
+        assert!(remainder.is_empty());
+        assert_eq!(frame, decoded);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejected_disposition() -> Result<(), AmqpCodecError> {
+        let mut info = Fields::default();
+        info.insert(Symbol::from_static("x-opt-reason"), Variant::from("quota"));
+
+        let frame = AmqpFrame::new(
+            0,
+            Disposition {
+                role: Role::Receiver,
+                first: 1,
+                last: None,
+                settled: true,
+                state: Some(DeliveryState::Rejected(Rejected {
+                    error: Some(Error {
+                        condition: ErrorCondition::AmqpError(AmqpError::ResourceLimitExceeded),
+                        description: None,
+                        info: Some(info),
+                    }),
+                })),
+                batchable: false,
+            }
+            .into(),
+        );
+
+        let mut buf = BytesMut::new();
+        buf.reserve(frame.encoded_size());
+        frame.encode(&mut buf);
+        let _ = buf.split_to(4);
+
+        let (remainder, decoded) = AmqpFrame::decode(&buf)?;
+        assert!(remainder.is_empty());
+        assert_eq!(frame, decoded);
+
+        Ok(())
+    }
+
+    fn malformed_frames() -> Vec<Bytes> {
+        use chrono::{TimeZone, Utc};
+
+        use crate::message::Message;
+        use crate::protocol::{
+            Attach, Begin, Close, Flow, MessageId, Open, ReceiverSettleMode, SenderSettleMode,
+            Source, Target, TerminusDurability, TerminusExpiryPolicy, Transfer, TransferBody,
+        };
+        use crate::types::Multiple;
+
+        let mut props = Fields::default();
+        props.insert(Symbol::from_static("product"), Variant::from("test"));
+        props.insert(Symbol::from_static("version"), Variant::from(1_u32));
+        let caps = Multiple(vec![
+            Symbol::from_static("cap1"),
+            Symbol::from_static("cap2"),
+        ]);
+
+        let mut msg = Message::with_body(Bytes::from_static(b"Hello world"));
+        msg.set_properties(|p| {
+            p.message_id = Some(MessageId::Ulong(10));
+            p.subject = Some("subject".into());
+            p.creation_time = Utc.timestamp_millis_opt(1_600_000_000_000).single();
+        })
+        .set_app_property("key", 100_i32)
+        .add_message_annotation("x-opt-annotation", "value");
+        let mut body = BytesMut::new();
+        body.reserve(msg.encoded_size());
+        msg.encode(&mut body);
+
+        let frames: Vec<AmqpFrame> = vec![
+            AmqpFrame::new(
+                0,
+                Open {
+                    container_id: "container".into(),
+                    hostname: Some("localhost".into()),
+                    max_frame_size: 65535,
+                    channel_max: 1024,
+                    idle_time_out: Some(120_000),
+                    outgoing_locales: None,
+                    incoming_locales: None,
+                    offered_capabilities: Some(caps.clone()),
+                    desired_capabilities: None,
+                    properties: Some(props.clone()),
+                }
+                .into(),
+            ),
+            AmqpFrame::new(
+                1,
+                Begin {
+                    remote_channel: Some(1),
+                    next_outgoing_id: 1,
+                    incoming_window: 2048,
+                    outgoing_window: 2048,
+                    handle_max: 1024,
+                    offered_capabilities: None,
+                    desired_capabilities: Some(caps.clone()),
+                    properties: None,
+                }
+                .into(),
+            ),
+            AmqpFrame::new(
+                1,
+                Attach {
+                    name: "link".into(),
+                    handle: 1,
+                    role: Role::Sender,
+                    snd_settle_mode: SenderSettleMode::Mixed,
+                    rcv_settle_mode: ReceiverSettleMode::First,
+                    source: Some(Source {
+                        address: Some("source".into()),
+                        durable: TerminusDurability::None,
+                        expiry_policy: TerminusExpiryPolicy::SessionEnd,
+                        timeout: 0,
+                        dynamic: false,
+                        dynamic_node_properties: None,
+                        distribution_mode: None,
+                        filter: None,
+                        default_outcome: None,
+                        outcomes: None,
+                        capabilities: Some(caps),
+                    }),
+                    target: Some(
+                        Target {
+                            address: Some("target".into()),
+                            durable: TerminusDurability::None,
+                            expiry_policy: TerminusExpiryPolicy::SessionEnd,
+                            timeout: 0,
+                            dynamic: false,
+                            dynamic_node_properties: None,
+                            capabilities: None,
+                        }
+                        .into(),
+                    ),
+                    unsettled: None,
+                    incomplete_unsettled: false,
+                    initial_delivery_count: Some(0),
+                    max_message_size: Some(65536),
+                    offered_capabilities: None,
+                    desired_capabilities: None,
+                    properties: Some(props),
+                }
+                .into(),
+            ),
+            AmqpFrame::new(
+                1,
+                Flow {
+                    next_incoming_id: Some(1),
+                    incoming_window: 2048,
+                    next_outgoing_id: 1,
+                    outgoing_window: 2048,
+                    handle: Some(1),
+                    delivery_count: Some(0),
+                    link_credit: Some(100),
+                    available: None,
+                    drain: false,
+                    echo: false,
+                    properties: None,
+                }
+                .into(),
+            ),
+            AmqpFrame::new(
+                1,
+                Transfer {
+                    handle: 1,
+                    delivery_id: Some(1),
+                    delivery_tag: Some(Bytes::from_static(b"tag")),
+                    message_format: Some(0),
+                    settled: Some(false),
+                    more: false,
+                    rcv_settle_mode: None,
+                    state: None,
+                    resume: false,
+                    aborted: false,
+                    batchable: false,
+                    body: Some(TransferBody::Data(body.freeze())),
+                }
+                .into(),
+            ),
+            AmqpFrame::new(
+                0,
+                Close {
+                    error: Some(Error {
+                        condition: ErrorCondition::AmqpError(AmqpError::InternalError),
+                        description: Some("error".into()),
+                        info: None,
+                    }),
+                }
+                .into(),
+            ),
+        ];
+
+        frames
+            .into_iter()
+            .map(|frame| {
+                let mut buf = BytesMut::new();
+                buf.reserve(frame.encoded_size());
+                frame.encode(&mut buf);
+                let _ = buf.split_to(4);
+                buf.freeze()
+            })
+            .collect()
+    }
+
+    fn decode_malformed(buf: &[u8]) {
+        if let Ok((_, frame)) = AmqpFrame::decode(buf) {
+            if let crate::protocol::Frame::Transfer(ref transfer) = frame.performative() {
+                if let Some(crate::protocol::TransferBody::Data(ref data)) = transfer.body {
+                    let _ = crate::message::Message::decode(data);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_malformed_frames() {
+        const BYTES: &[u8] = &[
+            0x00, 0x01, 0x02, 0x40, 0x41, 0x44, 0x45, 0x53, 0x70, 0x83, 0xa0, 0xa1, 0xa3, 0xb0,
+            0xb1, 0xc0, 0xc1, 0xd0, 0xd1, 0xe0, 0xf0, 0xfe, 0xff,
+        ];
+
+        // xorshift, deterministic input for every run
+        let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+        let mut rand = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+
+        for frame in malformed_frames() {
+            decode_malformed(&frame);
+
+            for len in 0..frame.len() {
+                decode_malformed(&frame[..len]);
+            }
+
+            for idx in 0..frame.len() {
+                for b in BYTES {
+                    let mut buf = frame.to_vec();
+                    buf[idx] = *b;
+                    decode_malformed(&buf);
+                }
+            }
+
+            for _ in 0..5000 {
+                let mut buf = frame.to_vec();
+                for _ in 0..(rand() % 4 + 1) {
+                    let idx = rand() as usize % buf.len();
+                    buf[idx] = rand() as u8;
+                }
+                let len = buf.len() - (rand() as usize % 4);
+                decode_malformed(&buf[..len]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_frame_size() {
+        use ntex_codec::Decoder;
+
+        let codec = crate::AmqpCodec::<AmqpFrame>::new();
+        for size in 0..8_u32 {
+            let mut buf = BytesMut::new();
+            buf.extend_from_slice(&size.to_be_bytes());
+            buf.extend_from_slice(&[2, 0, 0, 0]);
+            assert!(codec.decode(&mut buf).is_err());
+        }
+    }
 }
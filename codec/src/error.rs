@@ -29,6 +29,19 @@ pub enum AmqpParseError {
     #[display(fmt = "Unexpected type: '{:?}'", "_0")]
     UnexpectedType(&'static str),
     Utf8Error(std::str::Utf8Error),
+    #[from(ignore)]
+    #[display(fmt = "Timestamp is out of range: {}", "_0")]
+    InvalidTimestamp(i64),
+}
+
+impl std::error::Error for AmqpParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AmqpParseError::UuidParseError(err) => Some(err),
+            AmqpParseError::Utf8Error(err) => Some(err),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Display, From, Clone)]
@@ -40,6 +53,15 @@ pub enum AmqpCodecError {
     MaxSizeExceeded,
 }
 
+impl std::error::Error for AmqpCodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AmqpCodecError::ParseError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Display, From, Clone)]
 pub enum ProtocolIdError {
     InvalidHeader,
@@ -52,6 +74,8 @@ pub enum ProtocolIdError {
     },
 }
 
+impl std::error::Error for ProtocolIdError {}
+
 impl From<()> for Error {
     fn from(_: ()) -> Error {
         Error {
@@ -61,3 +85,18 @@ impl From<()> for Error {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error as StdError;
+
+    use super::*;
+
+    #[test]
+    fn test_error_source() {
+        let err: Box<dyn StdError> = Box::new(AmqpCodecError::from(AmqpParseError::InvalidSize));
+        let source = err.source().unwrap();
+        assert!(source.downcast_ref::<AmqpParseError>().is_some());
+        assert!(source.source().is_none());
+    }
+}
@@ -4,7 +4,7 @@ use byteorder::{BigEndian, ByteOrder};
 use ntex_bytes::{Buf, BufMut, BytesMut};
 use ntex_codec::{Decoder, Encoder};
 
-use super::error::{AmqpCodecError, ProtocolIdError};
+use super::error::{AmqpCodecError, AmqpParseError, ProtocolIdError};
 use super::framing::HEADER_LEN;
 use crate::codec::{Decode, Encode};
 use crate::protocol::ProtocolId;
@@ -76,6 +76,9 @@ impl<T: Decode + Encode> Decoder for AmqpCodec<T> {
                     if self.max_size != 0 && size > self.max_size {
                         return Err(AmqpCodecError::MaxSizeExceeded);
                     }
+                    if size < HEADER_LEN {
+                        return Err(AmqpParseError::InvalidSize.into());
+                    }
                     self.state.set(DecodeState::Frame(size - 4));
                     src.advance(4);
 
@@ -23,6 +23,24 @@ impl MessageBody {
         }
     }
 
+    /// Concatenated content of all data sections
+    pub fn data_concat(&self) -> Option<Bytes> {
+        match self.data.len() {
+            0 => None,
+            1 => Some(self.data[0].clone()),
+            _ => {
+                let mut buf = BytesMut::with_capacity(self.data.iter().map(|d| d.len()).sum());
+                self.data.iter().for_each(|d| buf.extend_from_slice(d));
+                Some(buf.freeze())
+            }
+        }
+    }
+
+    /// Append data section
+    pub fn add_data(&mut self, data: Bytes) {
+        self.data.push(data);
+    }
+
     pub fn value(&self) -> Option<&Variant> {
         self.value.as_ref()
     }
@@ -1,10 +1,12 @@
 use std::cell::Cell;
 
-use ntex_bytes::{Bytes, BytesMut};
+use ntex_bytes::{ByteString, Bytes, BytesMut};
 
 use crate::codec::{Decode, Encode};
 use crate::error::AmqpParseError;
-use crate::protocol::{Annotations, Header, MessageFormat, Properties, Section, TransferBody};
+use crate::protocol::{
+    Annotations, Header, MessageFormat, Properties, Section, SequenceNo, TransferBody,
+};
 use crate::types::{Descriptor, Str, Symbol, Variant, VecStringMap, VecSymbolMap};
 
 use super::body::MessageBody;
@@ -52,6 +54,31 @@ impl Message {
         self
     }
 
+    /// Set `durable` field of message header
+    ///
+    /// Durable messages are expected to be persisted by intermediaries.
+    pub fn set_durable(&mut self, durable: bool) -> &mut Self {
+        self.header_mut().durable = durable;
+        self
+    }
+
+    /// Set `priority` field of message header
+    pub fn set_priority(&mut self, priority: u8) -> &mut Self {
+        self.header_mut().priority = priority;
+        self
+    }
+
+    fn header_mut(&mut self) -> &mut Header {
+        self.size.set(0);
+        self.header.get_or_insert(Header {
+            durable: false,
+            priority: 4,
+            ttl: None,
+            first_acquirer: false,
+            delivery_count: 0,
+        })
+    }
+
     /// Message properties
     pub fn properties(&self) -> Option<&Properties> {
         self.properties.as_ref()
@@ -83,6 +110,24 @@ impl Message {
         self
     }
 
+    /// Set `group-id` property, message belongs to the group
+    pub fn set_group_id<T: Into<ByteString>>(&mut self, group_id: T) -> &mut Self {
+        self.properties_mut().group_id = Some(group_id.into());
+        self
+    }
+
+    /// Set `group-sequence` property, relative position of message within its group
+    pub fn set_group_sequence(&mut self, sequence: SequenceNo) -> &mut Self {
+        self.properties_mut().group_sequence = Some(sequence);
+        self
+    }
+
+    /// Set `reply-to-group-id` property, group of reply messages
+    pub fn set_reply_to_group_id<T: Into<ByteString>>(&mut self, group_id: T) -> &mut Self {
+        self.properties_mut().reply_to_group_id = Some(group_id.into());
+        self
+    }
+
     /// Get application property
     pub fn app_properties(&self) -> Option<&VecStringMap> {
         self.application_properties.as_ref()
@@ -151,9 +196,38 @@ impl Message {
 
     /// Mut reference to delivery annotations
     pub fn delivery_annotations_mut(&mut self) -> Option<&mut VecSymbolMap> {
+        self.size.set(0);
         self.delivery_annotations.as_mut()
     }
 
+    /// Get delivery annotation
+    pub fn delivery_annotation(&self, key: &str) -> Option<&Variant> {
+        if let Some(ref props) = self.delivery_annotations {
+            props
+                .iter()
+                .find_map(|item| if &item.0 == key { Some(&item.1) } else { None })
+        } else {
+            None
+        }
+    }
+
+    /// Add delivery annotation
+    pub fn add_delivery_annotation<K, V>(&mut self, key: K, value: V) -> &mut Self
+    where
+        K: Into<Symbol>,
+        V: Into<Variant>,
+    {
+        if let Some(ref mut props) = self.delivery_annotations {
+            props.push((key.into(), value.into()));
+        } else {
+            let mut props = VecSymbolMap::default();
+            props.push((key.into(), value.into()));
+            self.delivery_annotations = Some(props);
+        }
+        self.size.set(0);
+        self
+    }
+
     /// Call closure with message reference
     pub fn update<F>(self, f: F) -> Self
     where
@@ -202,6 +276,15 @@ impl Message {
         self
     }
 
+    /// Append data section to message body
+    ///
+    /// Data sections are encoded as consecutive `data` sections of the body.
+    pub fn add_data(&mut self, data: Bytes) -> &mut Self {
+        self.body.data.push(data);
+        self.size.set(0);
+        self
+    }
+
     /// Create new message and set `correlation_id` property
     pub fn reply_message(&self) -> Message {
         Message::default().if_some(&self.properties, |mut msg, data| {
@@ -360,6 +443,29 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_delivery_annotations() -> Result<(), AmqpCodecError> {
+        let mut msg = Message::default();
+        msg.add_delivery_annotation("x-opt-hop", 1);
+        msg.add_message_annotation("x-opt-msg", 2);
+
+        let mut buf = BytesMut::with_capacity(msg.encoded_size());
+        msg.encode(&mut buf);
+
+        let msg2 = Message::decode(&buf)?.1;
+        assert_eq!(
+            msg2.delivery_annotation("x-opt-hop"),
+            Some(&Variant::from(1))
+        );
+        assert_eq!(msg2.delivery_annotation("x-opt-msg"), None);
+        assert_eq!(
+            msg2.message_annotation("x-opt-msg"),
+            Some(&Variant::from(2))
+        );
+        assert_eq!(msg2.message_annotation("x-opt-hop"), None);
+        Ok(())
+    }
+
     #[test]
     fn test_header() -> Result<(), AmqpCodecError> {
         let hdr = Header {
@@ -394,6 +500,79 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_data_sections() -> Result<(), AmqpCodecError> {
+        let mut msg = Message::default();
+        msg.set_body(|body| {
+            body.add_data(Bytes::from_static(b"test "));
+            body.add_data(Bytes::from_static(b"data"));
+        });
+        let mut buf = BytesMut::with_capacity(msg.encoded_size());
+        msg.encode(&mut buf);
+
+        let msg2 = Message::decode(&buf)?.1;
+        assert_eq!(msg2.body.data.len(), 2);
+        assert_eq!(
+            msg2.body.data_concat().unwrap(),
+            Bytes::from_static(b"test data")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_data() -> Result<(), AmqpCodecError> {
+        let mut msg = Message::with_body(Bytes::from_static(b"test "));
+        let size = msg.encoded_size();
+        msg.add_data(Bytes::from_static(b"data"));
+        assert!(msg.encoded_size() > size);
+
+        let mut buf = BytesMut::with_capacity(msg.encoded_size());
+        msg.encode(&mut buf);
+        assert_eq!(buf.len(), msg.encoded_size());
+
+        let msg2 = Message::decode(&buf)?.1;
+        assert_eq!(msg2.body.data.len(), 2);
+        assert_eq!(
+            msg2.body.data_concat().unwrap(),
+            Bytes::from_static(b"test data")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_header_fields() -> Result<(), AmqpCodecError> {
+        let mut msg = Message::with_body(Bytes::from_static(b"data"));
+        msg.set_durable(true).set_priority(9);
+
+        let mut buf = BytesMut::with_capacity(msg.encoded_size());
+        msg.encode(&mut buf);
+
+        let msg2 = Message::decode(&buf)?.1;
+        let header = msg2.header().unwrap();
+        assert!(header.durable());
+        assert_eq!(header.priority(), 9);
+        assert_eq!(header.ttl(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_properties() -> Result<(), AmqpCodecError> {
+        let mut msg = Message::with_body(Bytes::from_static(b"data"));
+        msg.set_group_id("group")
+            .set_group_sequence(3)
+            .set_reply_to_group_id("reply-group");
+
+        let mut buf = BytesMut::with_capacity(msg.encoded_size());
+        msg.encode(&mut buf);
+
+        let msg2 = Message::decode(&buf)?.1;
+        let props = msg2.properties().unwrap();
+        assert_eq!(props.group_id().unwrap(), "group");
+        assert_eq!(props.group_sequence(), Some(3));
+        assert_eq!(props.reply_to_group_id().unwrap(), "reply-group");
+        Ok(())
+    }
+
     #[test]
     fn test_data_empty() -> Result<(), AmqpCodecError> {
         let msg = Message::default();
@@ -235,6 +235,8 @@ pub enum DeliveryState {
     Rejected(Rejected),
     Released(Released),
     Modified(Modified),
+    Declared(Declared),
+    TransactionalState(TransactionalState),
 }
 impl DecodeFormatted for DeliveryState {
     fn decode_with_format(input: &[u8], fmt: u8) -> Result<(&[u8], Self), AmqpParseError> {
@@ -256,6 +258,11 @@ impl DecodeFormatted for DeliveryState {
             Descriptor::Ulong(39) => {
                 decode_modified_inner(input).map(|(i, r)| (i, DeliveryState::Modified(r)))
             }
+            Descriptor::Ulong(51) => {
+                decode_declared_inner(input).map(|(i, r)| (i, DeliveryState::Declared(r)))
+            }
+            Descriptor::Ulong(52) => decode_transactional_state_inner(input)
+                .map(|(i, r)| (i, DeliveryState::TransactionalState(r))),
             Descriptor::Symbol(ref a) if a.as_str() == "amqp:received:list" => {
                 decode_received_inner(input).map(|(i, r)| (i, DeliveryState::Received(r)))
             }
@@ -271,6 +278,13 @@ impl DecodeFormatted for DeliveryState {
             Descriptor::Symbol(ref a) if a.as_str() == "amqp:modified:list" => {
                 decode_modified_inner(input).map(|(i, r)| (i, DeliveryState::Modified(r)))
             }
+            Descriptor::Symbol(ref a) if a.as_str() == "amqp:declared:list" => {
+                decode_declared_inner(input).map(|(i, r)| (i, DeliveryState::Declared(r)))
+            }
+            Descriptor::Symbol(ref a) if a.as_str() == "amqp:transactional-state:list" => {
+                decode_transactional_state_inner(input)
+                    .map(|(i, r)| (i, DeliveryState::TransactionalState(r)))
+            }
             _ => Err(AmqpParseError::InvalidDescriptor(descriptor)),
         }
     }
@@ -283,6 +297,8 @@ impl Encode for DeliveryState {
             DeliveryState::Rejected(ref v) => encoded_size_rejected_inner(v),
             DeliveryState::Released(ref v) => encoded_size_released_inner(v),
             DeliveryState::Modified(ref v) => encoded_size_modified_inner(v),
+            DeliveryState::Declared(ref v) => encoded_size_declared_inner(v),
+            DeliveryState::TransactionalState(ref v) => encoded_size_transactional_state_inner(v),
         }
     }
     fn encode(&self, buf: &mut BytesMut) {
@@ -292,6 +308,8 @@ impl Encode for DeliveryState {
             DeliveryState::Rejected(ref v) => encode_rejected_inner(v, buf),
             DeliveryState::Released(ref v) => encode_released_inner(v, buf),
             DeliveryState::Modified(ref v) => encode_modified_inner(v, buf),
+            DeliveryState::Declared(ref v) => encode_declared_inner(v, buf),
+            DeliveryState::TransactionalState(ref v) => encode_transactional_state_inner(v, buf),
         }
     }
 }
@@ -301,6 +319,7 @@ pub enum Outcome {
     Rejected(Rejected),
     Released(Released),
     Modified(Modified),
+    Declared(Declared),
 }
 impl DecodeFormatted for Outcome {
     fn decode_with_format(input: &[u8], fmt: u8) -> Result<(&[u8], Self), AmqpParseError> {
@@ -319,6 +338,9 @@ impl DecodeFormatted for Outcome {
             Descriptor::Ulong(39) => {
                 decode_modified_inner(input).map(|(i, r)| (i, Outcome::Modified(r)))
             }
+            Descriptor::Ulong(51) => {
+                decode_declared_inner(input).map(|(i, r)| (i, Outcome::Declared(r)))
+            }
             Descriptor::Symbol(ref a) if a.as_str() == "amqp:accepted:list" => {
                 decode_accepted_inner(input).map(|(i, r)| (i, Outcome::Accepted(r)))
             }
@@ -331,6 +353,9 @@ impl DecodeFormatted for Outcome {
             Descriptor::Symbol(ref a) if a.as_str() == "amqp:modified:list" => {
                 decode_modified_inner(input).map(|(i, r)| (i, Outcome::Modified(r)))
             }
+            Descriptor::Symbol(ref a) if a.as_str() == "amqp:declared:list" => {
+                decode_declared_inner(input).map(|(i, r)| (i, Outcome::Declared(r)))
+            }
             _ => Err(AmqpParseError::InvalidDescriptor(descriptor)),
         }
     }
@@ -342,6 +367,7 @@ impl Encode for Outcome {
             Outcome::Rejected(ref v) => encoded_size_rejected_inner(v),
             Outcome::Released(ref v) => encoded_size_released_inner(v),
             Outcome::Modified(ref v) => encoded_size_modified_inner(v),
+            Outcome::Declared(ref v) => encoded_size_declared_inner(v),
         }
     }
     fn encode(&self, buf: &mut BytesMut) {
@@ -350,6 +376,47 @@ impl Encode for Outcome {
             Outcome::Rejected(ref v) => encode_rejected_inner(v, buf),
             Outcome::Released(ref v) => encode_released_inner(v, buf),
             Outcome::Modified(ref v) => encode_modified_inner(v, buf),
+            Outcome::Declared(ref v) => encode_declared_inner(v, buf),
+        }
+    }
+}
+#[derive(Clone, Debug, PartialEq)]
+pub enum TargetTerminus {
+    Target(Target),
+    Coordinator(Coordinator),
+}
+impl DecodeFormatted for TargetTerminus {
+    fn decode_with_format(input: &[u8], fmt: u8) -> Result<(&[u8], Self), AmqpParseError> {
+        validate_code!(fmt, codec::FORMATCODE_DESCRIBED);
+        let (input, descriptor) = Descriptor::decode(input)?;
+        match descriptor {
+            Descriptor::Ulong(41) => {
+                decode_target_inner(input).map(|(i, r)| (i, TargetTerminus::Target(r)))
+            }
+            Descriptor::Ulong(48) => {
+                decode_coordinator_inner(input).map(|(i, r)| (i, TargetTerminus::Coordinator(r)))
+            }
+            Descriptor::Symbol(ref a) if a.as_str() == "amqp:target:list" => {
+                decode_target_inner(input).map(|(i, r)| (i, TargetTerminus::Target(r)))
+            }
+            Descriptor::Symbol(ref a) if a.as_str() == "amqp:coordinator:list" => {
+                decode_coordinator_inner(input).map(|(i, r)| (i, TargetTerminus::Coordinator(r)))
+            }
+            _ => Err(AmqpParseError::InvalidDescriptor(descriptor)),
+        }
+    }
+}
+impl Encode for TargetTerminus {
+    fn encoded_size(&self) -> usize {
+        match *self {
+            TargetTerminus::Target(ref v) => encoded_size_target_inner(v),
+            TargetTerminus::Coordinator(ref v) => encoded_size_coordinator_inner(v),
+        }
+    }
+    fn encode(&self, buf: &mut BytesMut) {
+        match *self {
+            TargetTerminus::Target(ref v) => encode_target_inner(v, buf),
+            TargetTerminus::Coordinator(ref v) => encode_coordinator_inner(v, buf),
         }
     }
 }
@@ -435,6 +502,8 @@ pub type MessageIdUuid = Uuid;
 pub type MessageIdBinary = Bytes;
 pub type MessageIdString = ByteString;
 pub type Address = ByteString;
+pub type TxnId = Bytes;
+pub type GlobalTxId = Bytes;
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Role {
     Sender,
@@ -1632,7 +1701,7 @@ pub struct Attach {
     pub snd_settle_mode: SenderSettleMode,
     pub rcv_settle_mode: ReceiverSettleMode,
     pub source: Option<Source>,
-    pub target: Option<Target>,
+    pub target: Option<TargetTerminus>,
     pub unsettled: Option<Map>,
     pub incomplete_unsettled: bool,
     pub initial_delivery_count: Option<SequenceNo>,
@@ -1660,7 +1729,7 @@ impl Attach {
     pub fn source(&self) -> Option<&Source> {
         self.source.as_ref()
     }
-    pub fn target(&self) -> Option<&Target> {
+    pub fn target(&self) -> Option<&TargetTerminus> {
         self.target.as_ref()
     }
     pub fn unsettled(&self) -> Option<&Map> {
@@ -1749,9 +1818,9 @@ fn decode_attach_inner(input: &[u8]) -> Result<(&[u8], Attach), AmqpParseError>
     } else {
         source = None;
     }
-    let target: Option<Target>;
+    let target: Option<TargetTerminus>;
     if count > 0 {
-        let decoded = Option::<Target>::decode(input)?;
+        let decoded = Option::<TargetTerminus>::decode(input)?;
         input = decoded.0;
         target = decoded.1;
         count -= 1;
@@ -4658,3 +4727,423 @@ impl Encode for Modified {
         encode_modified_inner(self, buf)
     }
 }
+#[derive(Clone, Debug, PartialEq)]
+pub struct Coordinator {
+    pub capabilities: Option<Symbols>,
+}
+impl Coordinator {
+    pub fn capabilities(&self) -> Option<&Symbols> {
+        self.capabilities.as_ref()
+    }
+    #[allow(clippy::identity_op)]
+    const FIELD_COUNT: usize = 0 + 1;
+}
+#[allow(unused_mut)]
+fn decode_coordinator_inner(input: &[u8]) -> Result<(&[u8], Coordinator), AmqpParseError> {
+    let (input, format) = decode_format_code(input)?;
+    let (input, header) = decode_list_header(input, format)?;
+    let size = header.size as usize;
+    decode_check_len!(input, size);
+    let (mut input, mut remainder) = input.split_at(size);
+    let mut count = header.count;
+    let capabilities: Option<Symbols>;
+    if count > 0 {
+        let decoded = Option::<Symbols>::decode(input)?;
+        input = decoded.0;
+        capabilities = decoded.1;
+        count -= 1;
+    } else {
+        capabilities = None;
+    }
+    Ok((remainder, Coordinator { capabilities }))
+}
+fn encoded_size_coordinator_inner(list: &Coordinator) -> usize {
+    #[allow(clippy::identity_op)]
+    let content_size = 0 + list.capabilities.encoded_size();
+    // header: 0x00 0x53 <descriptor code> format_code size count
+    (if content_size + 1 > u8::MAX as usize {
+        12
+    } else {
+        6
+    }) + content_size
+}
+fn encode_coordinator_inner(list: &Coordinator, buf: &mut BytesMut) {
+    Descriptor::Ulong(48).encode(buf);
+    #[allow(clippy::identity_op)]
+    let content_size = 0 + list.capabilities.encoded_size();
+    if content_size + 1 > u8::MAX as usize {
+        buf.put_u8(codec::FORMATCODE_LIST32);
+        buf.put_u32((content_size + 4) as u32); // +4 for 4 byte count
+        buf.put_u32(Coordinator::FIELD_COUNT as u32);
+    } else {
+        buf.put_u8(codec::FORMATCODE_LIST8);
+        buf.put_u8((content_size + 1) as u8);
+        buf.put_u8(Coordinator::FIELD_COUNT as u8);
+    }
+    list.capabilities.encode(buf);
+}
+impl DecodeFormatted for Coordinator {
+    fn decode_with_format(input: &[u8], fmt: u8) -> Result<(&[u8], Self), AmqpParseError> {
+        validate_code!(fmt, codec::FORMATCODE_DESCRIBED);
+        let (input, descriptor) = Descriptor::decode(input)?;
+        let is_match = match descriptor {
+            Descriptor::Ulong(val) => val == 48,
+            Descriptor::Symbol(ref sym) => sym.as_bytes() == b"amqp:coordinator:list",
+        };
+        if !is_match {
+            Err(AmqpParseError::InvalidDescriptor(descriptor))
+        } else {
+            decode_coordinator_inner(input)
+        }
+    }
+}
+impl Encode for Coordinator {
+    fn encoded_size(&self) -> usize {
+        encoded_size_coordinator_inner(self)
+    }
+    fn encode(&self, buf: &mut BytesMut) {
+        encode_coordinator_inner(self, buf)
+    }
+}
+#[derive(Clone, Debug, PartialEq)]
+pub struct Declare {
+    pub global_id: Option<GlobalTxId>,
+}
+impl Declare {
+    pub fn global_id(&self) -> Option<&GlobalTxId> {
+        self.global_id.as_ref()
+    }
+    #[allow(clippy::identity_op)]
+    const FIELD_COUNT: usize = 0 + 1;
+}
+#[allow(unused_mut)]
+fn decode_declare_inner(input: &[u8]) -> Result<(&[u8], Declare), AmqpParseError> {
+    let (input, format) = decode_format_code(input)?;
+    let (input, header) = decode_list_header(input, format)?;
+    let size = header.size as usize;
+    decode_check_len!(input, size);
+    let (mut input, mut remainder) = input.split_at(size);
+    let mut count = header.count;
+    let global_id: Option<GlobalTxId>;
+    if count > 0 {
+        let decoded = Option::<GlobalTxId>::decode(input)?;
+        input = decoded.0;
+        global_id = decoded.1;
+        count -= 1;
+    } else {
+        global_id = None;
+    }
+    Ok((remainder, Declare { global_id }))
+}
+fn encoded_size_declare_inner(list: &Declare) -> usize {
+    #[allow(clippy::identity_op)]
+    let content_size = 0 + list.global_id.encoded_size();
+    // header: 0x00 0x53 <descriptor code> format_code size count
+    (if content_size + 1 > u8::MAX as usize {
+        12
+    } else {
+        6
+    }) + content_size
+}
+fn encode_declare_inner(list: &Declare, buf: &mut BytesMut) {
+    Descriptor::Ulong(49).encode(buf);
+    #[allow(clippy::identity_op)]
+    let content_size = 0 + list.global_id.encoded_size();
+    if content_size + 1 > u8::MAX as usize {
+        buf.put_u8(codec::FORMATCODE_LIST32);
+        buf.put_u32((content_size + 4) as u32); // +4 for 4 byte count
+        buf.put_u32(Declare::FIELD_COUNT as u32);
+    } else {
+        buf.put_u8(codec::FORMATCODE_LIST8);
+        buf.put_u8((content_size + 1) as u8);
+        buf.put_u8(Declare::FIELD_COUNT as u8);
+    }
+    list.global_id.encode(buf);
+}
+impl DecodeFormatted for Declare {
+    fn decode_with_format(input: &[u8], fmt: u8) -> Result<(&[u8], Self), AmqpParseError> {
+        validate_code!(fmt, codec::FORMATCODE_DESCRIBED);
+        let (input, descriptor) = Descriptor::decode(input)?;
+        let is_match = match descriptor {
+            Descriptor::Ulong(val) => val == 49,
+            Descriptor::Symbol(ref sym) => sym.as_bytes() == b"amqp:declare:list",
+        };
+        if !is_match {
+            Err(AmqpParseError::InvalidDescriptor(descriptor))
+        } else {
+            decode_declare_inner(input)
+        }
+    }
+}
+impl Encode for Declare {
+    fn encoded_size(&self) -> usize {
+        encoded_size_declare_inner(self)
+    }
+    fn encode(&self, buf: &mut BytesMut) {
+        encode_declare_inner(self, buf)
+    }
+}
+#[derive(Clone, Debug, PartialEq)]
+pub struct Discharge {
+    pub txn_id: TxnId,
+    pub fail: Option<bool>,
+}
+impl Discharge {
+    pub fn txn_id(&self) -> &TxnId {
+        &self.txn_id
+    }
+    pub fn fail(&self) -> Option<bool> {
+        self.fail
+    }
+    #[allow(clippy::identity_op)]
+    const FIELD_COUNT: usize = 0 + 1 + 1;
+}
+#[allow(unused_mut)]
+fn decode_discharge_inner(input: &[u8]) -> Result<(&[u8], Discharge), AmqpParseError> {
+    let (input, format) = decode_format_code(input)?;
+    let (input, header) = decode_list_header(input, format)?;
+    let size = header.size as usize;
+    decode_check_len!(input, size);
+    let (mut input, mut remainder) = input.split_at(size);
+    let mut count = header.count;
+    let txn_id: TxnId;
+    if count > 0 {
+        let (in1, decoded) = TxnId::decode(input)?;
+        txn_id = decoded;
+        input = in1;
+        count -= 1;
+    } else {
+        return Err(AmqpParseError::RequiredFieldOmitted("txn_id"));
+    }
+    let fail: Option<bool>;
+    if count > 0 {
+        let decoded = Option::<bool>::decode(input)?;
+        input = decoded.0;
+        fail = decoded.1;
+        count -= 1;
+    } else {
+        fail = None;
+    }
+    Ok((remainder, Discharge { txn_id, fail }))
+}
+fn encoded_size_discharge_inner(list: &Discharge) -> usize {
+    #[allow(clippy::identity_op)]
+    let content_size = 0 + list.txn_id.encoded_size() + list.fail.encoded_size();
+    // header: 0x00 0x53 <descriptor code> format_code size count
+    (if content_size + 1 > u8::MAX as usize {
+        12
+    } else {
+        6
+    }) + content_size
+}
+fn encode_discharge_inner(list: &Discharge, buf: &mut BytesMut) {
+    Descriptor::Ulong(50).encode(buf);
+    #[allow(clippy::identity_op)]
+    let content_size = 0 + list.txn_id.encoded_size() + list.fail.encoded_size();
+    if content_size + 1 > u8::MAX as usize {
+        buf.put_u8(codec::FORMATCODE_LIST32);
+        buf.put_u32((content_size + 4) as u32); // +4 for 4 byte count
+        buf.put_u32(Discharge::FIELD_COUNT as u32);
+    } else {
+        buf.put_u8(codec::FORMATCODE_LIST8);
+        buf.put_u8((content_size + 1) as u8);
+        buf.put_u8(Discharge::FIELD_COUNT as u8);
+    }
+    list.txn_id.encode(buf);
+    list.fail.encode(buf);
+}
+impl DecodeFormatted for Discharge {
+    fn decode_with_format(input: &[u8], fmt: u8) -> Result<(&[u8], Self), AmqpParseError> {
+        validate_code!(fmt, codec::FORMATCODE_DESCRIBED);
+        let (input, descriptor) = Descriptor::decode(input)?;
+        let is_match = match descriptor {
+            Descriptor::Ulong(val) => val == 50,
+            Descriptor::Symbol(ref sym) => sym.as_bytes() == b"amqp:discharge:list",
+        };
+        if !is_match {
+            Err(AmqpParseError::InvalidDescriptor(descriptor))
+        } else {
+            decode_discharge_inner(input)
+        }
+    }
+}
+impl Encode for Discharge {
+    fn encoded_size(&self) -> usize {
+        encoded_size_discharge_inner(self)
+    }
+    fn encode(&self, buf: &mut BytesMut) {
+        encode_discharge_inner(self, buf)
+    }
+}
+#[derive(Clone, Debug, PartialEq)]
+pub struct Declared {
+    pub txn_id: TxnId,
+}
+impl Declared {
+    pub fn txn_id(&self) -> &TxnId {
+        &self.txn_id
+    }
+    #[allow(clippy::identity_op)]
+    const FIELD_COUNT: usize = 0 + 1;
+}
+#[allow(unused_mut)]
+fn decode_declared_inner(input: &[u8]) -> Result<(&[u8], Declared), AmqpParseError> {
+    let (input, format) = decode_format_code(input)?;
+    let (input, header) = decode_list_header(input, format)?;
+    let size = header.size as usize;
+    decode_check_len!(input, size);
+    let (mut input, mut remainder) = input.split_at(size);
+    let mut count = header.count;
+    let txn_id: TxnId;
+    if count > 0 {
+        let (in1, decoded) = TxnId::decode(input)?;
+        txn_id = decoded;
+        input = in1;
+        count -= 1;
+    } else {
+        return Err(AmqpParseError::RequiredFieldOmitted("txn_id"));
+    }
+    Ok((remainder, Declared { txn_id }))
+}
+fn encoded_size_declared_inner(list: &Declared) -> usize {
+    #[allow(clippy::identity_op)]
+    let content_size = 0 + list.txn_id.encoded_size();
+    // header: 0x00 0x53 <descriptor code> format_code size count
+    (if content_size + 1 > u8::MAX as usize {
+        12
+    } else {
+        6
+    }) + content_size
+}
+fn encode_declared_inner(list: &Declared, buf: &mut BytesMut) {
+    Descriptor::Ulong(51).encode(buf);
+    #[allow(clippy::identity_op)]
+    let content_size = 0 + list.txn_id.encoded_size();
+    if content_size + 1 > u8::MAX as usize {
+        buf.put_u8(codec::FORMATCODE_LIST32);
+        buf.put_u32((content_size + 4) as u32); // +4 for 4 byte count
+        buf.put_u32(Declared::FIELD_COUNT as u32);
+    } else {
+        buf.put_u8(codec::FORMATCODE_LIST8);
+        buf.put_u8((content_size + 1) as u8);
+        buf.put_u8(Declared::FIELD_COUNT as u8);
+    }
+    list.txn_id.encode(buf);
+}
+impl DecodeFormatted for Declared {
+    fn decode_with_format(input: &[u8], fmt: u8) -> Result<(&[u8], Self), AmqpParseError> {
+        validate_code!(fmt, codec::FORMATCODE_DESCRIBED);
+        let (input, descriptor) = Descriptor::decode(input)?;
+        let is_match = match descriptor {
+            Descriptor::Ulong(val) => val == 51,
+            Descriptor::Symbol(ref sym) => sym.as_bytes() == b"amqp:declared:list",
+        };
+        if !is_match {
+            Err(AmqpParseError::InvalidDescriptor(descriptor))
+        } else {
+            decode_declared_inner(input)
+        }
+    }
+}
+impl Encode for Declared {
+    fn encoded_size(&self) -> usize {
+        encoded_size_declared_inner(self)
+    }
+    fn encode(&self, buf: &mut BytesMut) {
+        encode_declared_inner(self, buf)
+    }
+}
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransactionalState {
+    pub txn_id: TxnId,
+    pub outcome: Option<Outcome>,
+}
+impl TransactionalState {
+    pub fn txn_id(&self) -> &TxnId {
+        &self.txn_id
+    }
+    pub fn outcome(&self) -> Option<&Outcome> {
+        self.outcome.as_ref()
+    }
+    #[allow(clippy::identity_op)]
+    const FIELD_COUNT: usize = 0 + 1 + 1;
+}
+#[allow(unused_mut)]
+fn decode_transactional_state_inner(
+    input: &[u8],
+) -> Result<(&[u8], TransactionalState), AmqpParseError> {
+    let (input, format) = decode_format_code(input)?;
+    let (input, header) = decode_list_header(input, format)?;
+    let size = header.size as usize;
+    decode_check_len!(input, size);
+    let (mut input, mut remainder) = input.split_at(size);
+    let mut count = header.count;
+    let txn_id: TxnId;
+    if count > 0 {
+        let (in1, decoded) = TxnId::decode(input)?;
+        txn_id = decoded;
+        input = in1;
+        count -= 1;
+    } else {
+        return Err(AmqpParseError::RequiredFieldOmitted("txn_id"));
+    }
+    let outcome: Option<Outcome>;
+    if count > 0 {
+        let decoded = Option::<Outcome>::decode(input)?;
+        input = decoded.0;
+        outcome = decoded.1;
+        count -= 1;
+    } else {
+        outcome = None;
+    }
+    Ok((remainder, TransactionalState { txn_id, outcome }))
+}
+fn encoded_size_transactional_state_inner(list: &TransactionalState) -> usize {
+    #[allow(clippy::identity_op)]
+    let content_size = 0 + list.txn_id.encoded_size() + list.outcome.encoded_size();
+    // header: 0x00 0x53 <descriptor code> format_code size count
+    (if content_size + 1 > u8::MAX as usize {
+        12
+    } else {
+        6
+    }) + content_size
+}
+fn encode_transactional_state_inner(list: &TransactionalState, buf: &mut BytesMut) {
+    Descriptor::Ulong(52).encode(buf);
+    #[allow(clippy::identity_op)]
+    let content_size = 0 + list.txn_id.encoded_size() + list.outcome.encoded_size();
+    if content_size + 1 > u8::MAX as usize {
+        buf.put_u8(codec::FORMATCODE_LIST32);
+        buf.put_u32((content_size + 4) as u32); // +4 for 4 byte count
+        buf.put_u32(TransactionalState::FIELD_COUNT as u32);
+    } else {
+        buf.put_u8(codec::FORMATCODE_LIST8);
+        buf.put_u8((content_size + 1) as u8);
+        buf.put_u8(TransactionalState::FIELD_COUNT as u8);
+    }
+    list.txn_id.encode(buf);
+    list.outcome.encode(buf);
+}
+impl DecodeFormatted for TransactionalState {
+    fn decode_with_format(input: &[u8], fmt: u8) -> Result<(&[u8], Self), AmqpParseError> {
+        validate_code!(fmt, codec::FORMATCODE_DESCRIBED);
+        let (input, descriptor) = Descriptor::decode(input)?;
+        let is_match = match descriptor {
+            Descriptor::Ulong(val) => val == 52,
+            Descriptor::Symbol(ref sym) => sym.as_bytes() == b"amqp:transactional-state:list",
+        };
+        if !is_match {
+            Err(AmqpParseError::InvalidDescriptor(descriptor))
+        } else {
+            decode_transactional_state_inner(input)
+        }
+    }
+}
+impl Encode for TransactionalState {
+    fn encoded_size(&self) -> usize {
+        encoded_size_transactional_state_inner(self)
+    }
+    fn encode(&self, buf: &mut BytesMut) {
+        encode_transactional_state_inner(self, buf)
+    }
+}
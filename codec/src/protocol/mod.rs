@@ -27,6 +27,11 @@ impl CompoundHeader {
     pub fn empty() -> CompoundHeader {
         CompoundHeader { size: 0, count: 0 }
     }
+
+    /// Number of items to preallocate, every item takes at least one byte
+    pub fn capacity(&self) -> usize {
+        std::cmp::min(self.count, self.size) as usize
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
@@ -205,12 +210,127 @@ impl Encode for DistributionMode {
     }
 }
 
+/// Sasl mechanism
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SaslMechanism {
+    Plain,
+    Anonymous,
+    External,
+    ScramSha1,
+    ScramSha256,
+    Other(Symbol),
+}
+
+impl SaslMechanism {
+    /// Mechanism name as it is sent over the wire
+    pub fn as_str(&self) -> &str {
+        match self {
+            SaslMechanism::Plain => "PLAIN",
+            SaslMechanism::Anonymous => "ANONYMOUS",
+            SaslMechanism::External => "EXTERNAL",
+            SaslMechanism::ScramSha1 => "SCRAM-SHA-1",
+            SaslMechanism::ScramSha256 => "SCRAM-SHA-256",
+            SaslMechanism::Other(ref v) => v.as_str(),
+        }
+    }
+}
+
+impl fmt::Display for SaslMechanism {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<'a> From<&'a str> for SaslMechanism {
+    fn from(s: &'a str) -> SaslMechanism {
+        match s {
+            "PLAIN" => SaslMechanism::Plain,
+            "ANONYMOUS" => SaslMechanism::Anonymous,
+            "EXTERNAL" => SaslMechanism::External,
+            "SCRAM-SHA-1" => SaslMechanism::ScramSha1,
+            "SCRAM-SHA-256" => SaslMechanism::ScramSha256,
+            _ => SaslMechanism::Other(Symbol::from_slice(s)),
+        }
+    }
+}
+
+impl From<String> for SaslMechanism {
+    fn from(s: String) -> SaslMechanism {
+        SaslMechanism::from(s.as_str())
+    }
+}
+
+impl<'a> From<&'a Symbol> for SaslMechanism {
+    fn from(s: &'a Symbol) -> SaslMechanism {
+        match SaslMechanism::from(s.as_str()) {
+            SaslMechanism::Other(_) => SaslMechanism::Other(s.clone()),
+            mech => mech,
+        }
+    }
+}
+
+impl From<Symbol> for SaslMechanism {
+    fn from(s: Symbol) -> SaslMechanism {
+        SaslMechanism::from(&s)
+    }
+}
+
+impl From<SaslMechanism> for Symbol {
+    fn from(mech: SaslMechanism) -> Symbol {
+        match mech {
+            SaslMechanism::Plain => Symbol::from_static("PLAIN"),
+            SaslMechanism::Anonymous => Symbol::from_static("ANONYMOUS"),
+            SaslMechanism::External => Symbol::from_static("EXTERNAL"),
+            SaslMechanism::ScramSha1 => Symbol::from_static("SCRAM-SHA-1"),
+            SaslMechanism::ScramSha256 => Symbol::from_static("SCRAM-SHA-256"),
+            SaslMechanism::Other(v) => v,
+        }
+    }
+}
+
+impl SaslMechanisms {
+    /// Parsed list of mechanisms offered by the server
+    pub fn mechanisms(&self) -> Vec<SaslMechanism> {
+        self.sasl_server_mechanisms
+            .iter()
+            .map(SaslMechanism::from)
+            .collect()
+    }
+}
+
 impl SaslInit {
+    /// Parsed sasl mechanism selected by the client
+    pub fn sasl_mechanism(&self) -> SaslMechanism {
+        SaslMechanism::from(&self.mechanism)
+    }
+
     pub fn prepare_response(authz_id: &str, authn_id: &str, password: &str) -> Bytes {
         Bytes::from(format!("{}\x00{}\x00{}", authz_id, authn_id, password))
     }
 }
 
+impl TargetTerminus {
+    /// Target address, coordinator does not have address
+    pub fn address(&self) -> Option<&Address> {
+        match self {
+            TargetTerminus::Target(ref target) => target.address(),
+            TargetTerminus::Coordinator(_) => None,
+        }
+    }
+}
+
+impl From<Target> for TargetTerminus {
+    fn from(target: Target) -> TargetTerminus {
+        TargetTerminus::Target(target)
+    }
+}
+
+impl From<Coordinator> for TargetTerminus {
+    fn from(coordinator: Coordinator) -> TargetTerminus {
+        TargetTerminus::Coordinator(coordinator)
+    }
+}
+
 impl Default for Properties {
     fn default() -> Properties {
         Properties {
@@ -4,6 +4,43 @@ use ntex_bytes::ByteString;
 
 use super::Str;
 
+/// Well-known symbols commonly used in connection, session and link negotiation:
+/// capabilities, filters, connection properties, sasl mechanisms, content types
+/// and message annotations.
+///
+/// Symbols are grouped by length, lookup compares only symbols of the same length.
+fn known_symbol(s: &str) -> Option<&'static str> {
+    let symbols: &[&'static str] = match s.len() {
+        5 => &["topic", "queue", "PLAIN"],
+        7 => &["product", "version"],
+        8 => &["platform", "EXTERNAL"],
+        9 => &["ANONYMOUS"],
+        10 => &["text/plain"],
+        11 => &["SHARED-SUBS", "SCRAM-SHA-1"],
+        12 => &["x-opt-offset"],
+        13 => &["SCRAM-SHA-256"],
+        14 => &["x-opt-jms-dest"],
+        15 => &["ANONYMOUS-RELAY"],
+        16 => &["DELAYED_DELIVERY", "application/json"],
+        18 => &["x-opt-jms-msg-type", "x-opt-jms-reply-to"],
+        19 => &["x-opt-enqueued-time", "x-opt-partition-key"],
+        20 => &["x-opt-delivery-count"],
+        21 => &["x-opt-sequence-number"],
+        24 => &["application/octet-stream"],
+        28 => &[
+            "com.microsoft:session-filter",
+            "x-opt-scheduled-enqueue-time",
+        ],
+        29 => &["sole-connection-for-container"],
+        31 => &["apache.org:no-local-filter:list"],
+        33 => &["apache.org:selector-filter:string"],
+        43 => &["apache.org:legacy-amqp-topic-binding:string"],
+        44 => &["apache.org:legacy-amqp-direct-binding:string"],
+        _ => return None,
+    };
+    symbols.iter().find(|sym| **sym == s).copied()
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Display)]
 pub struct Symbol(pub Str);
 
@@ -16,6 +53,15 @@ impl Symbol {
         Symbol(Str::ByteStr(ByteString::from(s)))
     }
 
+    /// Create symbol from slice, well-known symbols do not allocate
+    pub fn intern(s: &str) -> Symbol {
+        if let Some(sym) = known_symbol(s) {
+            Symbol::from_static(sym)
+        } else {
+            Symbol::from_slice(s)
+        }
+    }
+
     pub fn as_bytes(&self) -> &[u8] {
         self.0.as_bytes()
     }
@@ -54,6 +54,10 @@ impl<T> Cell<T> {
     pub(crate) fn get_mut(&self) -> &mut T {
         unsafe { &mut *self.inner.as_ref().get() }
     }
+
+    pub(crate) fn ptr_eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
 }
 
 impl<T: std::fmt::Debug> std::fmt::Debug for WeakCell<T> {
@@ -1,11 +1,13 @@
+use std::fmt;
+
 use ntex::codec::{AsyncRead, AsyncWrite};
 use ntex::framed::{Dispatcher as IoDispatcher, State as IoState, Timer};
-use ntex::service::{fn_service, Service};
-use ntex::util::Ready;
+use ntex::service::{fn_service, IntoService, Service};
+use ntex::util::{Bytes, Ready};
 
 use crate::codec::{AmqpCodec, AmqpFrame};
-use crate::error::{DispatcherError, LinkError};
-use crate::{dispatcher::Dispatcher, Configuration, Connection, State};
+use crate::error::{DispatcherError, Error, LinkError};
+use crate::{dispatcher::Dispatcher, types, Configuration, Connection, ControlFrame, State};
 
 /// Mqtt client
 pub struct Client<Io, St = ()> {
@@ -16,6 +18,7 @@ pub struct Client<Io, St = ()> {
     keepalive: u16,
     remote_config: Configuration,
     timer: Timer,
+    sasl_data: Option<Bytes>,
     st: State<St>,
 }
 
@@ -41,6 +44,7 @@ where
             keepalive,
             remote_config,
             timer,
+            sasl_data: None,
             st: State::new(()),
         }
     }
@@ -57,6 +61,17 @@ where
         self.connection.clone()
     }
 
+    /// Additional data of sasl outcome sent by server
+    ///
+    /// For scram authentication it contains verified `server-final-message`.
+    pub fn sasl_additional_data(&self) -> Option<&Bytes> {
+        self.sasl_data.as_ref()
+    }
+
+    pub(super) fn set_sasl_data(&mut self, data: Option<Bytes>) {
+        self.sasl_data = data;
+    }
+
     #[inline]
     /// Set connection state
     pub fn state<T: 'static>(self, st: T) -> Client<Io, T> {
@@ -68,20 +83,57 @@ where
             keepalive: self.keepalive,
             remote_config: self.remote_config,
             timer: self.timer,
+            sasl_data: self.sasl_data,
             st: State::new(st),
         }
     }
 
+    /// Deconstruct client into underlying io, io state and codec.
+    ///
+    /// Io state keeps already buffered data. Client is returned back
+    /// if connection has opened or opening sessions.
+    pub fn into_inner(self) -> Result<(Io, IoState, AmqpCodec<AmqpFrame>), Self> {
+        if self.connection.has_sessions() {
+            Err(self)
+        } else {
+            Ok((self.io, self.state, self.codec))
+        }
+    }
+
     /// Run client with default control messages handler.
     ///
     /// Default handler closes connection on any control message.
     pub async fn start_default(self) -> Result<(), DispatcherError> {
+        self.start(
+            fn_service(|_: ControlFrame| Ready::<_, LinkError>::Ok(())),
+            fn_service(|_: types::Link<St>| Ready::<_, LinkError>::Err(LinkError::force_detach())),
+        )
+        .await
+    }
+
+    /// Run client with provided control messages and links handlers.
+    ///
+    /// `control` service handles control frames (flow, detach, remote close, etc),
+    /// `service` handles links attached by remote peer.
+    pub async fn start<F, S, C, Ctl>(self, control: C, service: F) -> Result<(), DispatcherError>
+    where
+        C: IntoService<Ctl>,
+        Ctl: Service<Request = ControlFrame, Response = ()> + 'static,
+        Ctl::Error: fmt::Debug + 'static,
+        Ctl::Future: 'static,
+        F: IntoService<S>,
+        S: Service<Request = types::Link<St>, Response = ()> + 'static,
+        S::Error: fmt::Debug + 'static,
+        S::Future: 'static,
+        Error: From<S::Error> + From<Ctl::Error>,
+    {
+        let sink = self.connection.clone();
         let dispatcher = Dispatcher::new(
             self.st,
             self.connection,
-            fn_service(|_| Ready::<_, LinkError>::Err(LinkError::force_detach())),
-            fn_service(|_| Ready::<_, LinkError>::Ok(())),
-            self.remote_config.timeout_remote_secs(),
+            service.into_service(),
+            control.into_service(),
+            self.remote_config.timeout_remote_millis(),
         )
         .map(|_| Option::<AmqpFrame>::None);
 
@@ -91,6 +143,13 @@ where
             } else {
                 0
             })
-            .await
+            .await?;
+
+        if sink.0.is_closing() {
+            Ok(())
+        } else {
+            // unexpected eof
+            Err(DispatcherError::Disconnected)
+        }
     }
 }
@@ -5,7 +5,7 @@ use ntex::connect::{self, Address, Connect};
 use ntex::framed::{State, Timer};
 use ntex::rt::time::delay_for;
 use ntex::service::Service;
-use ntex::util::{select, ByteString, Either};
+use ntex::util::{select, ByteString, Bytes, Either, PoolId};
 
 #[cfg(feature = "openssl")]
 use ntex::connect::openssl::{OpensslConnector, SslConnector};
@@ -13,11 +13,14 @@ use ntex::connect::openssl::{OpensslConnector, SslConnector};
 #[cfg(feature = "rustls")]
 use ntex::connect::rustls::{ClientConfig, RustlsConnector};
 
-use crate::codec::protocol::{Frame, Milliseconds, ProtocolId, SaslCode, SaslFrameBody, SaslInit};
-use crate::codec::{types::Symbol, AmqpCodec, AmqpFrame, ProtocolIdCodec, SaslFrame};
+use crate::codec::protocol::{
+    Frame, Milliseconds, ProtocolId, SaslCode, SaslFrameBody, SaslInit, SaslMechanism, SaslResponse,
+};
+use crate::codec::types::{Symbol, Variant};
+use crate::codec::{AmqpCodec, AmqpFrame, ProtocolIdCodec, SaslFrame};
 use crate::{error::ProtocolIdError, Configuration, Connection};
 
-use super::{connection::Client, error::ConnectError, SaslAuth};
+use super::{connection::Client, error::ConnectError, scram::ScramSha256, SaslAuth};
 
 /// Amqp client connector
 pub struct Connector<A, T> {
@@ -28,6 +31,7 @@ pub struct Connector<A, T> {
     lw: u16,
     read_hw: u16,
     write_hw: u16,
+    pool: PoolId,
     timer: Timer,
     _t: PhantomData<A>,
 }
@@ -43,6 +47,7 @@ impl<A> Connector<A, ()> {
             lw: 1024,
             read_hw: 8 * 1024,
             write_hw: 8 * 1024,
+            pool: PoolId::DEFAULT,
             config: Configuration::default(),
             timer: Timer::with(Duration::from_secs(1)),
             _t: PhantomData,
@@ -81,9 +86,13 @@ where
 
     /// Set idle time-out for the connection in seconds.
     ///
+    /// Connection gets closed if no frames are received within idle time-out,
+    /// zero disables time-out. Heartbeats are sent only if remote peer
+    /// advertises its own idle time-out, local time-out is enforced either way.
+    ///
     /// By default idle time-out is set to 120 seconds
     pub fn idle_timeout(&mut self, timeout: u16) -> &mut Self {
-        self.config.idle_time_out = (timeout * 1000) as Milliseconds;
+        self.config.idle_time_out = timeout as Milliseconds * 1000;
         self
     }
 
@@ -95,6 +104,52 @@ where
         self
     }
 
+    /// Set connection property, properties are sent in `Open` frame
+    ///
+    /// Well-known properties are `product`, `version` and `platform`.
+    pub fn property<K, V>(&mut self, key: K, value: V) -> &mut Self
+    where
+        K: Into<Symbol>,
+        V: Into<Variant>,
+    {
+        self.config.property(key, value);
+        self
+    }
+
+    /// Reply to remote `Close` frame automatically.
+    ///
+    /// By default automatic reply is enabled
+    pub fn auto_close_reply(&mut self, enabled: bool) -> &mut Self {
+        self.config.auto_close_reply = enabled;
+        self
+    }
+
+    /// Set close handshake timeout in seconds.
+    ///
+    /// Connection gets dropped if close handshake does not complete in time.
+    ///
+    /// By default close timeout is set to 10 seconds
+    pub fn close_timeout(&mut self, timeout: u16) -> &mut Self {
+        self.config.close_timeout(timeout);
+        self
+    }
+
+    /// Set max link handle value for sessions.
+    ///
+    /// By default handle max is set to `u32::MAX`
+    pub fn handle_max(&mut self, max: u32) -> &mut Self {
+        self.config.handle_max(max);
+        self
+    }
+
+    /// Set number of sessions preallocated for the connection.
+    ///
+    /// By default storage for 8 sessions is preallocated
+    pub fn session_capacity(&mut self, capacity: usize) -> &mut Self {
+        self.config.session_capacity(capacity);
+        self
+    }
+
     /// Set handshake timeout in milliseconds.
     ///
     /// Handshake includes `connect` packet and response `connect-ack`.
@@ -120,6 +175,12 @@ where
     #[inline]
     /// Set read/write buffer params
     ///
+    /// Larger read buffer reduces syscalls of bulk transfers at the cost of
+    /// memory per connection. Params are applied to memory pool set by
+    /// `memory_pool()`, default memory pool params are not changed.
+    /// Pool params are global, they are shared by all clients and servers
+    /// that use the same memory pool.
+    ///
     /// By default read buffer is 8kb, write buffer is 8kb
     pub fn buffer_params(
         mut self,
@@ -130,14 +191,34 @@ where
         self.read_hw = max_read_buf_size;
         self.write_hw = max_write_buf_size;
         self.lw = min_buf_size;
+        self.apply_pool_params();
+        self
+    }
+
+    /// Set memory pool for read and write buffers of connection
+    ///
+    /// By default `PoolId::DEFAULT` pool is used
+    pub fn memory_pool(mut self, id: PoolId) -> Self {
+        self.pool = id;
+        self.apply_pool_params();
         self
     }
 
+    /// Apply buffer params to memory pool, default pool is not changed
+    fn apply_pool_params(&self) {
+        if self.pool != PoolId::DEFAULT {
+            self.pool
+                .set_read_params(self.read_hw as u32, self.lw as u32)
+                .set_write_params(self.write_hw as u32, self.lw as u32);
+        }
+    }
+
     #[inline]
     #[doc(hidden)]
     #[deprecated(since = "0.4.3")]
     pub fn low_watermark(mut self, lw: u16) -> Self {
         self.lw = lw;
+        self.apply_pool_params();
         self
     }
 
@@ -146,6 +227,7 @@ where
     #[deprecated(since = "0.4.3")]
     pub fn read_high_watermark(mut self, hw: u16) -> Self {
         self.read_hw = hw;
+        self.apply_pool_params();
         self
     }
 
@@ -154,6 +236,7 @@ where
     #[deprecated(since = "0.4.3")]
     pub fn write_high_watermark(mut self, hw: u16) -> Self {
         self.write_hw = hw;
+        self.apply_pool_params();
         self
     }
 
@@ -171,6 +254,7 @@ where
             lw: self.lw,
             read_hw: self.read_hw,
             write_hw: self.write_hw,
+            pool: self.pool,
             timer: self.timer,
             _t: PhantomData,
         }
@@ -187,6 +271,7 @@ where
             lw: self.lw,
             read_hw: self.read_hw,
             write_hw: self.write_hw,
+            pool: self.pool,
             timer: self.timer,
             _t: PhantomData,
         }
@@ -205,6 +290,7 @@ where
             lw: self.lw,
             read_hw: self.read_hw,
             write_hw: self.write_hw,
+            pool: self.pool,
             timer: self.timer,
             _t: PhantomData,
         }
@@ -238,14 +324,22 @@ where
     {
         trace!("Negotiation client protocol id: Amqp");
 
+        let state = self.io_state();
+
+        _connect_plain(io, state, self.config.clone(), self.timer.clone())
+    }
+
+    fn io_state(&self) -> State {
         let state = State::with_params(
             self.read_hw,
             self.write_hw,
             self.lw,
             self.disconnect_timeout,
         );
-
-        _connect_plain(io, state, self.config.clone(), self.timer.clone())
+        if self.pool != PoolId::DEFAULT {
+            state.set_memory_pool(self.pool.pool_ref());
+        }
+        state
     }
 
     fn _connect(
@@ -255,12 +349,7 @@ where
         let fut = self.connector.call(Connect::new(address));
         let config = self.config.clone();
         let timer = self.timer.clone();
-        let state = State::with_params(
-            self.read_hw,
-            self.write_hw,
-            self.lw,
-            self.disconnect_timeout,
-        );
+        let state = self.io_state();
 
         async move {
             trace!("Negotiation client protocol id: Amqp");
@@ -271,6 +360,8 @@ where
     }
 
     /// Connect to amqp server
+    ///
+    /// SCRAM-SHA-256 mechanism is used if server offers it, PLAIN otherwise.
     pub fn connect_sasl(
         &self,
         addr: A,
@@ -293,6 +384,8 @@ where
     }
 
     /// Negotiate amqp sasl protocol over opened socket
+    ///
+    /// SCRAM-SHA-256 mechanism is used if server offers it, PLAIN otherwise.
     pub fn negotiate_sasl<Io>(
         &self,
         io: Io,
@@ -305,12 +398,7 @@ where
 
         let config = self.config.clone();
         let timer = self.timer.clone();
-        let state = State::with_params(
-            self.read_hw,
-            self.write_hw,
-            self.lw,
-            self.disconnect_timeout,
-        );
+        let state = self.io_state();
 
         _connect_sasl(io, state, auth, config, timer)
     }
@@ -323,12 +411,7 @@ where
         let fut = self.connector.call(Connect::new(addr));
         let config = self.config.clone();
         let timer = self.timer.clone();
-        let state = State::with_params(
-            self.read_hw,
-            self.write_hw,
-            self.lw,
-            self.disconnect_timeout,
-        );
+        let state = self.io_state();
 
         async move { _connect_sasl(fut.await?, state, auth, config, timer).await }
     }
@@ -344,6 +427,8 @@ async fn _connect_sasl<T>(
 where
     T: AsyncRead + AsyncWrite + Unpin + 'static,
 {
+    config.validate()?;
+
     trace!("Negotiation client protocol id: AmqpSasl");
 
     state
@@ -367,45 +452,107 @@ where
         }));
     }
 
-    let codec = AmqpCodec::<SaslFrame>::new();
+    let codec = AmqpCodec::<SaslFrame>::new().max_size(config.handshake_max_frame_size as usize);
 
     // processing sasl-mechanisms
-    let _ = state
+    let sasl_frame = state
         .next(&mut io, &codec)
         .await
         .map_err(ConnectError::from)
         .and_then(|res| res.ok_or(ConnectError::Disconnected))?;
+    #[cfg(feature = "sasl-trace")]
+    crate::utils::trace_sasl("incoming", &sasl_frame.body);
 
-    let initial_response =
-        SaslInit::prepare_response(&auth.authz_id, &auth.authn_id, &auth.password);
-
-    let sasl_init = SaslInit {
-        hostname: config.hostname.clone(),
-        mechanism: Symbol::from("PLAIN"),
-        initial_response: Some(initial_response),
+    let mechanisms = if let SaslFrameBody::SaslMechanisms(ref mechanisms) = sasl_frame.body {
+        mechanisms.mechanisms()
+    } else {
+        Vec::new()
     };
+    trace!("Server sasl mechanisms: {:?}", mechanisms);
 
-    state.send(&mut io, &codec, sasl_init.into()).await?;
-
-    // processing sasl-outcome
-    let sasl_frame = state
-        .next(&mut io, &codec)
-        .await
-        .map_err(ConnectError::from)
-        .and_then(|res| res.ok_or(ConnectError::Disconnected))?;
+    // prefer scram if server supports it
+    let mut scram = if mechanisms.contains(&SaslMechanism::ScramSha256) {
+        Some(ScramSha256::new(&auth.authn_id, &auth.password))
+    } else {
+        None
+    };
 
-    if let SaslFrame {
-        body: SaslFrameBody::SaslOutcome(outcome),
-    } = sasl_frame
-    {
-        if outcome.code() != SaslCode::Ok {
-            return Err(ConnectError::Sasl(outcome.code()));
+    let sasl_init = if let Some(ref scram) = scram {
+        SaslInit {
+            hostname: config.hostname.clone(),
+            mechanism: SaslMechanism::ScramSha256.into(),
+            initial_response: Some(scram.client_first()),
         }
     } else {
-        return Err(ConnectError::Disconnected);
-    }
+        SaslInit {
+            hostname: config.hostname.clone(),
+            mechanism: SaslMechanism::Plain.into(),
+            initial_response: Some(SaslInit::prepare_response(
+                &auth.authz_id,
+                &auth.authn_id,
+                &auth.password,
+            )),
+        }
+    };
+
+    let mechanism = sasl_init.mechanism.clone();
+    let frame: SaslFrame = sasl_init.into();
+    #[cfg(feature = "sasl-trace")]
+    crate::utils::trace_sasl("outgoing", &frame.body);
+    state.send(&mut io, &codec, frame).await?;
+
+    // processing sasl challenges and sasl-outcome
+    let mut verified = false;
+    let sasl_data = loop {
+        let sasl_frame = state
+            .next(&mut io, &codec)
+            .await
+            .map_err(ConnectError::from)
+            .and_then(|res| res.ok_or(ConnectError::Disconnected))?;
+        #[cfg(feature = "sasl-trace")]
+        crate::utils::trace_sasl("incoming", &sasl_frame.body);
+
+        match sasl_frame.body {
+            SaslFrameBody::SaslChallenge(challenge) => {
+                let scram = scram.as_mut().ok_or(ConnectError::Sasl(SaslCode::Auth))?;
+                let response = if scram.is_started() {
+                    // server-final-message sent as challenge
+                    scram.verify_server_final(&challenge.challenge)?;
+                    verified = true;
+                    Bytes::new()
+                } else {
+                    scram.client_final(&challenge.challenge)?
+                };
+                let frame: SaslFrame = SaslResponse { response }.into();
+                #[cfg(feature = "sasl-trace")]
+                crate::utils::trace_sasl("outgoing", &frame.body);
+                state.send(&mut io, &codec, frame).await?;
+            }
+            SaslFrameBody::SaslOutcome(outcome) => {
+                if outcome.code() != SaslCode::Ok {
+                    return Err(ConnectError::Sasl(outcome.code()));
+                }
+                if let Some(ref scram) = scram {
+                    if !verified {
+                        let data = outcome
+                            .additional_data
+                            .as_ref()
+                            .ok_or(ConnectError::Sasl(SaslCode::Auth))?;
+                        scram.verify_server_final(data)?;
+                    }
+                }
+                break outcome.additional_data;
+            }
+            _ => return Err(ConnectError::Disconnected),
+        }
+    };
 
-    _connect_plain(io, state, config, timer).await
+    let mut client = _connect_plain(io, state, config, timer).await?;
+    client.set_sasl_data(sasl_data);
+    client
+        .sink()
+        .set_sasl(Some(SaslMechanism::from(&mechanism)), None);
+    Ok(client)
 }
 
 async fn _connect_plain<T>(
@@ -417,6 +564,8 @@ async fn _connect_plain<T>(
 where
     T: AsyncRead + AsyncWrite + Unpin + 'static,
 {
+    config.validate()?;
+
     trace!("Negotiation client protocol id: Amqp");
 
     state
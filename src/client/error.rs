@@ -1,6 +1,7 @@
 use ntex::util::Either;
 
 use crate::codec::{protocol, AmqpCodecError, AmqpFrame, ProtocolIdError};
+use crate::error::ConfigError;
 
 /// Errors which can occur when attempting to handle amqp client connection.
 #[derive(Debug, Display, From)]
@@ -26,11 +27,24 @@ pub enum ConnectError {
     /// Connect error
     #[display(fmt = "Connect error: {}", _0)]
     Connect(ntex::connect::ConnectError),
+    /// Invalid connection configuration
+    #[display(fmt = "Configuration error: {}", _0)]
+    Config(ConfigError),
     /// Unexpected io error
     Io(std::io::Error),
 }
 
-impl std::error::Error for ConnectError {}
+impl std::error::Error for ConnectError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConnectError::Codec(err) => Some(err),
+            ConnectError::ProtocolNegotiation(err) => Some(err),
+            ConnectError::Config(err) => Some(err),
+            ConnectError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
 
 impl From<Either<AmqpCodecError, std::io::Error>> for ConnectError {
     fn from(err: Either<AmqpCodecError, std::io::Error>) -> Self {
@@ -3,6 +3,7 @@ use ntex::util::ByteString;
 mod connection;
 mod connector;
 mod error;
+mod scram;
 
 pub use self::connection::Client;
 pub use self::connector::Connector;
@@ -0,0 +1,207 @@
+//! SCRAM-SHA-256 client side exchange (RFC 5802, RFC 7677)
+use hmac::{Hmac, Mac, NewMac};
+use ntex::util::Bytes;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::codec::protocol::SaslCode;
+
+use super::error::ConnectError;
+
+/// Channel binding is not supported
+const GS2_HEADER: &str = "n,,";
+
+/// Max iteration count accepted from server, `Hi()` cost is linear to it
+const MAX_ITERATIONS: u32 = 100_000;
+
+pub(super) struct ScramSha256 {
+    password: String,
+    client_nonce: String,
+    client_first_bare: String,
+    server_signature: Option<Vec<u8>>,
+}
+
+impl ScramSha256 {
+    pub(super) fn new(user: &str, password: &str) -> Self {
+        Self::with_nonce(user, password, Uuid::new_v4().to_simple().to_string())
+    }
+
+    fn with_nonce(user: &str, password: &str, client_nonce: String) -> Self {
+        let client_first_bare = format!("n={},r={}", escape_username(user), client_nonce);
+
+        ScramSha256 {
+            client_nonce,
+            client_first_bare,
+            password: password.to_string(),
+            server_signature: None,
+        }
+    }
+
+    /// Check if `client-final-message` is prepared
+    pub(super) fn is_started(&self) -> bool {
+        self.server_signature.is_some()
+    }
+
+    /// `client-first-message`
+    pub(super) fn client_first(&self) -> Bytes {
+        Bytes::from(format!("{}{}", GS2_HEADER, self.client_first_bare))
+    }
+
+    /// Process `server-first-message` and build `client-final-message`
+    pub(super) fn client_final(&mut self, server_first: &[u8]) -> Result<Bytes, ConnectError> {
+        let server_first = std::str::from_utf8(server_first).map_err(|_| sasl_error())?;
+
+        let mut nonce = None;
+        let mut salt = None;
+        let mut iterations = None;
+        for attr in server_first.split(',') {
+            if let Some(v) = attr.strip_prefix("r=") {
+                nonce = Some(v);
+            } else if let Some(v) = attr.strip_prefix("s=") {
+                salt = base64::decode(v).ok();
+            } else if let Some(v) = attr.strip_prefix("i=") {
+                iterations = v.parse::<u32>().ok();
+            } else if attr.starts_with("m=") {
+                // mandatory extensions are not supported
+                return Err(sasl_error());
+            }
+        }
+
+        let nonce = nonce.ok_or_else(sasl_error)?;
+        let salt = salt.ok_or_else(sasl_error)?;
+        let iterations = iterations.filter(|i| *i > 0).ok_or_else(sasl_error)?;
+        if iterations > MAX_ITERATIONS {
+            log::trace!("Server iteration count is too large: {}", iterations);
+            return Err(sasl_error());
+        }
+
+        if !nonce.starts_with(&self.client_nonce) || nonce.len() == self.client_nonce.len() {
+            log::trace!("Server nonce does not extend client nonce");
+            return Err(sasl_error());
+        }
+
+        let client_final_without_proof = format!("c={},r={}", base64::encode(GS2_HEADER), nonce);
+        let auth_message = format!(
+            "{},{},{}",
+            self.client_first_bare, server_first, client_final_without_proof
+        );
+
+        let salted_password = hi(self.password.as_bytes(), &salt, iterations);
+        let client_key = hmac(&salted_password, b"Client Key");
+        let stored_key = Sha256::digest(&client_key);
+        let client_signature = hmac(&stored_key, auth_message.as_bytes());
+        let proof: Vec<u8> = client_key
+            .iter()
+            .zip(client_signature.iter())
+            .map(|(k, s)| k ^ s)
+            .collect();
+
+        let server_key = hmac(&salted_password, b"Server Key");
+        self.server_signature = Some(hmac(&server_key, auth_message.as_bytes()));
+
+        Ok(Bytes::from(format!(
+            "{},p={}",
+            client_final_without_proof,
+            base64::encode(&proof)
+        )))
+    }
+
+    /// Verify `server-final-message`
+    pub(super) fn verify_server_final(&self, server_final: &[u8]) -> Result<(), ConnectError> {
+        let server_final = std::str::from_utf8(server_final).map_err(|_| sasl_error())?;
+
+        if let Some(err) = server_final.strip_prefix("e=") {
+            log::trace!("Server reported scram error: {}", err);
+            return Err(sasl_error());
+        }
+
+        let verifier = server_final
+            .split(',')
+            .find_map(|attr| attr.strip_prefix("v="))
+            .and_then(|v| base64::decode(v).ok())
+            .ok_or_else(sasl_error)?;
+
+        match self.server_signature {
+            Some(ref signature) if *signature == verifier => Ok(()),
+            _ => {
+                log::trace!("Server signature mismatch");
+                Err(sasl_error())
+            }
+        }
+    }
+}
+
+fn sasl_error() -> ConnectError {
+    ConnectError::Sasl(SaslCode::Auth)
+}
+
+fn escape_username(user: &str) -> String {
+    user.replace('=', "=3D").replace(',', "=2C")
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC can take key of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// `Hi()` function, PBKDF2 with HMAC-SHA-256
+fn hi(password: &[u8], salt: &[u8], iterations: u32) -> Vec<u8> {
+    let mut input = salt.to_vec();
+    input.extend_from_slice(&1u32.to_be_bytes());
+
+    let mut u = hmac(password, &input);
+    let mut result = u.clone();
+    for _ in 1..iterations {
+        u = hmac(password, &u);
+        result.iter_mut().zip(u.iter()).for_each(|(r, u)| *r ^= u);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rfc7677_exchange() {
+        let mut scram =
+            ScramSha256::with_nonce("user", "pencil", "rOprNGfwEbeRWgbNEkqO".to_string());
+        assert_eq!(
+            scram.client_first(),
+            Bytes::from_static(b"n,,n=user,r=rOprNGfwEbeRWgbNEkqO")
+        );
+
+        let client_final = scram
+            .client_final(
+                b"r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,\
+                  s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096",
+            )
+            .unwrap();
+        assert_eq!(
+            client_final,
+            Bytes::from_static(
+                b"c=biws,r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,\
+                  p=dHzbZapWIk4jUhN+Ute9ytag9zjfMHgsqmmiz7AndVQ="
+            )
+        );
+
+        assert!(scram
+            .verify_server_final(b"v=6rriTRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4=")
+            .is_ok());
+        assert!(scram
+            .verify_server_final(b"v=AAAATRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4=")
+            .is_err());
+    }
+
+    #[test]
+    fn test_iterations_limit() {
+        let mut scram = ScramSha256::with_nonce("user", "pencil", "nonce".to_string());
+        assert!(scram
+            .client_final(b"r=nonce-server,s=W22ZaJ0SNY7soEsUEjb6gQ==,i=100001")
+            .is_err());
+        assert!(scram
+            .client_final(b"r=nonce-server,s=W22ZaJ0SNY7soEsUEjb6gQ==,i=0")
+            .is_err());
+    }
+}
@@ -0,0 +1,86 @@
+//! Message body compression according to `content-encoding` property
+use std::io::{self, Read, Write};
+
+use flate2::{read::GzDecoder, read::ZlibDecoder, write::GzEncoder, write::ZlibEncoder};
+use ntex::util::Bytes;
+
+use crate::codec::types::Symbol;
+use crate::codec::Message;
+
+/// Supported content encodings of message body
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ContentEncoding {
+    /// `gzip` encoding
+    Gzip,
+    /// `deflate` encoding, zlib format
+    Deflate,
+}
+
+impl ContentEncoding {
+    /// Get encoding from `content-encoding` property value
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "gzip" => Some(ContentEncoding::Gzip),
+            "deflate" => Some(ContentEncoding::Deflate),
+            _ => None,
+        }
+    }
+
+    /// Value of `content-encoding` property
+    pub fn name(&self) -> &'static str {
+        match self {
+            ContentEncoding::Gzip => "gzip",
+            ContentEncoding::Deflate => "deflate",
+        }
+    }
+
+    /// Compress data
+    pub fn compress(&self, data: &[u8]) -> io::Result<Bytes> {
+        let compression = flate2::Compression::default();
+        let buf = match self {
+            ContentEncoding::Gzip => {
+                let mut enc = GzEncoder::new(Vec::new(), compression);
+                enc.write_all(data)?;
+                enc.finish()?
+            }
+            ContentEncoding::Deflate => {
+                let mut enc = ZlibEncoder::new(Vec::new(), compression);
+                enc.write_all(data)?;
+                enc.finish()?
+            }
+        };
+        Ok(Bytes::from(buf))
+    }
+
+    /// Decompress data
+    pub fn decompress(&self, data: &[u8]) -> io::Result<Bytes> {
+        let mut buf = Vec::new();
+        match self {
+            ContentEncoding::Gzip => GzDecoder::new(data).read_to_end(&mut buf)?,
+            ContentEncoding::Deflate => ZlibDecoder::new(data).read_to_end(&mut buf)?,
+        };
+        Ok(Bytes::from(buf))
+    }
+
+    /// Compress data sections of the message and set `content-encoding` property
+    ///
+    /// Data sections are replaced with single compressed section. Messages
+    /// without data sections or with content encoding already set are not modified.
+    pub(crate) fn compress_message(&self, msg: &mut Message) -> io::Result<()> {
+        if msg
+            .properties()
+            .and_then(|props| props.content_encoding())
+            .is_some()
+        {
+            return Ok(());
+        }
+
+        if let Some(data) = msg.body().data_concat() {
+            let data = self.compress(&data)?;
+            let encoding = Symbol::from_static(self.name());
+            msg.set_body(|body| body.set_data(data.clone()))
+                .set_properties(|props| props.content_encoding = Some(encoding.clone()));
+        }
+        Ok(())
+    }
+}
@@ -1,22 +1,31 @@
-use std::future::Future;
+use std::{future::Future, task::Context, time::Duration, time::Instant};
 
 use ntex::channel::{condition::Condition, condition::Waiter, oneshot};
 use ntex::framed::State;
-use ntex::util::{HashMap, Ready};
+use ntex::util::{ByteString, Either, HashMap, Ready};
 
 use crate::cell::Cell;
-use crate::codec::protocol::{Begin, Close, End, Error, Frame};
-use crate::codec::{AmqpCodec, AmqpCodecError, AmqpFrame};
-use crate::error::AmqpProtocolError;
-use crate::session::{Session, SessionInner};
+use crate::codec::protocol::{
+    Begin, Close, ConnectionError, End, Error, Frame, ProtocolId, SaslMechanism,
+};
+use crate::codec::{AmqpCodec, AmqpCodecError, AmqpFrame, Encode};
+use crate::error::{AmqpProtocolError, LinkError};
+use crate::server::Identity;
+use crate::session::{Session, SessionBuilder, SessionInfo, SessionInner};
+use crate::span::Span;
 use crate::Configuration;
 
+/// Max number of transfer frames written within one dispatcher poll
+const WRITE_BUDGET_FRAMES: usize = 64;
+/// Max number of transfer bytes written within one dispatcher poll
+const WRITE_BUDGET_BYTES: usize = 256 * 1024;
+
 #[derive(Clone)]
 pub struct Connection(pub(crate) Cell<ConnectionInner>);
 
 pub(crate) struct ConnectionInner {
     st: ConnectionState,
-    state: State,
+    pub(crate) state: State,
     codec: AmqpCodec<AmqpFrame>,
     pub(crate) sessions: slab::Slab<ChannelState>,
     pub(crate) sessions_map: HashMap<u16, usize>,
@@ -24,12 +33,40 @@ pub(crate) struct ConnectionInner {
     pub(crate) error: Option<AmqpProtocolError>,
     channel_max: usize,
     pub(crate) max_frame_size: usize,
+    auto_close_reply: bool,
+    pub(crate) transfers_paused: bool,
+    /// Session that writes pending transfers first on resume
+    resume_next: usize,
+    /// Transfer frames and bytes left within current dispatcher poll
+    write_frames: usize,
+    write_bytes: usize,
+    /// Pending transfers get written on next dispatcher poll
+    flush_pending: bool,
+    pub(crate) read_paused: bool,
+    close_timeout: u64,
+    /// Close handshake must complete before deadline, checked by dispatcher
+    close_deadline: Option<Instant>,
+    pub(crate) link_idle_timeout: u64,
+    idle_timeout: usize,
+    heartbeat: u64,
+    /// Time of last frame written to the connection
+    pub(crate) last_write: Instant,
+    handle_max: u32,
+    /// Size of partially received deliveries buffered by links
+    reassembly_size: usize,
+    reassembly_max: usize,
+    local_config: Configuration,
+    remote_config: Configuration,
+    /// Mechanism of sasl authentication preceding amqp protocol
+    sasl_mechanism: Option<SaslMechanism>,
+    /// Client identity authenticated by server's sasl authenticator
+    identity: Option<Identity>,
+    pub(crate) span: Span,
 }
 
 pub(crate) enum ChannelState {
     Opening(Option<oneshot::Sender<Session>>, Cell<ConnectionInner>),
     Established(Cell<SessionInner>),
-    #[allow(dead_code)]
     Closing(Option<oneshot::Sender<Result<(), AmqpProtocolError>>>),
 }
 
@@ -43,10 +80,37 @@ impl ChannelState {
 pub(crate) enum ConnectionState {
     Normal,
     Closing,
+    RemoteCloseWait,
     RemoteClose,
     Drop,
 }
 
+/// Connection close state
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionStatus {
+    /// Connection is open
+    Open,
+    /// `Close` frame is sent, waiting for remote peer's `Close`
+    Closing,
+    /// Remote peer closed connection
+    RemoteClosed,
+    /// Connection is dropped without close handshake
+    Dropped,
+}
+
+impl From<ConnectionState> for ConnectionStatus {
+    fn from(st: ConnectionState) -> Self {
+        match st {
+            ConnectionState::Normal => ConnectionStatus::Open,
+            ConnectionState::Closing => ConnectionStatus::Closing,
+            ConnectionState::RemoteCloseWait | ConnectionState::RemoteClose => {
+                ConnectionStatus::RemoteClosed
+            }
+            ConnectionState::Drop => ConnectionStatus::Dropped,
+        }
+    }
+}
+
 impl Connection {
     pub(crate) fn new(
         state: State,
@@ -57,17 +121,84 @@ impl Connection {
             state,
             codec: AmqpCodec::new(),
             st: ConnectionState::Normal,
-            sessions: slab::Slab::with_capacity(8),
+            sessions: slab::Slab::with_capacity(local_config.session_capacity),
             sessions_map: HashMap::default(),
             error: None,
             on_close: Condition::new(),
             channel_max: local_config.channel_max,
             max_frame_size: remote_config.max_frame_size as usize,
+            auto_close_reply: local_config.auto_close_reply,
+            transfers_paused: false,
+            resume_next: 0,
+            write_frames: WRITE_BUDGET_FRAMES,
+            write_bytes: WRITE_BUDGET_BYTES,
+            flush_pending: false,
+            read_paused: false,
+            close_timeout: local_config.close_timeout as u64,
+            close_deadline: None,
+            link_idle_timeout: local_config.link_idle_timeout as u64,
+            idle_timeout: local_config.timeout_secs(),
+            heartbeat: remote_config.timeout_remote_millis(),
+            last_write: Instant::now(),
+            handle_max: local_config.handle_max,
+            reassembly_size: 0,
+            reassembly_max: local_config.reassembly_buffer_max,
+            local_config: local_config.clone(),
+            remote_config: remote_config.clone(),
+            sasl_mechanism: None,
+            identity: None,
+            span: Span::connection(),
         }))
     }
 
+    /// Configuration advertised to remote peer
+    pub fn local_config(&self) -> &Configuration {
+        &self.0.get_ref().local_config
+    }
+
+    /// Configuration received from remote peer
+    pub fn remote_config(&self) -> &Configuration {
+        &self.0.get_ref().remote_config
+    }
+
+    /// Protocol negotiated during connection handshake
+    ///
+    /// Returns `ProtocolId::AmqpSasl` if sasl authentication preceded amqp protocol.
+    pub fn protocol(&self) -> ProtocolId {
+        if self.0.get_ref().sasl_mechanism.is_some() {
+            ProtocolId::AmqpSasl
+        } else {
+            ProtocolId::Amqp
+        }
+    }
+
+    /// Sasl mechanism used for connection authentication
+    ///
+    /// Returns `None` for plain amqp connection.
+    pub fn sasl_mechanism(&self) -> Option<&SaslMechanism> {
+        self.0.get_ref().sasl_mechanism.as_ref()
+    }
+
+    /// Identity of the client authenticated by server's sasl authenticator
+    ///
+    /// Returns `None` for anonymous connection, plain amqp or sasl
+    /// negotiation that is handled without authenticator.
+    pub fn identity(&self) -> Option<&Identity> {
+        self.0.get_ref().identity.as_ref()
+    }
+
+    pub(crate) fn set_sasl(&self, mechanism: Option<SaslMechanism>, identity: Option<Identity>) {
+        let inner = self.0.get_mut();
+        inner.sasl_mechanism = mechanism;
+        inner.identity = identity;
+    }
+
     #[inline]
     /// Force close connection
+    ///
+    /// Connection does not wait for close handshake, frames that are
+    /// already written to the write buffer (i.e. final `Close`) are
+    /// flushed before io gets shutdown.
     pub fn force_close(&self) {
         let inner = self.0.get_mut();
         inner.st = ConnectionState::Drop;
@@ -84,6 +215,18 @@ impl Connection {
         inner.error.is_none()
     }
 
+    #[inline]
+    /// Connection close state
+    pub fn status(&self) -> ConnectionStatus {
+        self.0.get_ref().st.into()
+    }
+
+    #[inline]
+    /// Check if local close handshake is in progress
+    pub fn is_closing(&self) -> bool {
+        self.0.get_ref().st == ConnectionState::Closing
+    }
+
     /// Get waiter for on_close event
     pub fn on_close(&self) -> Waiter {
         self.0.get_ref().on_close.wait()
@@ -94,25 +237,84 @@ impl Connection {
         self.0.get_ref().error.clone()
     }
 
+    /// Idle time-out in effect for the connection
+    ///
+    /// Connection gets closed if no frames are received from remote peer
+    /// within this period. Returns `None` if idle time-out is disabled.
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        secs(self.0.get_ref().idle_timeout)
+    }
+
+    /// Interval of heartbeat frames, derived from remote peer's idle time-out
+    ///
+    /// Returns `None` if remote peer does not require heartbeats.
+    pub fn heartbeat_interval(&self) -> Option<Duration> {
+        let heartbeat = self.0.get_ref().heartbeat;
+        if heartbeat > 0 {
+            Some(Duration::from_millis(heartbeat))
+        } else {
+            None
+        }
+    }
+
     /// Gracefully close connection
+    ///
+    /// Open connection sends `Close` frame and waits for remote peer's `Close`,
+    /// connection is dropped if reply is not received within close time-out.
+    /// If close time-out is disabled, connection is closed right after `Close` is sent.
+    /// If remote peer closed connection first, its `Close` gets answered.
     pub fn close(&self) -> impl Future<Output = Result<(), AmqpProtocolError>> {
-        self.0.get_ref().state.close();
+        self.0.get_mut().send_close(None);
+        self.close_io();
         Ready::Ok(())
     }
 
-    // TODO: implement
     /// Close connection with error
-    pub fn close_with_error<E>(
-        &self,
-        _err: E,
-    ) -> impl Future<Output = Result<(), AmqpProtocolError>>
+    pub fn close_with_error<E>(&self, err: E) -> impl Future<Output = Result<(), AmqpProtocolError>>
     where
         Error: From<E>,
     {
-        self.0.get_ref().state.close();
+        self.0.get_mut().send_close(Some(err.into()));
+        self.close_io();
         Ready::Ok(())
     }
 
+    /// Close io, or keep reading until remote `Close` reply if close time-out is set
+    fn close_io(&self) {
+        let inner = self.0.get_ref();
+        if inner.st != ConnectionState::Closing || inner.close_timeout == 0 {
+            inner.state.close();
+        }
+        self.start_close_timer();
+    }
+
+    /// Stop reading frames from the connection
+    ///
+    /// Incoming frames are left in socket buffers until `resume_reading()`
+    /// is called, heartbeats are still sent to remote peer.
+    pub fn pause_reading(&self) {
+        self.0.get_mut().read_paused = true;
+    }
+
+    /// Resume reading frames paused by `pause_reading()`
+    pub fn resume_reading(&self) {
+        let inner = self.0.get_mut();
+        if inner.read_paused {
+            inner.read_paused = false;
+            inner.state.wake_dispatcher();
+        }
+    }
+
+    /// Check if reading is paused
+    pub fn is_reading_paused(&self) -> bool {
+        self.0.get_ref().read_paused
+    }
+
+    /// Session builder
+    pub fn build_session(&self) -> SessionBuilder {
+        SessionBuilder::new(self.clone())
+    }
+
     /// Opens the session
     pub fn open_session(&self) -> impl Future<Output = Result<Session, AmqpProtocolError>> {
         let cell = self.0.clone();
@@ -141,7 +343,7 @@ impl Connection {
                         next_outgoing_id: 1,
                         incoming_window: std::u32::MAX,
                         outgoing_window: std::u32::MAX,
-                        handle_max: std::u32::MAX,
+                        handle_max: inner.handle_max,
                         offered_capabilities: None,
                         desired_capabilities: None,
                         properties: None,
@@ -154,6 +356,29 @@ impl Connection {
         }
     }
 
+    /// Check if both handles refer to the same connection
+    pub(crate) fn ptr_eq(&self, other: &Connection) -> bool {
+        self.0.ptr_eq(&other.0)
+    }
+
+    /// Established sessions with their links
+    pub fn sessions(&self) -> Vec<SessionInfo> {
+        self.0
+            .get_ref()
+            .sessions
+            .iter()
+            .filter_map(|(_, st)| match st {
+                ChannelState::Established(ref session) => Some(session.get_ref().info()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Check if connection has opened or opening sessions
+    pub fn has_sessions(&self) -> bool {
+        !self.0.get_ref().sessions.is_empty()
+    }
+
     /// Get session by remote id. This method panics if session does not exists or in opening/closing state.
     pub(crate) fn get_remote_session(&self, id: usize) -> Option<Cell<SessionInner>> {
         let inner = self.0.get_ref();
@@ -188,6 +413,7 @@ impl Connection {
             begin.next_outgoing_id(),
             begin.incoming_window(),
             begin.outgoing_window(),
+            begin.handle_max(),
         ));
         entry.insert(ChannelState::Established(session));
         inner.sessions_map.insert(channel_id, token);
@@ -197,7 +423,7 @@ impl Connection {
             next_outgoing_id: 1,
             incoming_window: std::u32::MAX,
             outgoing_window: begin.incoming_window(),
-            handle_max: std::u32::MAX,
+            handle_max: inner.handle_max,
             offered_capabilities: None,
             desired_capabilities: None,
             properties: None,
@@ -210,15 +436,229 @@ impl Connection {
             .map(|_| ())
     }
 
+    /// Set deadline for close handshake, dispatcher drops connection
+    /// if handshake does not complete within close timeout
+    pub(crate) fn start_close_timer(&self) {
+        let inner = self.0.get_mut();
+        if inner.close_timeout == 0 || inner.close_deadline.is_some() {
+            return;
+        }
+        inner.close_deadline = Some(Instant::now() + Duration::from_millis(inner.close_timeout));
+        inner.state.wake_dispatcher();
+    }
+
+    /// Deadline of close handshake
+    pub(crate) fn close_deadline(&self) -> Option<Instant> {
+        self.0.get_ref().close_deadline
+    }
+
+    /// Drop connection if close handshake is not completed
+    pub(crate) fn close_timeout_expired(&self) {
+        let st = self.0.get_ref().st;
+        if st == ConnectionState::Closing || st == ConnectionState::RemoteCloseWait {
+            log::trace!(
+                "Close handshake is not completed in {:?} ms, drop connection",
+                self.0.get_ref().close_timeout
+            );
+            self.0.get_mut().set_error(AmqpProtocolError::Disconnected);
+            self.force_close();
+        }
+    }
+
+    /// Hold back transfer frames, control frames are still written
+    pub(crate) fn pause_transfers(&self) {
+        self.0.get_mut().transfers_paused = true;
+    }
+
+    /// Resume writing transfer frames held back by `pause_transfers()`
+    pub(crate) fn resume_transfers(&self) {
+        self.0.get_mut().transfers_paused = false;
+        self.flush_transfers();
+    }
+
+    /// Reset write budget for new dispatcher poll
+    ///
+    /// Transfers held back by exhausted budget of previous poll get written.
+    pub(crate) fn poll_write_budget(&self, cx: &mut Context<'_>) {
+        let inner = self.0.get_mut();
+        inner.write_frames = WRITE_BUDGET_FRAMES;
+        inner.write_bytes = WRITE_BUDGET_BYTES;
+        inner.state.register_dispatcher(cx.waker());
+
+        if inner.flush_pending && !inner.transfers_paused {
+            inner.flush_pending = false;
+            self.flush_transfers();
+        }
+    }
+
+    /// Write pending transfers of all sessions
+    ///
+    /// Sessions are flushed in round-robin order and share write budget,
+    /// so busy session could not take whole write buffer on each flush.
+    fn flush_transfers(&self) {
+        let inner = self.0.get_mut();
+        let mut sessions: Vec<_> = inner
+            .sessions
+            .iter()
+            .filter_map(|(id, channel)| {
+                if let ChannelState::Established(ref session) = channel {
+                    Some((id, session.clone()))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        if let Some(pos) = sessions.iter().position(|(id, _)| *id >= inner.resume_next) {
+            sessions.rotate_left(pos);
+        }
+        inner.resume_next = sessions.first().map(|(id, _)| id + 1).unwrap_or(0);
+
+        for (_, session) in sessions {
+            session.get_mut().flush_pending_transfers();
+        }
+    }
+
+    /// Detach links that have no activity within link idle timeout
+    pub(crate) fn detach_idle_links(&self) {
+        let inner = self.0.get_ref();
+        let timeout = Duration::from_millis(inner.link_idle_timeout);
+        let now = Instant::now();
+
+        let links: Vec<_> = inner
+            .sessions
+            .iter()
+            .filter_map(|(_, channel)| {
+                if let ChannelState::Established(ref session) = channel {
+                    Some(session.get_ref().idle_links(now, timeout))
+                } else {
+                    None
+                }
+            })
+            .flatten()
+            .collect();
+
+        for link in links {
+            let err = LinkError::force_detach().text("Link is idle");
+            match link {
+                Either::Left(link) => {
+                    log::trace!("Detach idle sender link {:?}", link.name());
+                    let _ = link.close_with_error(err);
+                }
+                Either::Right(link) => {
+                    log::trace!("Detach idle receiver link {:?}", link.frame().name());
+                    let _ = link.close_with_error(err);
+                }
+            }
+        }
+    }
+
+    /// Check if any session has links suspended by remote peer
+    pub(crate) fn has_suspended_links(&self) -> bool {
+        self.0.get_ref().sessions.iter().any(|(_, channel)| {
+            if let ChannelState::Established(ref session) = channel {
+                session.get_ref().has_suspended_links()
+            } else {
+                false
+            }
+        })
+    }
+
+    /// Detach suspended links that are not resumed within their time-out
+    pub(crate) fn expire_suspended_links(&self) {
+        let inner = self.0.get_ref();
+        let now = Instant::now();
+
+        let links: Vec<_> = inner
+            .sessions
+            .iter()
+            .filter_map(|(_, channel)| {
+                if let ChannelState::Established(ref session) = channel {
+                    Some(session.get_ref().expired_links(now))
+                } else {
+                    None
+                }
+            })
+            .flatten()
+            .collect();
+
+        for link in links {
+            match link {
+                Either::Left(link) => {
+                    log::trace!("Suspended sender link is expired {:?}", link.name());
+                    let _ = link.close();
+                }
+                Either::Right(link) => {
+                    log::trace!(
+                        "Suspended receiver link is expired {:?}",
+                        link.frame().name()
+                    );
+                    let _ = link.close();
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "testing")]
+    /// Write raw frame to the channel
+    ///
+    /// Frame is not validated and connection state is not updated,
+    /// intended for protocol conformance tests only.
+    pub fn post_raw_frame(&self, channel: u16, frame: Frame) {
+        self.post_frame(AmqpFrame::new(channel, frame))
+    }
+
+    /// Reserve buffer space for partial delivery
+    ///
+    /// Returns `false` if reassembly buffer limit of the connection is exceeded.
+    pub(crate) fn reserve_reassembly(&self, size: usize) -> bool {
+        let inner = self.0.get_mut();
+        if inner.reassembly_max != 0 && inner.reassembly_size + size > inner.reassembly_max {
+            false
+        } else {
+            inner.reassembly_size += size;
+            true
+        }
+    }
+
+    pub(crate) fn release_reassembly(&self, size: usize) {
+        let inner = self.0.get_mut();
+        inner.reassembly_size = inner.reassembly_size.saturating_sub(size);
+    }
+
     pub(crate) fn post_frame(&self, frame: AmqpFrame) {
         #[cfg(feature = "frame-trace")]
         log::trace!("outcoming: {:#?}", frame);
 
         let inner = self.0.get_mut();
+        inner.last_write = Instant::now();
         if let Err(e) = inner.state.write().encode(frame, &inner.codec) {
             inner.set_error(e.into())
         }
     }
+
+    /// Check if transfer could be written within current dispatcher poll
+    pub(crate) fn has_write_budget(&self) -> bool {
+        let inner = self.0.get_ref();
+        inner.write_frames > 0 && inner.write_bytes > 0
+    }
+
+    /// Write transfer frame within write budget of current dispatcher poll
+    pub(crate) fn post_transfer(&self, frame: AmqpFrame) {
+        let size = frame.encoded_size();
+        let inner = self.0.get_mut();
+        inner.write_frames = inner.write_frames.saturating_sub(1);
+        inner.write_bytes = inner.write_bytes.saturating_sub(size);
+        self.post_frame(frame);
+    }
+
+    /// Wake dispatcher task to write pending transfers
+    pub(crate) fn request_flush(&self) {
+        let inner = self.0.get_mut();
+        if !inner.flush_pending {
+            inner.flush_pending = true;
+            inner.state.wake_dispatcher();
+        }
+    }
 }
 
 impl ConnectionInner {
@@ -226,7 +666,12 @@ impl ConnectionInner {
         log::trace!("Set connection error: {:?}", err);
         for (_, channel) in self.sessions.iter_mut() {
             match channel {
-                ChannelState::Opening(_, _) | ChannelState::Closing(_) => (),
+                ChannelState::Opening(_, _) => (),
+                ChannelState::Closing(ref mut tx) => {
+                    if let Some(tx) = tx.take() {
+                        let _ = tx.send(Err(err.clone()));
+                    }
+                }
                 ChannelState::Established(ref mut ses) => {
                     ses.get_mut().set_error(err.clone());
                 }
@@ -240,18 +685,68 @@ impl ConnectionInner {
         }
     }
 
+    /// Check if close handshake is started by any side or connection is dropped locally
+    pub(crate) fn is_closing(&self) -> bool {
+        self.st != ConnectionState::Normal
+    }
+
+    /// Send `Close` frame or reply to remote `Close` if it is not answered yet
+    fn send_close(&mut self, error: Option<Error>) {
+        match self.st {
+            ConnectionState::Normal => {
+                self.post_frame(AmqpFrame::new(0, Close { error }.into()));
+                self.st = ConnectionState::Closing;
+            }
+            ConnectionState::RemoteCloseWait => {
+                self.post_frame(AmqpFrame::new(0, Close { error }.into()));
+                self.st = ConnectionState::RemoteClose;
+            }
+            _ => (),
+        }
+    }
+
     pub(crate) fn post_frame(&mut self, frame: AmqpFrame) {
+        self.last_write = Instant::now();
         if let Err(e) = self.state.write().encode(frame, &self.codec) {
             self.set_error(e.into())
         }
     }
 
+    /// Close connection with `amqp:connection:framing-error` error
+    fn framing_error(&mut self, frame: AmqpFrame, description: &'static str) -> Option<AmqpFrame> {
+        error!("{}: {:?}", description, frame);
+
+        self.send_close(Some(Error {
+            condition: ConnectionError::FramingError.into(),
+            description: Some(ByteString::from_static(description)),
+            info: None,
+        }));
+        self.state.close();
+        self.set_error(AmqpProtocolError::Unexpected(Box::new(
+            frame.into_parts().1,
+        )));
+        None
+    }
+
+    /// Local session end is initiated, wait for remote `End` frame
+    pub(crate) fn end_session(
+        &mut self,
+        id: usize,
+        tx: oneshot::Sender<Result<(), AmqpProtocolError>>,
+    ) {
+        if let Some(channel) = self.sessions.get_mut(id) {
+            *channel = ChannelState::Closing(Some(tx));
+        } else {
+            let _ = tx.send(Ok(()));
+        }
+    }
+
     pub(crate) fn complete_session_creation(
         &mut self,
         channel_id: u16,
         remote_channel_id: u16,
         begin: &Begin,
-    ) {
+    ) -> bool {
         trace!(
             "Session opened: local {:?} remote {:?}",
             channel_id,
@@ -271,20 +766,28 @@ impl ConnectionInner {
                         begin.next_outgoing_id(),
                         begin.incoming_window(),
                         begin.outgoing_window(),
+                        begin.handle_max(),
                     ));
                     self.sessions_map.insert(channel_id, id);
 
-                    // TODO: send end session if `tx` is None
-                    tx.take()
-                        .and_then(|tx| tx.send(Session::new(session.clone())).err());
-                    *channel = ChannelState::Established(session)
+                    let opened = tx
+                        .take()
+                        .map(|tx| tx.send(Session::new(session.clone())).is_ok())
+                        .unwrap_or(false);
+                    if opened {
+                        *channel = ChannelState::Established(session);
+                    } else {
+                        // open session future is dropped, end session,
+                        // channel is released on remote `End` frame
+                        trace!("Session opener is gone, end session: {:?}", id);
+                        *channel = ChannelState::Closing(None);
+                        self.post_frame(AmqpFrame::new(id as u16, End { error: None }.into()));
+                    }
+                    return true;
                 }
-            } else {
-                // TODO: send error response
             }
-        } else {
-            // TODO: rogue begin right now - do nothing. in future might indicate incoming attach
         }
+        false
     }
 
     pub(crate) fn handle_frame(
@@ -301,20 +804,38 @@ impl ConnectionInner {
             if self.st == ConnectionState::Closing {
                 log::trace!("Connection closed: {:?}", close);
                 self.set_error(AmqpProtocolError::Disconnected);
-            } else {
+                self.st = ConnectionState::RemoteClose;
+                // close handshake is completed
+                self.state.close();
+            } else if self.auto_close_reply {
                 log::trace!("Connection closed remotely: {:?}", close);
                 let close = Close { error: None };
                 self.post_frame(AmqpFrame::new(0, close.into()));
                 self.st = ConnectionState::RemoteClose;
+            } else {
+                log::trace!("Connection closed remotely, reply is delayed: {:?}", close);
+                self.st = ConnectionState::RemoteCloseWait;
+                return Ok(Some(frame));
             }
             return Ok(None);
         }
 
+        // local `Close` is sent, only remote `Close` reply is expected
+        if self.st == ConnectionState::Closing {
+            trace!("Connection is closing, drop frame: {:?}", frame);
+            return Ok(None);
+        }
+
         if self.error.is_some() {
             error!("Connection closed but new framed is received: {:?}", frame);
             return Ok(None);
         }
 
+        // channel 0 is valid session channel, only connection level frames are unexpected
+        if let Frame::Open(_) = frame.performative() {
+            return Ok(self.framing_error(frame, "Unexpected open frame"));
+        }
+
         // get local session id
         let state = if let Some(token) = self.sessions_map.get(&frame.channel_id()) {
             if let Some(state) = self.sessions.get_mut(*token) {
@@ -332,8 +853,11 @@ impl ConnectionInner {
             if let Frame::Begin(ref begin) = frame.performative() {
                 // response Begin for open session
                 if let Some(id) = begin.remote_channel() {
-                    self.complete_session_creation(frame.channel_id(), id, begin);
-                    return Ok(None);
+                    if self.complete_session_creation(frame.channel_id(), id, begin) {
+                        return Ok(None);
+                    }
+                    // begin refers to session that is not opening
+                    return Ok(self.framing_error(frame, "Unexpected begin frame"));
                 } else {
                     return Ok(Some(frame));
                 }
@@ -355,6 +879,10 @@ impl ConnectionInner {
                 )))
             }
             ChannelState::Established(ref mut session) => match frame.performative() {
+                Frame::Begin(_) => Ok(self.framing_error(frame, "Channel is in use")),
+                Frame::Attach(attach) if attach.handle() > self.handle_max => {
+                    Ok(self.framing_error(frame, "Link handle exceeds handle max"))
+                }
                 Frame::Attach(attach) => {
                     let cell = session.clone();
                     if !session.get_mut().handle_attach(attach, cell) {
@@ -401,3 +929,11 @@ impl ConnectionInner {
         }
     }
 }
+
+fn secs(secs: usize) -> Option<Duration> {
+    if secs > 0 {
+        Some(Duration::from_secs(secs as u64))
+    } else {
+        None
+    }
+}
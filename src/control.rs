@@ -31,6 +31,7 @@ pub enum ControlFrameKind {
     DetachSender(protocol::Detach, SenderLink),
     DetachReceiver(protocol::Detach, ReceiverLink),
     ProtocolError(AmqpProtocolError),
+    RemoteClose(protocol::Close),
     Closed(bool),
 }
 
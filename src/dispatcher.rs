@@ -7,11 +7,16 @@ use ntex::util::Ready;
 
 use crate::cell::Cell;
 use crate::codec::protocol::{Frame, Role};
-use crate::codec::{AmqpCodec, AmqpFrame};
-use crate::error::{AmqpProtocolError, DispatcherError, Error};
+use crate::codec::{AmqpCodec, AmqpCodecError, AmqpFrame};
+use crate::error::{AmqpError, AmqpProtocolError, DispatcherError, Error};
 use crate::sndlink::{SenderLink, SenderLinkInner};
+use crate::span::Span;
+use crate::utils::catch_unwind;
 use crate::{connection::Connection, types, ControlFrame, ControlFrameKind, State};
 
+/// Interval of link idle time-out checks
+const LINK_IDLE_CHECK: time::Duration = time::Duration::from_secs(1);
+
 /// Amqp server dispatcher service.
 pub(crate) struct Dispatcher<St, Sr, Ctl: Service> {
     state: State<St>,
@@ -21,7 +26,15 @@ pub(crate) struct Dispatcher<St, Sr, Ctl: Service> {
     ctl_fut: RefCell<Option<(ControlFrame, Pin<Box<Ctl::Future>>)>>,
     shutdown: std::cell::Cell<bool>,
     expire: RefCell<Pin<Box<Sleep>>>,
-    idle_timeout: usize,
+    /// Heartbeat interval in milliseconds, zero if remote peer does not
+    /// advertise idle time-out
+    ///
+    /// Heartbeat is sent only if no other frame is written within interval
+    idle_timeout: u64,
+    link_expire: RefCell<Pin<Box<Sleep>>>,
+    link_idle_timeout: u64,
+    close_expire: RefCell<Option<Pin<Box<Sleep>>>>,
+    span: Span,
 }
 
 impl<St, Sr, Ctl> Dispatcher<St, Sr, Ctl>
@@ -38,8 +51,11 @@ where
         sink: Connection,
         service: Sr,
         ctl_service: Ctl,
-        idle_timeout: usize,
+        idle_timeout: u64,
     ) -> Self {
+        let link_idle_timeout = sink.0.get_ref().link_idle_timeout;
+        let span = sink.0.get_ref().span.clone();
+
         Dispatcher {
             sink,
             state,
@@ -48,9 +64,11 @@ where
             idle_timeout,
             ctl_fut: RefCell::new(None),
             shutdown: std::cell::Cell::new(false),
-            expire: RefCell::new(Box::pin(sleep(time::Duration::from_secs(
-                idle_timeout as u64,
-            )))),
+            expire: RefCell::new(Box::pin(sleep(time::Duration::from_millis(idle_timeout)))),
+            link_idle_timeout,
+            link_expire: RefCell::new(Box::pin(sleep(LINK_IDLE_CHECK))),
+            close_expire: RefCell::new(None),
+            span,
         }
     }
 
@@ -59,9 +77,46 @@ where
         if idle_timeout > 0 {
             let mut expire = self.expire.borrow_mut();
             if Pin::new(&mut *expire).poll(cx).is_ready() {
-                log::trace!("Send keep-alive ping, timeout: {:?} secs", idle_timeout);
-                self.sink.post_frame(AmqpFrame::new(0, Frame::Empty));
-                *expire = Box::pin(sleep(time::Duration::from_secs(idle_timeout as u64)));
+                let interval = time::Duration::from_millis(idle_timeout);
+                let elapsed = self.sink.0.get_ref().last_write.elapsed();
+
+                // any written frame resets remote idle timer,
+                // heartbeat is needed only if connection is idle
+                if elapsed < interval {
+                    *expire = Box::pin(sleep(interval - elapsed));
+                } else {
+                    log::trace!("Send keep-alive ping, interval: {:?} ms", idle_timeout);
+                    self.sink.post_frame(AmqpFrame::new(0, Frame::Empty));
+                    *expire = Box::pin(sleep(interval));
+                }
+                let _ = Pin::new(&mut *expire).poll(cx);
+            }
+        }
+    }
+
+    fn handle_close_timeout(&self, cx: &mut Context<'_>) {
+        if let Some(deadline) = self.sink.close_deadline() {
+            let mut expire = self.close_expire.borrow_mut();
+            let expire = expire.get_or_insert_with(|| {
+                Box::pin(sleep(
+                    deadline.saturating_duration_since(time::Instant::now()),
+                ))
+            });
+            if Pin::new(&mut *expire).poll(cx).is_ready() {
+                self.sink.close_timeout_expired();
+            }
+        }
+    }
+
+    fn handle_link_idle_timeout(&self, cx: &mut Context<'_>) {
+        if self.link_idle_timeout > 0 || self.sink.has_suspended_links() {
+            let mut expire = self.link_expire.borrow_mut();
+            if Pin::new(&mut *expire).poll(cx).is_ready() {
+                if self.link_idle_timeout > 0 {
+                    self.sink.detach_idle_links();
+                }
+                self.sink.expire_suspended_links();
+                *expire = Box::pin(sleep(LINK_IDLE_CHECK));
                 let _ = Pin::new(&mut *expire).poll(cx);
             }
         }
@@ -112,6 +167,9 @@ where
                 ControlFrameKind::DetachReceiver(_, ref link) => {
                     let _ = link.close_with_error(err);
                 }
+                ControlFrameKind::RemoteClose(_) => {
+                    let _ = self.sink.close_with_error(err);
+                }
                 ControlFrameKind::ProtocolError(ref err) => return Err(err.clone().into()),
                 _ => (),
             }
@@ -123,10 +181,19 @@ where
                         .service
                         .call(types::Link::new(link.clone(), self.state.clone()));
                     ntex::rt::spawn(async move {
-                        let res = fut.await;
+                        // panic in link service affects only this link
+                        let res = catch_unwind(fut).await;
                         match res {
-                            Ok(_) => link.close().await,
-                            Err(err) => link.close_with_error(Error::from(err)).await,
+                            Ok(Ok(_)) => link.close().await,
+                            Ok(Err(err)) => link.close_with_error(Error::from(err)).await,
+                            Err(_) => {
+                                log::error!("Link service panicked, detaching link");
+                                link.close_with_error(
+                                    AmqpError::internal_error()
+                                        .description("Link service panicked"),
+                                )
+                                .await
+                            }
                         }
                     });
                 }
@@ -169,9 +236,27 @@ where
     type Future = Ready<Self::Response, Self::Error>;
 
     fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let _enter = self.span.enter();
+
+        // send heartbeats, also while reading is paused
+        self.handle_idle_timeout(cx);
+        self.handle_link_idle_timeout(cx);
+        self.handle_close_timeout(cx);
+
+        // new write budget, write transfers held back by previous poll
+        self.sink.poll_write_budget(cx);
+
         // process control frame
         let res0 = !self.handle_control_fut(cx)?;
 
+        // reading is paused by application
+        let sink = self.sink.0.get_ref();
+        if sink.read_paused {
+            // incoming frames are not processed, do not expire keep-alive
+            sink.state.reset_keepalive();
+            return Poll::Pending;
+        }
+
         // check readiness
         let res1 = self.service.poll_ready(cx).map_err(|err| {
             error!("Error during publish service readiness check: {:?}", err);
@@ -195,6 +280,9 @@ where
         if !self.shutdown.get() {
             self.shutdown.set(true);
             let sink = self.sink.0.get_mut();
+
+            // peer is gone without close handshake
+            let is_error = is_error || !sink.is_closing();
             if is_error {
                 sink.set_error(AmqpProtocolError::Disconnected);
             }
@@ -218,6 +306,8 @@ where
     }
 
     fn call(&self, request: Self::Request) -> Self::Future {
+        let _enter = self.span.enter();
+
         match request {
             DispatchItem::Item(frame) => {
                 #[cfg(feature = "frame-trace")]
@@ -237,6 +327,15 @@ where
 
                 let (channel_id, frame) = frame.into_parts();
 
+                // remote close, reply is up to control service
+                if let Frame::Close(frm) = frame {
+                    self.sink.start_close_timer();
+                    let frame = ControlFrame::new_kind(ControlFrameKind::RemoteClose(frm));
+                    *self.ctl_fut.borrow_mut() =
+                        Some((frame.clone(), Box::pin(self.ctl_service.call(frame))));
+                    return Ready::Ok(());
+                }
+
                 // remote session
                 if let Frame::Begin(frm) = frame {
                     return Ready::from(
@@ -334,6 +433,15 @@ where
 
                 Ready::from(result)
             }
+            DispatchItem::DecoderError(AmqpCodecError::MaxSizeExceeded) => {
+                // peer does not respect max frame size advertised in `Open`
+                let err = AmqpProtocolError::Codec(AmqpCodecError::MaxSizeExceeded);
+                let _ = self.sink.close_with_error(err.clone());
+                let frame = ControlFrame::new_kind(ControlFrameKind::ProtocolError(err));
+                *self.ctl_fut.borrow_mut() =
+                    Some((frame.clone(), Box::pin(self.ctl_service.call(frame))));
+                Ready::from(Ok(()))
+            }
             DispatchItem::EncoderError(err) | DispatchItem::DecoderError(err) => {
                 let frame = ControlFrame::new_kind(ControlFrameKind::ProtocolError(err.into()));
                 *self.ctl_fut.borrow_mut() =
@@ -354,7 +462,13 @@ where
                     .set_error(AmqpProtocolError::Disconnected);
                 Ready::from(Ok(()))
             }
-            DispatchItem::WBackPressureEnabled | DispatchItem::WBackPressureDisabled => {
+            DispatchItem::WBackPressureEnabled => {
+                // write buffer is full, keep it for control frames
+                self.sink.pause_transfers();
+                Ready::from(Ok(()))
+            }
+            DispatchItem::WBackPressureDisabled => {
+                self.sink.resume_transfers();
                 Ready::from(Ok(()))
             }
         }
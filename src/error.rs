@@ -1,10 +1,11 @@
-use std::{convert::TryFrom, io};
+use std::{convert::TryFrom, fmt, io};
 
 use ntex::util::{ByteString, Either};
 
 pub use crate::codec::protocol::Error;
+use crate::codec::protocol::{self, Milliseconds};
 pub use crate::codec::{AmqpCodecError, AmqpParseError, ProtocolIdError};
-use crate::{codec::protocol, types::Outcome};
+use crate::types::Outcome;
 
 /// Errors which can occur when attempting to handle amqp connection.
 #[derive(Debug, Display, From)]
@@ -37,6 +38,8 @@ impl From<Either<AmqpCodecError, io::Error>> for DispatcherError {
 pub enum AmqpProtocolError {
     Codec(AmqpCodecError),
     TooManyChannels,
+    #[display(fmt = "Link handles are exhausted, remote handle-max: {}", _0)]
+    TooManyLinks(protocol::Handle),
     KeepAliveTimeout,
     Disconnected,
     #[display(fmt = "Unknown session: {} {:?}", _0, _1)]
@@ -47,18 +50,194 @@ pub enum AmqpProtocolError {
     SessionEnded(Option<protocol::Error>),
     #[display(fmt = "Link detached, error: {:?}", _0)]
     LinkDetached(Option<protocol::Error>),
+    #[display(fmt = "Transaction is rejected, error: {:?}", _0)]
+    Transaction(Option<protocol::Error>),
+    #[display(fmt = "Delivery is rejected, error: {:?}", _0)]
+    Rejected(Option<protocol::Error>),
+    #[display(fmt = "Link name is already in use: {}", _0)]
+    LinkNameInUse(ByteString),
     #[display(fmt = "Unexpected frame for opening state, got: {:?}", _0)]
     UnexpectedOpeningState(Box<protocol::Frame>),
     #[display(fmt = "Unexpected frame, got: {:?}", _0)]
     Unexpected(Box<protocol::Frame>),
 }
 
+impl std::error::Error for AmqpProtocolError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AmqpProtocolError::Codec(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl AmqpProtocolError {
+    /// Error sent by remote peer in `Close`, `End`, `Detach` or `Disposition` frame
+    pub fn remote_error(&self) -> Option<&protocol::Error> {
+        match self {
+            AmqpProtocolError::Closed(Some(err))
+            | AmqpProtocolError::SessionEnded(Some(err))
+            | AmqpProtocolError::LinkDetached(Some(err))
+            | AmqpProtocolError::Transaction(Some(err))
+            | AmqpProtocolError::Rejected(Some(err)) => Some(err),
+            _ => None,
+        }
+    }
+
+    /// Error condition of remote error
+    pub fn condition(&self) -> Option<&protocol::ErrorCondition> {
+        self.remote_error().map(|err| &err.condition)
+    }
+
+    /// Description of remote error
+    pub fn description(&self) -> Option<&ByteString> {
+        self.remote_error().and_then(|err| err.description.as_ref())
+    }
+}
+
 impl From<AmqpCodecError> for AmqpProtocolError {
     fn from(err: AmqpCodecError) -> Self {
         AmqpProtocolError::Codec(err)
     }
 }
 
+impl From<AmqpProtocolError> for protocol::Error {
+    fn from(err: AmqpProtocolError) -> protocol::Error {
+        let (condition, description): (protocol::ErrorCondition, _) = match err {
+            AmqpProtocolError::Closed(Some(err))
+            | AmqpProtocolError::SessionEnded(Some(err))
+            | AmqpProtocolError::LinkDetached(Some(err))
+            | AmqpProtocolError::Transaction(Some(err))
+            | AmqpProtocolError::Rejected(Some(err)) => return err,
+            AmqpProtocolError::Codec(AmqpCodecError::MaxSizeExceeded) => (
+                protocol::ConnectionError::FramingError.into(),
+                "Max frame size exceeded".to_string(),
+            ),
+            AmqpProtocolError::Codec(ref e) => {
+                (protocol::AmqpError::DecodeError.into(), e.to_string())
+            }
+            AmqpProtocolError::TooManyChannels => (
+                protocol::AmqpError::ResourceLimitExceeded.into(),
+                "Too many channels".to_string(),
+            ),
+            AmqpProtocolError::KeepAliveTimeout => (
+                protocol::AmqpError::ResourceLimitExceeded.into(),
+                "Idle time-out expired".to_string(),
+            ),
+            AmqpProtocolError::Disconnected | AmqpProtocolError::Closed(None) => (
+                protocol::ConnectionError::ConnectionForced.into(),
+                "Connection is closed".to_string(),
+            ),
+            AmqpProtocolError::SessionEnded(None) => (
+                protocol::AmqpError::IllegalState.into(),
+                "Session ended".to_string(),
+            ),
+            AmqpProtocolError::LinkDetached(None) => (
+                protocol::LinkError::DetachForced.into(),
+                "Link detached".to_string(),
+            ),
+            AmqpProtocolError::Transaction(None) => (
+                crate::error_code::TRANSACTION_ROLLBACK.into(),
+                "Transaction is rejected".to_string(),
+            ),
+            AmqpProtocolError::Rejected(None) => (
+                protocol::AmqpError::NotAllowed.into(),
+                "Delivery is rejected".to_string(),
+            ),
+            AmqpProtocolError::LinkNameInUse(_) => {
+                (protocol::AmqpError::InvalidField.into(), err.to_string())
+            }
+            AmqpProtocolError::UnknownSession(..)
+            | AmqpProtocolError::UnexpectedOpeningState(_)
+            | AmqpProtocolError::Unexpected(_) => {
+                (protocol::AmqpError::IllegalState.into(), err.to_string())
+            }
+        };
+        protocol::Error {
+            condition,
+            description: Some(ByteString::from(description)),
+            info: None,
+        }
+    }
+}
+
+/// Errors which can occur when validating connection configuration.
+#[derive(Copy, Clone, Debug, Display, PartialEq)]
+pub enum ConfigError {
+    /// Channel max must allow at least one session
+    #[display(fmt = "Channel max must be greater than 0")]
+    ZeroChannelMax,
+    /// Channel max does not fit into `Open` frame
+    #[display(fmt = "Channel max must not exceed 65535, got: {}", _0)]
+    ChannelMaxOverflow(usize),
+    /// Max frame size is below protocol minimum
+    #[display(fmt = "Max frame size must be at least 512 bytes, got: {}", _0)]
+    MaxFrameSizeTooSmall(u32),
+    /// Idle time-out is too small for heartbeat resolution
+    #[display(fmt = "Idle time-out must be 0 or at least 1000ms, got: {}ms", _0)]
+    IdleTimeoutTooSmall(Milliseconds),
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Sender link has no credit for new delivery
+#[derive(Copy, Clone, Debug, Display, PartialEq)]
+#[display(fmt = "Link has no credit")]
+pub struct NoCredit;
+
+impl std::error::Error for NoCredit {}
+
+/// Errors which can occur when decoding transfer body.
+#[derive(Debug)]
+pub enum BodyError {
+    /// Amqp message parse error
+    Parse(AmqpParseError),
+    /// Content type of the message does not match requested body type
+    ContentType(ByteString),
+    /// Body is not valid utf-8 string
+    Utf8,
+    /// Json deserialize error
+    #[cfg(feature = "json")]
+    Json(serde_json::Error),
+    /// Content encoding of the message is not supported
+    #[cfg(feature = "compress")]
+    ContentEncoding(ByteString),
+    /// Body decompression error
+    #[cfg(feature = "compress")]
+    Decompress(std::io::Error),
+}
+
+impl fmt::Display for BodyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BodyError::Parse(err) => write!(f, "Amqp parse error: {}", err),
+            BodyError::ContentType(ct) => write!(f, "Unexpected content type: {}", ct),
+            BodyError::Utf8 => write!(f, "Body is not valid utf-8 string"),
+            #[cfg(feature = "json")]
+            BodyError::Json(err) => write!(f, "Json error: {}", err),
+            #[cfg(feature = "compress")]
+            BodyError::ContentEncoding(enc) => write!(f, "Unsupported content encoding: {}", enc),
+            #[cfg(feature = "compress")]
+            BodyError::Decompress(err) => write!(f, "Decompression error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for BodyError {}
+
+impl From<AmqpParseError> for BodyError {
+    fn from(err: AmqpParseError) -> Self {
+        BodyError::Parse(err)
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<serde_json::Error> for BodyError {
+    fn from(err: serde_json::Error) -> Self {
+        BodyError::Json(err)
+    }
+}
+
 #[derive(Debug, Display)]
 #[display(fmt = "Amqp error: {:?} {:?} ({:?})", err, description, info)]
 pub struct AmqpError {
@@ -67,6 +246,8 @@ pub struct AmqpError {
     info: Option<protocol::Fields>,
 }
 
+impl std::error::Error for AmqpError {}
+
 impl AmqpError {
     pub fn new(err: protocol::AmqpError) -> Self {
         AmqpError {
@@ -121,6 +302,12 @@ impl AmqpError {
         self.description = Some(text);
         self
     }
+
+    #[allow(clippy::mutable_key_type)]
+    pub fn fields(mut self, fields: protocol::Fields) -> Self {
+        self.info = Some(fields);
+        self
+    }
 }
 
 impl From<AmqpError> for protocol::Error {
@@ -153,6 +340,8 @@ pub struct LinkError {
     info: Option<protocol::Fields>,
 }
 
+impl std::error::Error for LinkError {}
+
 impl LinkError {
     pub fn new(error: protocol::ErrorCondition) -> Self {
         LinkError {
@@ -222,3 +411,51 @@ impl TryFrom<LinkError> for Outcome {
         Ok(Outcome::Error(err.into()))
     }
 }
+
+/// Publish service error
+///
+/// Transient failures settle delivery and keep link open,
+/// fatal failure detaches the link.
+#[derive(Debug, Display)]
+pub enum PublishError {
+    /// Transient failure, message is released and could be redelivered
+    #[display(fmt = "Message is released")]
+    Release,
+    /// Message is rejected
+    #[display(fmt = "Message is rejected: {:?}", _0)]
+    Reject(Option<Error>),
+    /// Fatal failure, link is detached with error
+    #[display(fmt = "Link is detached: {:?}", _0)]
+    Detach(Error),
+}
+
+impl From<PublishError> for Error {
+    fn from(err: PublishError) -> Error {
+        match err {
+            PublishError::Release => LinkError::force_detach().text("Message is released").into(),
+            PublishError::Reject(Some(err)) | PublishError::Detach(err) => err,
+            PublishError::Reject(None) => {
+                LinkError::force_detach().text("Message is rejected").into()
+            }
+        }
+    }
+}
+
+impl From<LinkError> for PublishError {
+    fn from(err: LinkError) -> Self {
+        PublishError::Detach(err.into())
+    }
+}
+
+impl TryFrom<PublishError> for Outcome {
+    type Error = Error;
+
+    fn try_from(err: PublishError) -> Result<Self, Error> {
+        match err {
+            PublishError::Release => Ok(Outcome::Release),
+            PublishError::Reject(Some(err)) => Ok(Outcome::Error(err)),
+            PublishError::Reject(None) => Ok(Outcome::Reject),
+            PublishError::Detach(err) => Err(err),
+        }
+    }
+}
@@ -10,7 +10,8 @@ use std::{future::Future, pin::Pin, task::Context, task::Poll};
 
 use ntex::channel::oneshot;
 use ntex::util::ByteString;
-use ntex_amqp_codec::protocol::{Disposition, Handle, Milliseconds, Open};
+use ntex_amqp_codec::protocol::{Disposition, Fields, Handle, Milliseconds, Open, Symbols};
+use ntex_amqp_codec::types::{Symbol, Variant};
 use uuid::Uuid;
 
 #[macro_use]
@@ -18,27 +19,37 @@ mod utils;
 
 mod cell;
 pub mod client;
+#[cfg(feature = "compress")]
+mod compress;
 mod connection;
 mod control;
 mod default;
 mod dispatcher;
 pub mod error;
 pub mod error_code;
-mod hb;
 mod rcvlink;
 mod router;
 pub mod server;
 mod session;
 mod sndlink;
+mod span;
 mod state;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod transaction;
 pub mod types;
 
-pub use self::connection::Connection;
+#[cfg(feature = "compress")]
+pub use self::compress::ContentEncoding;
+pub use self::connection::{Connection, ConnectionStatus};
 pub use self::control::{ControlFrame, ControlFrameKind};
-pub use self::rcvlink::{ReceiverLink, ReceiverLinkBuilder};
-pub use self::session::Session;
-pub use self::sndlink::{SenderLink, SenderLinkBuilder};
+pub use self::rcvlink::{
+    Deliveries, IncomingTransfer, LinkStats, ReceiverLink, ReceiverLinkBuilder,
+};
+pub use self::session::{LinkInfo, Session, SessionBuilder, SessionInfo, UnsettledPolicy};
+pub use self::sndlink::{DeliveryTag, SenderLink, SenderLinkBuilder};
 pub use self::state::State;
+pub use self::transaction::{Coordinator, Transaction};
 
 pub mod codec {
     pub use ntex_amqp_codec::*;
@@ -78,13 +89,37 @@ impl Future for Delivery {
     }
 }
 
+/// Smallest max frame size allowed by the protocol
+const MIN_MAX_FRAME_SIZE: u32 = 512;
+
+/// Default max size of sasl and open frames received during handshake
+const DEFAULT_HANDSHAKE_MAX_FRAME_SIZE: u32 = 16 * 1024;
+
+/// Exclusive connection for container capability
+const SOLE_CONNECTION_FOR_CONTAINER: &str = "sole-connection-for-container";
+
+// well-known connection properties
+const PROPERTY_PRODUCT: &str = "product";
+const PROPERTY_VERSION: &str = "version";
+const PROPERTY_PLATFORM: &str = "platform";
+
 /// Amqp1 transport configuration.
 #[derive(Debug, Clone)]
 pub struct Configuration {
     pub max_frame_size: u32,
+    pub handshake_max_frame_size: u32,
     pub channel_max: usize,
     pub idle_time_out: Milliseconds,
+    pub close_timeout: Milliseconds,
+    pub link_idle_timeout: Milliseconds,
+    pub handle_max: u32,
+    pub session_capacity: usize,
+    pub reassembly_buffer_max: usize,
     pub hostname: Option<ByteString>,
+    pub container_id: Option<ByteString>,
+    pub auto_close_reply: bool,
+    pub sole_connection_for_container: bool,
+    pub properties: Option<Fields>,
 }
 
 impl Default for Configuration {
@@ -98,9 +133,19 @@ impl Configuration {
     pub fn new() -> Self {
         Configuration {
             max_frame_size: std::u16::MAX as u32,
+            handshake_max_frame_size: DEFAULT_HANDSHAKE_MAX_FRAME_SIZE,
             channel_max: 1024,
             idle_time_out: 120_000,
+            close_timeout: 10_000,
+            link_idle_timeout: 0,
+            handle_max: std::u32::MAX,
+            session_capacity: 8,
+            reassembly_buffer_max: 0,
             hostname: None,
+            container_id: None,
+            auto_close_reply: true,
+            sole_connection_for_container: false,
+            properties: None,
         }
     }
 
@@ -122,6 +167,17 @@ impl Configuration {
         self
     }
 
+    /// Set max size of sasl and open frames received during handshake.
+    ///
+    /// Limit applies before max frame size is negotiated.
+    /// Zero value means no limit.
+    ///
+    /// By default handshake max frame size is set to 16kb
+    pub fn handshake_max_frame_size(&mut self, size: u32) -> &mut Self {
+        self.handshake_max_frame_size = size;
+        self
+    }
+
     /// Get max frame size for the connection.
     pub fn get_max_frame_size(&self) -> usize {
         self.max_frame_size as usize
@@ -129,9 +185,73 @@ impl Configuration {
 
     /// Set idle time-out for the connection in seconds.
     ///
+    /// Connection gets closed if no frames are received within idle time-out,
+    /// zero disables time-out. Heartbeats are sent only if remote peer
+    /// advertises its own idle time-out and no other frames are sent,
+    /// local time-out is enforced either way.
+    ///
     /// By default idle time-out is set to 120 seconds
     pub fn idle_timeout(&mut self, timeout: u16) -> &mut Self {
-        self.idle_time_out = (timeout * 1000) as Milliseconds;
+        self.idle_time_out = timeout as Milliseconds * 1000;
+        self
+    }
+
+    /// Set close handshake timeout for the connection in seconds.
+    ///
+    /// If remote peer does not reply to `Close` frame, or reply to remote
+    /// `Close` is not sent within timeout, connection gets dropped.
+    /// To disable timeout set value to 0.
+    ///
+    /// By default close timeout is set to 10 seconds
+    pub fn close_timeout(&mut self, timeout: u16) -> &mut Self {
+        self.close_timeout = timeout as Milliseconds * 1000;
+        self
+    }
+
+    /// Set idle time-out for links of the connection in seconds.
+    ///
+    /// Link that does not see transfers or flow updates within timeout
+    /// gets detached with `amqp:link:detach-forced` error. Links without
+    /// credit are waiting for remote peer and never get detached.
+    /// To disable timeout set value to 0.
+    ///
+    /// By default link idle time-out is disabled
+    pub fn link_idle_timeout(&mut self, timeout: u16) -> &mut Self {
+        self.link_idle_timeout = timeout as Milliseconds * 1000;
+        self
+    }
+
+    /// Set max link handle value for sessions of the connection.
+    ///
+    /// Connection gets closed with `amqp:connection:framing-error` error
+    /// if remote peer attaches link with handle above this value.
+    ///
+    /// By default handle max is set to `u32::MAX`
+    pub fn handle_max(&mut self, max: u32) -> &mut Self {
+        self.handle_max = max;
+        self
+    }
+
+    /// Set number of sessions preallocated for the connection.
+    ///
+    /// Session storage grows on demand, preallocation avoids
+    /// reallocations for connections with many sessions.
+    ///
+    /// By default storage for 8 sessions is preallocated
+    pub fn session_capacity(&mut self, capacity: usize) -> &mut Self {
+        self.session_capacity = capacity;
+        self
+    }
+
+    /// Set max total size of partially received deliveries of the connection.
+    ///
+    /// Multi-frame deliveries are buffered by receiver links until the last
+    /// transfer arrives. Connection gets closed with `amqp:resource-limit-exceeded`
+    /// error if buffers of all links exceed this size. To disable limit set value to 0.
+    ///
+    /// By default limit is disabled
+    pub fn reassembly_buffer_max(&mut self, size: usize) -> &mut Self {
+        self.reassembly_buffer_max = size;
         self
     }
 
@@ -143,10 +263,110 @@ impl Configuration {
         self
     }
 
+    /// Set connection container id
+    ///
+    /// Random container id is generated by default
+    pub fn container_id(&mut self, id: &str) -> &mut Self {
+        self.container_id = Some(ByteString::from(id));
+        self
+    }
+
+    /// Enable `sole-connection-for-container` capability.
+    ///
+    /// Client requests exclusive connection for its container id.
+    /// Server closes previous connection of the container if new connection
+    /// requests the capability, connections are tracked per server worker.
+    ///
+    /// By default capability is disabled
+    pub fn sole_connection_for_container(&mut self, enabled: bool) -> &mut Self {
+        self.sole_connection_for_container = enabled;
+        self
+    }
+
+    /// Reply to remote `Close` frame automatically.
+    ///
+    /// If disabled, remote `Close` frame is passed to control service
+    /// as `ControlFrameKind::RemoteClose` and reply is sent by
+    /// `Connection::close()` or `Connection::close_with_error()`.
+    ///
+    /// By default automatic reply is enabled
+    pub fn auto_close_reply(&mut self, enabled: bool) -> &mut Self {
+        self.auto_close_reply = enabled;
+        self
+    }
+
+    /// Set connection property, properties are sent in `Open` frame
+    pub fn property<K, V>(&mut self, key: K, value: V) -> &mut Self
+    where
+        K: Into<Symbol>,
+        V: Into<Variant>,
+    {
+        self.properties
+            .get_or_insert_with(Fields::default)
+            .insert(key.into(), value.into());
+        self
+    }
+
+    /// Set `product` connection property
+    pub fn product(&mut self, product: &str) -> &mut Self {
+        self.property(PROPERTY_PRODUCT, ByteString::from(product))
+    }
+
+    /// Set `version` connection property
+    pub fn version(&mut self, version: &str) -> &mut Self {
+        self.property(PROPERTY_VERSION, ByteString::from(version))
+    }
+
+    /// Set `platform` connection property
+    pub fn platform(&mut self, platform: &str) -> &mut Self {
+        self.property(PROPERTY_PLATFORM, ByteString::from(platform))
+    }
+
+    /// Get connection property
+    pub fn get_property(&self, key: &str) -> Option<&Variant> {
+        self.properties.as_ref().and_then(|props| props.get(key))
+    }
+
+    /// Get `product` connection property
+    pub fn get_product(&self) -> Option<&str> {
+        self.get_property(PROPERTY_PRODUCT).and_then(|v| v.as_str())
+    }
+
+    /// Get `version` connection property
+    pub fn get_version(&self) -> Option<&str> {
+        self.get_property(PROPERTY_VERSION).and_then(|v| v.as_str())
+    }
+
+    /// Get `platform` connection property
+    pub fn get_platform(&self) -> Option<&str> {
+        self.get_property(PROPERTY_PLATFORM)
+            .and_then(|v| v.as_str())
+    }
+
+    /// Check configuration for settings that can not be used for a connection.
+    pub fn validate(&self) -> Result<(), error::ConfigError> {
+        if self.channel_max == 0 {
+            Err(error::ConfigError::ZeroChannelMax)
+        } else if self.channel_max > std::u16::MAX as usize {
+            Err(error::ConfigError::ChannelMaxOverflow(self.channel_max))
+        } else if self.max_frame_size < MIN_MAX_FRAME_SIZE {
+            Err(error::ConfigError::MaxFrameSizeTooSmall(
+                self.max_frame_size,
+            ))
+        } else if self.idle_time_out > 0 && self.idle_time_out < 1000 {
+            Err(error::ConfigError::IdleTimeoutTooSmall(self.idle_time_out))
+        } else {
+            Ok(())
+        }
+    }
+
     /// Create `Open` performative for this configuration.
     pub fn to_open(&self) -> Open {
         Open {
-            container_id: ByteString::from(Uuid::new_v4().to_simple().to_string()),
+            container_id: self
+                .container_id
+                .clone()
+                .unwrap_or_else(|| ByteString::from(Uuid::new_v4().to_simple().to_string())),
             hostname: self.hostname.clone(),
             max_frame_size: self.max_frame_size,
             channel_max: self.channel_max as u16,
@@ -157,9 +377,19 @@ impl Configuration {
             },
             outgoing_locales: None,
             incoming_locales: None,
-            offered_capabilities: None,
-            desired_capabilities: None,
-            properties: None,
+            offered_capabilities: self.capabilities(),
+            desired_capabilities: self.capabilities(),
+            properties: self.properties.clone(),
+        }
+    }
+
+    fn capabilities(&self) -> Option<Symbols> {
+        if self.sole_connection_for_container {
+            Some(Symbols::from(vec![Symbol::from_static(
+                SOLE_CONNECTION_FOR_CONTAINER,
+            )]))
+        } else {
+            None
         }
     }
 
@@ -171,9 +401,14 @@ impl Configuration {
         }
     }
 
-    pub(crate) fn timeout_remote_secs(&self) -> usize {
+    /// Heartbeat interval for remote peer in milliseconds, zero if remote
+    /// does not require heartbeats
+    ///
+    /// Empty frames are sent at half of remote idle time-out, so sub-second
+    /// time-outs are also honored.
+    pub(crate) fn timeout_remote_millis(&self) -> u64 {
         if self.idle_time_out > 0 {
-            ((self.idle_time_out as f32) * 0.75 / 1000.0) as usize
+            std::cmp::max(self.idle_time_out as u64 / 2, 1)
         } else {
             0
         }
@@ -184,9 +419,23 @@ impl<'a> From<&'a Open> for Configuration {
     fn from(open: &'a Open) -> Self {
         Configuration {
             max_frame_size: open.max_frame_size,
+            handshake_max_frame_size: DEFAULT_HANDSHAKE_MAX_FRAME_SIZE,
             channel_max: open.channel_max as usize,
             idle_time_out: open.idle_time_out.unwrap_or(0),
+            close_timeout: 0,
+            link_idle_timeout: 0,
+            handle_max: std::u32::MAX,
+            session_capacity: 8,
+            reassembly_buffer_max: 0,
             hostname: open.hostname.clone(),
+            container_id: Some(open.container_id.clone()),
+            auto_close_reply: true,
+            sole_connection_for_container: open
+                .desired_capabilities
+                .as_ref()
+                .map(|caps| caps.iter().any(|cap| cap == SOLE_CONNECTION_FOR_CONTAINER))
+                .unwrap_or(false),
+            properties: open.properties.clone(),
         }
     }
 }
@@ -1,17 +1,45 @@
+use std::time::{Duration, Instant};
 use std::{collections::VecDeque, future::Future, pin::Pin, task::Context, task::Poll};
 
-use ntex::util::{ByteString, BytesMut};
+use ntex::util::{ByteString, Bytes, BytesMut, HashMap, HashSet};
 use ntex::Stream;
 use ntex::{channel::oneshot, task::LocalWaker};
 use ntex_amqp_codec::protocol::{
-    Attach, DeliveryNumber, Disposition, Error, Handle, LinkError, ReceiverSettleMode, Role,
-    SenderSettleMode, Source, TerminusDurability, TerminusExpiryPolicy, Transfer, TransferBody,
+    AmqpError, Attach, ConnectionError, DeliveryNumber, DeliveryState, Disposition, Error, Fields,
+    Handle, LinkError, Modified, ReceiverSettleMode, Released, Role, Seconds, SenderSettleMode,
+    Source, Symbols, TargetTerminus, TerminusDurability, TerminusExpiryPolicy, Transfer,
+    TransferBody,
 };
 use ntex_amqp_codec::Encode;
 
 use crate::cell::Cell;
+use crate::connection::Connection;
 use crate::error::AmqpProtocolError;
-use crate::session::{Session, SessionInner};
+use crate::session::{LinkInfo, Session, SessionInner};
+use crate::span::Span;
+use crate::types::{is_expired, Outcome};
+use crate::utils::{serial_lt, serial_range};
+
+/// Default max message size for remotely opened links
+const DEFAULT_MAX_MESSAGE_SIZE: u64 = 262_144;
+
+/// Default number of recently settled deliveries kept per link
+const DEFAULT_SETTLED_CACHE_SIZE: usize = 32;
+
+/// Outcome counters of receiver link
+///
+/// Counters are updated for each delivery state sent by the link.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LinkStats {
+    /// Number of accepted deliveries
+    pub accepted: u64,
+    /// Number of rejected deliveries
+    pub rejected: u64,
+    /// Number of released deliveries
+    pub released: u64,
+    /// Number of modified deliveries
+    pub modified: u64,
+}
 
 #[derive(Clone, Debug)]
 pub struct ReceiverLink {
@@ -31,6 +59,18 @@ impl ReceiverLink {
         self.inner.get_ref().credit
     }
 
+    /// Number of deliveries received by the link, serial number
+    pub fn delivery_count(&self) -> u32 {
+        self.inner.get_ref().delivery_count
+    }
+
+    /// Number of messages remote sender has ready to send
+    ///
+    /// Value is updated from `available` field of incoming `Flow` frames.
+    pub fn available(&self) -> u32 {
+        self.inner.get_ref().available
+    }
+
     pub fn session(&self) -> &Session {
         &self.inner.get_ref().session
     }
@@ -43,6 +83,26 @@ impl ReceiverLink {
         &self.inner.get_ref().attach
     }
 
+    /// Receiver settle mode of the link
+    ///
+    /// In `Second` mode dispositions are sent unsettled, delivery
+    /// gets settled after remote sender settles it.
+    pub fn rcv_settle_mode(&self) -> ReceiverSettleMode {
+        self.inner.get_ref().attach.rcv_settle_mode
+    }
+
+    /// Sender settle mode of the link
+    ///
+    /// In `Settled` mode all deliveries are settled by sender, dispositions are not sent.
+    pub fn snd_settle_mode(&self) -> SenderSettleMode {
+        self.inner.get_ref().attach.snd_settle_mode
+    }
+
+    /// Link properties sent by remote peer in `Attach` frame
+    pub fn remote_properties(&self) -> Option<&Fields> {
+        self.inner.get_ref().remote_properties.as_ref()
+    }
+
     pub fn open(&mut self) {
         let inner = self.inner.get_mut();
         inner
@@ -63,14 +123,74 @@ impl ReceiverLink {
         self.inner.get_mut().set_max_partial_transfer(size);
     }
 
+    /// Max message size accepted by the link, `None` means no limit
+    pub fn max_message_size(&self) -> Option<u64> {
+        self.inner.get_ref().max_message_size
+    }
+
+    /// Set max message size for remotely opened link.
+    ///
+    /// Must be set before link get opened. Messages exceeding the limit
+    /// cause link detach with `amqp:link:message-size-exceeded` error.
+    /// Zero value means no limit.
+    ///
+    /// Default is 256Kb
+    pub fn set_max_message_size(&self, size: u64) {
+        self.inner.get_mut().set_max_message_size(size);
+    }
+
+    /// Set number of recently settled deliveries kept by the link.
+    ///
+    /// Retransmitted transfer for cached delivery is not passed to
+    /// the link, cached disposition is sent instead. Zero value disables cache.
+    ///
+    /// Default is 32
+    pub fn set_settled_cache_size(&self, size: usize) {
+        let inner = self.inner.get_mut();
+        inner.settled_max = size;
+        while inner.settled.len() > size {
+            inner.settled.pop_front();
+        }
+    }
+
+    /// Release expired messages automatically.
+    ///
+    /// Expired messages are not passed to the link, `released`
+    /// disposition is sent instead, see `Transfer::is_expired()`.
+    ///
+    /// Disabled by default
+    pub fn set_release_expired(&self, enabled: bool) {
+        self.inner.get_mut().release_expired = enabled;
+    }
+
+    /// Outcome counters of the link
+    pub fn stats(&self) -> LinkStats {
+        self.inner.get_ref().stats
+    }
+
     /// Send disposition frame
     pub fn send_disposition(&self, disp: Disposition) {
-        self.inner
-            .get_mut()
-            .session
-            .inner
-            .get_mut()
-            .post_frame(disp.into());
+        let inner = self.inner.get_mut();
+        if let Some(ref state) = disp.state {
+            let count = serial_range(disp.first, disp.last.unwrap_or(disp.first)).count();
+            inner.outcome_sent(state, count as u64);
+        }
+        if disp.settled {
+            for id in serial_range(disp.first, disp.last.unwrap_or(disp.first)) {
+                inner.unsettled.remove(&id);
+                inner.outcomes.remove(&id);
+            }
+            if let Some(ref state) = disp.state {
+                inner.settled_deliveries(disp.first, disp.last, state);
+            }
+        } else if let Some(ref state) = disp.state {
+            for id in serial_range(disp.first, disp.last.unwrap_or(disp.first)) {
+                if inner.unsettled.contains(&id) {
+                    inner.outcomes.insert(id, state.clone());
+                }
+            }
+        }
+        inner.session.inner.get_mut().post_frame(disp.into());
     }
 
     /// Wait for disposition with specified number
@@ -81,6 +201,11 @@ impl ReceiverLink {
         self.inner.get_mut().session.wait_disposition(id)
     }
 
+    /// Stream of received transfers, each transfer could be settled individually
+    pub fn deliveries(&self) -> Deliveries {
+        Deliveries { link: self.clone() }
+    }
+
     pub fn close(&self) -> impl Future<Output = Result<(), AmqpProtocolError>> {
         self.inner.get_mut().close(None)
     }
@@ -136,10 +261,118 @@ impl Stream for ReceiverLink {
     }
 }
 
+/// Stream of transfers received by receiver link
+#[derive(Debug)]
+pub struct Deliveries {
+    link: ReceiverLink,
+}
+
+impl Stream for Deliveries {
+    type Item = Result<IncomingTransfer, AmqpProtocolError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match Pin::new(&mut self.link).poll_next(cx) {
+            Poll::Ready(Some(Ok(frame))) => Poll::Ready(Some(Ok(IncomingTransfer {
+                frame,
+                link: self.link.clone(),
+            }))),
+            Poll::Ready(Some(Err(err))) => Poll::Ready(Some(Err(err))),
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Transfer received by receiver link
+#[derive(Debug)]
+pub struct IncomingTransfer {
+    frame: Transfer,
+    link: ReceiverLink,
+}
+
+impl IncomingTransfer {
+    /// Transfer frame
+    pub fn frame(&self) -> &Transfer {
+        &self.frame
+    }
+
+    /// Transfer body
+    pub fn body(&self) -> Option<&Bytes> {
+        match self.frame.body {
+            Some(TransferBody::Data(ref b)) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Receiver link
+    pub fn link(&self) -> &ReceiverLink {
+        &self.link
+    }
+
+    /// Accept transfer
+    pub fn accept(self) {
+        self.settle(Outcome::Accept)
+    }
+
+    /// Reject transfer
+    pub fn reject(self, error: Option<Error>) {
+        match error {
+            Some(err) => self.settle(Outcome::Error(err)),
+            None => self.settle(Outcome::Reject),
+        }
+    }
+
+    /// Settle transfer with modified outcome
+    ///
+    /// Message annotations get merged into the message by the sender.
+    pub fn modify(self, delivery_failed: bool, annotations: Option<Fields>) {
+        self.settle(Outcome::Modified(Modified {
+            delivery_failed: Some(delivery_failed),
+            undeliverable_here: None,
+            message_annotations: annotations,
+        }))
+    }
+
+    /// Settle transfer with specified outcome
+    ///
+    /// Transfers that are pre-settled by sender do not require disposition.
+    pub fn settle(self, outcome: Outcome) {
+        self.settle_with_state(outcome.into_delivery_state())
+    }
+
+    /// Settle transfer with specified delivery state
+    ///
+    /// Disposition is sent settled only if link's receiver settle mode is `First`.
+    pub fn settle_with_state(self, state: DeliveryState) {
+        let settled = self.link.rcv_settle_mode() == ReceiverSettleMode::First;
+        self.disposition(state, settled)
+    }
+
+    /// Send disposition with specified delivery state, overrides link's settle mode
+    ///
+    /// Unsettled delivery gets settled after remote sender settles it.
+    pub fn disposition(self, state: DeliveryState, settled: bool) {
+        if self.frame.settled == Some(true) {
+            return;
+        }
+        if let Some(id) = self.frame.delivery_id {
+            self.link.send_disposition(Disposition {
+                state: Some(state),
+                role: Role::Receiver,
+                first: id,
+                last: None,
+                settled,
+                batchable: false,
+            });
+        }
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct ReceiverLinkInner {
     handle: Handle,
     attach: Attach,
+    pub(crate) remote_properties: Option<Fields>,
     session: Session,
     closed: bool,
     reader_task: LocalWaker,
@@ -147,8 +380,20 @@ pub(crate) struct ReceiverLinkInner {
     credit: u32,
     delivery_count: u32,
     error: Option<Error>,
-    partial_body: Option<BytesMut>,
+    partial_body: Option<PartialBody>,
     partial_body_max: usize,
+    max_message_size: Option<u64>,
+    settled: VecDeque<(DeliveryNumber, DeliveryState)>,
+    settled_max: usize,
+    unsettled: HashSet<DeliveryNumber>,
+    /// Outcomes chosen by application and sent unsettled,
+    /// remote sender has not settled deliveries yet
+    outcomes: HashMap<DeliveryNumber, DeliveryState>,
+    release_expired: bool,
+    last_activity: Instant,
+    available: u32,
+    stats: LinkStats,
+    span: Span,
 }
 
 impl ReceiverLinkInner {
@@ -157,6 +402,8 @@ impl ReceiverLinkInner {
         handle: Handle,
         attach: Attach,
     ) -> ReceiverLinkInner {
+        let span = session.get_ref().span.link(handle);
+
         ReceiverLinkInner {
             handle,
             session: Session::new(session),
@@ -167,7 +414,18 @@ impl ReceiverLinkInner {
             error: None,
             partial_body: None,
             partial_body_max: 262144,
+            max_message_size: Some(DEFAULT_MAX_MESSAGE_SIZE),
+            settled: VecDeque::new(),
+            settled_max: DEFAULT_SETTLED_CACHE_SIZE,
+            unsettled: HashSet::default(),
+            outcomes: HashMap::default(),
+            release_expired: false,
+            last_activity: Instant::now(),
+            available: 0,
+            stats: LinkStats::default(),
+            span,
             delivery_count: attach.initial_delivery_count().unwrap_or(0),
+            remote_properties: None,
             attach,
         }
     }
@@ -175,9 +433,30 @@ impl ReceiverLinkInner {
     pub(crate) fn detached(&mut self) {
         // drop pending transfers
         self.queue.clear();
+        self.partial_body = None;
         self.closed = true;
     }
 
+    /// Link is suspended by remote peer
+    ///
+    /// Incomplete delivery is dropped, remote peer resends it after link is resumed.
+    pub(crate) fn suspended(&mut self) {
+        if self.partial_body.take().is_some() {
+            self.queue.pop_back();
+            // return credit consumed by first transfer of the delivery
+            self.credit += 1;
+        }
+    }
+
+    /// Link is resumed by remote peer, remaining link credit is granted again
+    pub(crate) fn resumed(&mut self) {
+        self.last_activity = Instant::now();
+        self.session
+            .inner
+            .get_mut()
+            .rcv_link_flow(self.handle, self.delivery_count, self.credit);
+    }
+
     pub(crate) fn close(
         &mut self,
         error: Option<Error>,
@@ -206,7 +485,185 @@ impl ReceiverLinkInner {
         self.partial_body_max = size;
     }
 
+    pub(crate) fn info(&self) -> LinkInfo {
+        LinkInfo {
+            handle: self.handle,
+            name: self.attach.name().clone(),
+            role: Role::Receiver,
+            source: self.attach.source.as_ref().and_then(|s| s.address.clone()),
+            target: self
+                .attach
+                .target
+                .as_ref()
+                .and_then(|t| t.address().cloned()),
+            link_credit: self.credit,
+            delivery_count: self.delivery_count,
+        }
+    }
+
+    /// Update outcome counters
+    pub(crate) fn outcome_sent(&mut self, state: &DeliveryState, count: u64) {
+        match state {
+            DeliveryState::Accepted(_) => self.stats.accepted += count,
+            DeliveryState::Rejected(_) => self.stats.rejected += count,
+            DeliveryState::Released(_) => self.stats.released += count,
+            DeliveryState::Modified(_) => self.stats.modified += count,
+            _ => (),
+        }
+    }
+
+    pub(crate) fn max_message_size(&self) -> Option<u64> {
+        self.max_message_size
+    }
+
+    pub(crate) fn set_max_message_size(&mut self, size: u64) {
+        self.max_message_size = if size == 0 { None } else { Some(size) };
+    }
+
+    fn is_message_size_exceeded(&self, size: usize) -> bool {
+        self.max_message_size
+            .map(|max| size as u64 > max)
+            .unwrap_or(false)
+    }
+
+    fn message_size_exceeded(&mut self) {
+        let err = Error {
+            condition: LinkError::MessageSizeExceeded.into(),
+            description: None,
+            info: None,
+        };
+        let _ = self.close(Some(err));
+    }
+
+    fn reassembly_limit_exceeded(&mut self) {
+        let err = Error {
+            condition: AmqpError::ResourceLimitExceeded.into(),
+            description: Some(ByteString::from_static(
+                "Partial deliveries exceed reassembly buffer limit",
+            )),
+            info: None,
+        };
+        let _ = self.session.connection().close_with_error(err);
+    }
+
+    /// Remember settled deliveries, cache keeps most recent `settled_max` entries
+    fn settled_deliveries(
+        &mut self,
+        first: DeliveryNumber,
+        last: Option<DeliveryNumber>,
+        state: &DeliveryState,
+    ) {
+        if self.settled_max == 0 {
+            return;
+        }
+        let last = last.unwrap_or(first);
+        let oldest = last.wrapping_sub(self.settled_max as DeliveryNumber - 1);
+        let first = if serial_lt(first, oldest) {
+            oldest
+        } else {
+            first
+        };
+        for id in serial_range(first, last) {
+            if self.settled.len() >= self.settled_max {
+                self.settled.pop_front();
+            }
+            self.settled.push_back((id, state.clone()));
+        }
+    }
+
+    /// Re-send disposition for already settled delivery
+    fn resettle(&mut self, id: DeliveryNumber) -> bool {
+        let state = self
+            .settled
+            .iter()
+            .find(|(settled_id, _)| *settled_id == id)
+            .map(|(_, state)| state.clone());
+
+        if let Some(state) = state {
+            trace!("Delivery {} is already settled, re-send disposition", id);
+            let disp = Disposition {
+                role: Role::Receiver,
+                first: id,
+                last: None,
+                settled: true,
+                state: Some(state),
+                batchable: false,
+            };
+            self.session.inner.get_mut().post_frame(disp.into());
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Deliveries settled by remote sender
+    pub(crate) fn remote_settled(&mut self, first: DeliveryNumber, last: Option<DeliveryNumber>) {
+        for id in serial_range(first, last.unwrap_or(first)) {
+            self.unsettled.remove(&id);
+            self.outcomes.remove(&id);
+        }
+    }
+
+    /// Unsettled deliveries with outcome chosen by application
+    ///
+    /// Deliveries without outcome, i.e. not yet read by application, stay unsettled.
+    pub(crate) fn take_outcomes(&mut self) -> Vec<(DeliveryNumber, DeliveryState)> {
+        let outcomes: Vec<_> = self.outcomes.drain().collect();
+        for (id, _) in &outcomes {
+            self.unsettled.remove(id);
+        }
+        outcomes
+    }
+
+    /// Pass complete delivery to the link
+    fn deliver(&mut self, mut transfer: Transfer) {
+        // in `Settled` mode sender settles all deliveries
+        if self.attach.snd_settle_mode == SenderSettleMode::Settled {
+            transfer.settled = Some(true);
+        }
+        if self.release_if_expired(&transfer) {
+            return;
+        }
+        if transfer.settled != Some(true) {
+            if let Some(id) = transfer.delivery_id {
+                self.unsettled.insert(id);
+            }
+        }
+        self.queue.push_back(transfer);
+        if self.queue.len() == 1 {
+            self.reader_task.wake()
+        }
+    }
+
+    /// Release delivery if message is expired
+    fn release_if_expired(&mut self, transfer: &Transfer) -> bool {
+        if !self.release_expired || !is_expired(transfer.body.as_ref()) {
+            return false;
+        }
+
+        trace!(
+            "Message is expired, release delivery {:?}",
+            transfer.delivery_id
+        );
+        if transfer.settled != Some(true) {
+            if let Some(id) = transfer.delivery_id {
+                let disp = Disposition {
+                    role: Role::Receiver,
+                    first: id,
+                    last: None,
+                    settled: true,
+                    state: Some(DeliveryState::Released(Released {})),
+                    batchable: false,
+                };
+                self.stats.released += 1;
+                self.session.inner.get_mut().post_frame(disp.into());
+            }
+        }
+        true
+    }
+
     pub(crate) fn set_link_credit(&mut self, credit: u32) {
+        self.last_activity = Instant::now();
         self.credit += credit;
         self.session
             .inner
@@ -214,8 +671,34 @@ impl ReceiverLinkInner {
             .rcv_link_flow(self.handle as u32, self.delivery_count, credit);
     }
 
+    pub(crate) fn set_available(&mut self, available: u32) {
+        self.available = available;
+    }
+
+    /// Target terminus time-out
+    pub(crate) fn terminus_timeout(&self) -> Seconds {
+        match self.attach.target() {
+            Some(TargetTerminus::Target(target)) => target.timeout,
+            _ => 0,
+        }
+    }
+
+    /// Link has credit and no buffered transfers for longer than timeout
+    pub(crate) fn is_idle(&self, now: Instant, timeout: Duration) -> bool {
+        !self.closed
+            && self.credit > 0
+            && self.queue.is_empty()
+            && self.partial_body.is_none()
+            && now.saturating_duration_since(self.last_activity) >= timeout
+    }
+
     pub(crate) fn handle_transfer(&mut self, mut transfer: Transfer) {
-        if self.credit == 0 {
+        let span = self.span.clone();
+        let _enter = span.enter();
+        self.last_activity = Instant::now();
+
+        // link credit is consumed by first transfer of the delivery
+        if self.credit == 0 && self.partial_body.is_none() {
             // check link credit
             let err = Error {
                 condition: LinkError::TransferLimitExceeded.into(),
@@ -224,7 +707,19 @@ impl ReceiverLinkInner {
             };
             let _ = self.close(Some(err));
         } else {
-            self.credit -= 1;
+            if self.partial_body.is_none() {
+                self.credit -= 1;
+            }
+
+            if transfer.aborted {
+                // discard partially received delivery
+                trace!("Delivery is aborted: {:?}", transfer.delivery_id);
+                if self.partial_body.take().is_some() {
+                    self.queue.pop_back();
+                }
+                self.delivery_count = self.delivery_count.wrapping_add(1);
+                return;
+            }
 
             if let Some(ref mut body) = self.partial_body {
                 if transfer.delivery_id.is_some() {
@@ -245,31 +740,56 @@ impl ReceiverLinkInner {
                     }
                 }
 
-                // merge transfer data and check size
-                if let Some(transfer_body) = transfer.body.take() {
-                    if body.len() + transfer_body.len() > self.partial_body_max {
+                // all transfers of the delivery must carry same message format
+                if let Some(format) = transfer.message_format {
+                    if self
+                        .queue
+                        .back()
+                        .map(|back| back.message_format.unwrap_or(0) != format)
+                        .unwrap_or(false)
+                    {
                         let err = Error {
-                            condition: LinkError::MessageSizeExceeded.into(),
-                            description: None,
+                            condition: ConnectionError::FramingError.into(),
+                            description: Some(ByteString::from_static(
+                                "message-format of transfer is changed",
+                            )),
                             info: None,
                         };
-                        let _ = self.close(Some(err));
+                        let _ = self.session.connection().close_with_error(err);
                         return;
                     }
+                }
 
-                    transfer_body.encode(body);
+                // merge transfer data and check size
+                if let Some(transfer_body) = transfer.body.take() {
+                    let size = body.len() + transfer_body.len();
+                    if size > self.partial_body_max || self.is_message_size_exceeded(size) {
+                        self.message_size_exceeded();
+                        return;
+                    }
+
+                    if !body.extend(transfer_body) {
+                        self.reassembly_limit_exceeded();
+                        return;
+                    }
+                }
+
+                // sender could settle delivery with any of the transfers
+                if transfer.settled == Some(true) {
+                    if let Some(back) = self.queue.back_mut() {
+                        back.settled = Some(true);
+                    }
                 }
 
                 // received last partial transfer
                 if !transfer.more {
-                    self.delivery_count += 1;
+                    self.delivery_count = self.delivery_count.wrapping_add(1);
                     let partial_body = self.partial_body.take();
                     if partial_body.is_some() && !self.queue.is_empty() {
                         self.queue.back_mut().unwrap().body =
                             Some(TransferBody::Data(partial_body.unwrap().freeze()));
-                        if self.queue.len() == 1 {
-                            self.reader_task.wake()
-                        }
+                        let transfer = self.queue.pop_back().unwrap();
+                        self.deliver(transfer);
                     } else {
                         log::error!("Inconsistent state, bug");
                         let err = Error {
@@ -289,10 +809,20 @@ impl ReceiverLinkInner {
                         info: None,
                     };
                     let _ = self.close(Some(err));
+                } else if transfer
+                    .body
+                    .as_ref()
+                    .map(|body| self.is_message_size_exceeded(body.len()))
+                    .unwrap_or(false)
+                {
+                    self.message_size_exceeded();
                 } else {
                     let body = if let Some(body) = transfer.body.take() {
                         match body {
-                            TransferBody::Data(data) => BytesMut::from(data.as_ref()),
+                            // reuse buffer of the first transfer if it is not shared
+                            TransferBody::Data(data) => data
+                                .try_mut()
+                                .unwrap_or_else(|data| BytesMut::from(data.as_ref())),
                             TransferBody::Message(msg) => {
                                 let mut buf = BytesMut::with_capacity(msg.encoded_size());
                                 msg.encode(&mut buf);
@@ -302,23 +832,90 @@ impl ReceiverLinkInner {
                     } else {
                         BytesMut::new()
                     };
-                    self.partial_body = Some(body);
-                    self.queue.push_back(transfer);
+                    let conn = self.session.connection().clone();
+                    if let Some(body) = PartialBody::new(body, conn) {
+                        self.partial_body = Some(body);
+                        self.queue.push_back(transfer);
+                    } else {
+                        self.reassembly_limit_exceeded();
+                    }
                 }
+            } else if transfer
+                .delivery_id
+                .map(|id| self.resettle(id))
+                .unwrap_or(false)
+            {
+                // retransmitted delivery
+                self.delivery_count = self.delivery_count.wrapping_add(1);
+            } else if transfer
+                .body
+                .as_ref()
+                .map(|body| self.is_message_size_exceeded(body.len()))
+                .unwrap_or(false)
+            {
+                self.message_size_exceeded();
             } else {
-                self.delivery_count += 1;
-                self.queue.push_back(transfer);
-                if self.queue.len() == 1 {
-                    self.reader_task.wake()
-                }
+                self.delivery_count = self.delivery_count.wrapping_add(1);
+                self.deliver(transfer);
             }
         }
     }
 }
 
+/// Buffer of partially received delivery
+///
+/// Buffer size is accounted in reassembly buffer limit of the connection.
+struct PartialBody {
+    buf: BytesMut,
+    reserved: usize,
+    conn: Connection,
+}
+
+impl PartialBody {
+    fn new(buf: BytesMut, conn: Connection) -> Option<Self> {
+        let reserved = buf.len();
+        if conn.reserve_reassembly(reserved) {
+            Some(PartialBody {
+                buf,
+                reserved,
+                conn,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Append transfer body, `false` if reassembly buffer limit is exceeded
+    fn extend(&mut self, body: TransferBody) -> bool {
+        let size = body.len();
+        if self.conn.reserve_reassembly(size) {
+            self.reserved += size;
+            body.encode(&mut self.buf);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn freeze(mut self) -> Bytes {
+        std::mem::take(&mut self.buf).freeze()
+    }
+}
+
+impl Drop for PartialBody {
+    fn drop(&mut self) {
+        self.conn.release_reassembly(self.reserved);
+    }
+}
+
 pub struct ReceiverLinkBuilder {
     frame: Attach,
     session: Cell<SessionInner>,
+    auto_suffix: bool,
 }
 
 impl ReceiverLinkBuilder {
@@ -353,11 +950,78 @@ impl ReceiverLinkBuilder {
             properties: None,
         };
 
-        ReceiverLinkBuilder { frame, session }
+        ReceiverLinkBuilder {
+            frame,
+            session,
+            auto_suffix: false,
+        }
     }
 
+    /// Set max message size accepted by the link.
+    ///
+    /// Zero value means no limit.
     pub fn max_message_size(mut self, size: u64) -> Self {
-        self.frame.max_message_size = Some(size);
+        self.frame.max_message_size = if size == 0 { None } else { Some(size) };
+        self
+    }
+
+    /// Set link properties, i.e. `priority` or broker specific `x-opt-*` values
+    pub fn properties(mut self, properties: Fields) -> Self {
+        self.frame.properties = Some(properties);
+        self
+    }
+
+    /// Set durability of the source terminus.
+    ///
+    /// Durable subscriptions use `TerminusDurability::UnsettledState`
+    /// with non-zero expiry timeout to survive reconnects.
+    ///
+    /// Default is `TerminusDurability::None`
+    pub fn durable(self, durable: TerminusDurability) -> Self {
+        self.with_source(|source| source.durable = durable)
+    }
+
+    /// Set expiry policy of the source terminus.
+    ///
+    /// Default is `TerminusExpiryPolicy::SessionEnd`
+    pub fn expiry_policy(self, policy: TerminusExpiryPolicy) -> Self {
+        self.with_source(|source| source.expiry_policy = policy)
+    }
+
+    /// Set time in seconds the source terminus is retained after expiry policy triggers.
+    pub fn expiry_timeout(self, timeout: Seconds) -> Self {
+        self.with_source(|source| source.timeout = timeout)
+    }
+
+    /// Set capabilities of the source terminus.
+    pub fn capabilities(self, capabilities: Symbols) -> Self {
+        self.with_source(|source| source.capabilities = Some(capabilities))
+    }
+
+    fn with_source<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut Source),
+    {
+        if let Some(ref mut source) = self.frame.source {
+            f(source);
+        }
+        self
+    }
+
+    /// Append numeric suffix to link name if name is already in use.
+    ///
+    /// By default opening link with name in use fails,
+    /// final name is available via `ReceiverLink::frame()`
+    pub fn auto_suffix_name(mut self) -> Self {
+        self.auto_suffix = true;
+        self
+    }
+
+    /// Set receiver settle mode.
+    ///
+    /// Default is `First`
+    pub fn rcv_settle_mode(mut self, mode: ReceiverSettleMode) -> Self {
+        self.frame.rcv_settle_mode = mode;
         self
     }
 
@@ -366,7 +1030,7 @@ impl ReceiverLinkBuilder {
         let res = self
             .session
             .get_mut()
-            .open_local_receiver_link(cell, self.frame)
+            .open_local_receiver_link(cell, self.frame, self.auto_suffix)?
             .await;
 
         match res {
@@ -376,3 +1040,255 @@ impl ReceiverLinkBuilder {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ntex::framed::State;
+
+    use ntex_amqp_codec::protocol::{Accepted, Timestamp};
+    use ntex_amqp_codec::{Decode, Message};
+
+    use super::*;
+    use crate::connection::{Connection, ConnectionStatus};
+    use crate::Configuration;
+
+    fn receiver_link() -> ReceiverLinkInner {
+        let config = Configuration::default();
+        let conn = Connection::new(State::new(), &config, &config);
+        let session = Cell::new(SessionInner::new(0, true, conn, 0, 1, 1, 1, u32::MAX));
+        let attach = ReceiverLinkBuilder::new("test".into(), "test".into(), session.clone()).frame;
+
+        let mut link = ReceiverLinkInner::new(session, 0, attach);
+        link.credit = 10;
+        link
+    }
+
+    fn transfer(delivery_id: Option<DeliveryNumber>, more: bool, aborted: bool) -> Transfer {
+        Transfer {
+            handle: 0,
+            delivery_id,
+            delivery_tag: delivery_id.map(|_| Bytes::from_static(b"tag")),
+            message_format: None,
+            settled: None,
+            more,
+            rcv_settle_mode: None,
+            state: None,
+            resume: false,
+            aborted,
+            batchable: false,
+            body: Some(TransferBody::Data(Bytes::from_static(b"data"))),
+        }
+    }
+
+    #[test]
+    fn test_aborted_transfer() {
+        let mut link = receiver_link();
+
+        link.handle_transfer(transfer(Some(1), true, false));
+        link.handle_transfer(transfer(None, true, false));
+        link.handle_transfer(transfer(None, false, true));
+        assert!(link.partial_body.is_none());
+        assert!(link.queue.is_empty());
+        assert!(!link.closed);
+
+        link.handle_transfer(transfer(Some(2), false, false));
+        assert_eq!(link.queue.len(), 1);
+        assert_eq!(link.queue[0].delivery_id, Some(2));
+        assert_eq!(link.delivery_count, 2);
+    }
+
+    fn expiring_transfer(delivery_id: DeliveryNumber, expiry_millis: i64) -> Transfer {
+        let mut buf = vec![0x83];
+        buf.extend_from_slice(&expiry_millis.to_be_bytes());
+        let (_, expiry) = Timestamp::decode(&buf).unwrap();
+
+        let mut msg = Message::default();
+        msg.properties_mut().absolute_expiry_time = Some(expiry);
+
+        let mut transfer = transfer(Some(delivery_id), false, false);
+        transfer.body = Some(TransferBody::Message(Box::new(msg)));
+        transfer
+    }
+
+    #[test]
+    fn test_release_expired() {
+        let mut link = receiver_link();
+        link.handle_transfer(expiring_transfer(1, 1000));
+        assert_eq!(link.queue.len(), 1);
+
+        let mut link = receiver_link();
+        link.release_expired = true;
+        link.handle_transfer(expiring_transfer(1, 1000));
+        link.handle_transfer(expiring_transfer(2, 32_503_680_000_000));
+        link.handle_transfer(transfer(Some(3), false, false));
+        assert_eq!(link.queue.len(), 2);
+        assert_eq!(link.queue[0].delivery_id, Some(2));
+        assert_eq!(link.delivery_count, 3);
+    }
+
+    #[test]
+    fn test_unsettled_deliveries() {
+        let mut link = receiver_link();
+        link.handle_transfer(transfer(Some(1), false, false));
+        link.handle_transfer(transfer(Some(2), false, false));
+        let mut settled = transfer(Some(3), false, false);
+        settled.settled = Some(true);
+        link.handle_transfer(settled);
+
+        let link = ReceiverLink::new(Cell::new(link));
+        link.send_disposition(Disposition {
+            role: Role::Receiver,
+            first: 1,
+            last: None,
+            settled: true,
+            state: Some(DeliveryState::Accepted(Accepted {})),
+            batchable: false,
+        });
+        let inner = link.inner.get_mut();
+        assert!(inner.take_outcomes().is_empty());
+        assert!(inner.unsettled.contains(&2));
+        assert_eq!(inner.unsettled.len(), 1);
+    }
+
+    #[test]
+    fn test_unsettled_outcomes() {
+        let mut link = receiver_link();
+        link.attach.rcv_settle_mode = ReceiverSettleMode::Second;
+        link.handle_transfer(transfer(Some(1), false, false));
+        link.handle_transfer(transfer(Some(2), false, false));
+        let frame = link.queue.pop_front().unwrap();
+
+        // outcome of delivery 1 is chosen, delivery 2 is not read
+        let link = ReceiverLink::new(Cell::new(link));
+        IncomingTransfer {
+            frame,
+            link: link.clone(),
+        }
+        .accept();
+
+        let inner = link.inner.get_mut();
+        let outcomes = inner.take_outcomes();
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(outcomes[0].0, 1);
+        assert!(matches!(outcomes[0].1, DeliveryState::Accepted(_)));
+        assert!(!inner.unsettled.contains(&1));
+        assert!(inner.unsettled.contains(&2));
+    }
+
+    #[test]
+    fn test_link_stats() {
+        let link = ReceiverLink::new(Cell::new(receiver_link()));
+        link.send_disposition(Disposition {
+            role: Role::Receiver,
+            first: 1,
+            last: Some(3),
+            settled: true,
+            state: Some(DeliveryState::Accepted(Accepted {})),
+            batchable: false,
+        });
+        link.send_disposition(Disposition {
+            role: Role::Receiver,
+            first: 4,
+            last: None,
+            settled: true,
+            state: Some(DeliveryState::Released(Released {})),
+            batchable: false,
+        });
+        assert_eq!(
+            link.stats(),
+            LinkStats {
+                accepted: 3,
+                released: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_settled_snd_settle_mode() {
+        let mut link = receiver_link();
+        link.attach.snd_settle_mode = SenderSettleMode::Settled;
+        link.handle_transfer(transfer(Some(1), false, false));
+        assert_eq!(link.queue[0].settled, Some(true));
+        assert!(link.unsettled.is_empty());
+    }
+
+    #[ntex::test]
+    async fn test_message_format_mismatch() {
+        let mut link = receiver_link();
+        let mut first = transfer(Some(1), true, false);
+        first.message_format = Some(1);
+        link.handle_transfer(first);
+
+        let mut second = transfer(None, true, false);
+        second.message_format = Some(1);
+        link.handle_transfer(second);
+        assert_eq!(link.session.connection().status(), ConnectionStatus::Open);
+
+        let mut last = transfer(None, false, false);
+        last.message_format = Some(2);
+        link.handle_transfer(last);
+        assert_eq!(
+            link.session.connection().status(),
+            ConnectionStatus::Closing
+        );
+    }
+
+    #[ntex::test]
+    async fn test_reassembly_buffer_max() {
+        let mut config = Configuration::default();
+        config.reassembly_buffer_max(10);
+        let conn = Connection::new(State::new(), &config, &config);
+        let session = Cell::new(SessionInner::new(
+            0,
+            true,
+            conn.clone(),
+            0,
+            1,
+            1,
+            1,
+            u32::MAX,
+        ));
+        let link = |handle| {
+            let attach = ReceiverLinkBuilder::new("test".into(), "test".into(), session.clone());
+            let mut link = ReceiverLinkInner::new(session.clone(), handle, attach.frame);
+            link.credit = 10;
+            link
+        };
+        let mut link1 = link(0);
+        let mut link2 = link(1);
+
+        // aborted delivery releases its buffer
+        link1.handle_transfer(transfer(Some(1), true, false));
+        link1.handle_transfer(transfer(None, true, false));
+        link1.handle_transfer(transfer(None, false, true));
+
+        link1.handle_transfer(transfer(Some(2), true, false));
+        link2.handle_transfer(transfer(Some(3), true, false));
+        assert_eq!(conn.status(), ConnectionStatus::Open);
+
+        // limit is shared by all links of the connection
+        link2.handle_transfer(transfer(None, true, false));
+        assert_eq!(conn.status(), ConnectionStatus::Closing);
+    }
+
+    #[test]
+    fn test_second_settle_mode() {
+        let mut link = receiver_link();
+        link.attach.rcv_settle_mode = ReceiverSettleMode::Second;
+        link.handle_transfer(transfer(Some(1), false, false));
+        let frame = link.queue.pop_front().unwrap();
+
+        let link = ReceiverLink::new(Cell::new(link));
+        IncomingTransfer {
+            frame,
+            link: link.clone(),
+        }
+        .accept();
+        assert!(link.inner.get_ref().unsettled.contains(&1));
+
+        link.inner.get_mut().remote_settled(1, None);
+        assert!(link.inner.get_ref().unsettled.is_empty());
+        assert!(link.inner.get_ref().outcomes.is_empty());
+    }
+}
@@ -6,9 +6,12 @@ use ntex::service::{boxed, fn_factory_with_config, IntoServiceFactory, Service,
 use ntex::util::{Either, Ready};
 use ntex::Stream;
 
-use crate::codec::protocol::{DeliveryNumber, DeliveryState, Disposition, Error, Rejected, Role};
-use crate::error::LinkError;
+use crate::codec::protocol::{
+    DeliveryNumber, DeliveryState, Disposition, Error, ReceiverSettleMode, Role,
+};
+use crate::error::{AmqpError, LinkError};
 use crate::types::{Link, Outcome, Transfer};
+use crate::utils::{catch_unwind, CatchUnwind};
 use crate::{cell::Cell, rcvlink::ReceiverLink, State};
 
 type Handle<S> = boxed::BoxServiceFactory<Link<S>, Transfer<S>, Outcome, Error, Error>;
@@ -85,7 +88,7 @@ impl<S: 'static> Service for RouterService<S> {
             .frame()
             .target
             .as_ref()
-            .and_then(|target| target.address.as_ref().cloned());
+            .and_then(|target| target.address().cloned());
 
         if let Some(path) = path {
             link.path_mut().set(path);
@@ -157,7 +160,7 @@ impl<S> Future for RouterServiceResponse<S> {
                                     .frame()
                                     .target
                                     .as_ref()
-                                    .map(|t| t.address.as_ref().map(|s| s.as_ref()).unwrap_or(""))
+                                    .and_then(|t| t.address().map(|s| s.as_ref()))
                                     .unwrap_or("")
                             );
                             return Poll::Pending;
@@ -190,32 +193,34 @@ impl<S> Future for RouterServiceResponse<S> {
                                         link.set_link_credit(50);
                                     }
 
+                                    // pre-settled deliveries do not require disposition
+                                    let presettled = transfer.settled == Some(true);
                                     let msg =
                                         Transfer::new(app_state.clone(), transfer, link.clone());
 
                                     let mut fut = srv.call(msg);
                                     match Pin::new(&mut fut).poll(cx) {
-                                        Poll::Ready(Ok(outcome)) => settle(
-                                            &mut this.link,
-                                            delivery_id,
-                                            outcome.into_delivery_state(),
-                                        ),
+                                        Poll::Ready(Ok(outcome)) => {
+                                            if !presettled {
+                                                settle(
+                                                    &mut this.link,
+                                                    delivery_id,
+                                                    outcome.into_delivery_state(),
+                                                )
+                                            }
+                                        }
                                         Poll::Pending => {
                                             ntex::rt::spawn(HandleMessage {
-                                                fut,
+                                                fut: catch_unwind(fut),
                                                 delivery_id,
+                                                presettled,
                                                 link: this.link.clone(),
                                             });
                                         }
                                         Poll::Ready(Err(e)) => {
                                             log::trace!("Service response error: {:?}", e);
-                                            settle(
-                                                &mut this.link,
-                                                delivery_id,
-                                                DeliveryState::Rejected(Rejected {
-                                                    error: Some(e),
-                                                }),
-                                            )
+                                            let _ = this.link.close_with_error(e);
+                                            return Poll::Ready(Ok(()));
                                         }
                                     }
                                 }
@@ -243,7 +248,7 @@ impl<S> Future for RouterServiceResponse<S> {
                                 .frame()
                                 .target
                                 .as_ref()
-                                .map(|t| t.address.as_ref().map(|s| s.as_ref()).unwrap_or(""))
+                                .and_then(|t| t.address().map(|s| s.as_ref()))
                                 .unwrap_or("")
                         );
                         this.link.open();
@@ -258,7 +263,7 @@ impl<S> Future for RouterServiceResponse<S> {
                                 .frame()
                                 .target
                                 .as_ref()
-                                .map(|t| t.address.as_ref().map(|s| s.as_ref()).unwrap_or(""))
+                                .and_then(|t| t.address().map(|s| s.as_ref()))
                                 .unwrap_or(""),
                             e
                         );
@@ -274,7 +279,8 @@ impl<S> Future for RouterServiceResponse<S> {
 struct HandleMessage {
     link: ReceiverLink,
     delivery_id: DeliveryNumber,
-    fut: Pin<Box<dyn Future<Output = Result<Outcome, Error>>>>,
+    presettled: bool,
+    fut: CatchUnwind<Pin<Box<dyn Future<Output = Result<Outcome, Error>>>>>,
 }
 
 impl Future for HandleMessage {
@@ -285,7 +291,7 @@ impl Future for HandleMessage {
 
         match Pin::new(&mut this.fut).poll(cx) {
             Poll::Pending => Poll::Pending,
-            Poll::Ready(Ok(outcome)) => {
+            Poll::Ready(Ok(Ok(outcome))) => {
                 log::trace!(
                     "Outcome is ready {:?} for {}",
                     outcome,
@@ -293,14 +299,16 @@ impl Future for HandleMessage {
                         .frame()
                         .target
                         .as_ref()
-                        .map(|t| t.address.as_ref().map(|s| s.as_ref()).unwrap_or(""))
+                        .and_then(|t| t.address().map(|s| s.as_ref()))
                         .unwrap_or("")
                 );
-                let delivery_id = this.delivery_id;
-                settle(&mut this.link, delivery_id, outcome.into_delivery_state());
+                if !this.presettled {
+                    let delivery_id = this.delivery_id;
+                    settle(&mut this.link, delivery_id, outcome.into_delivery_state());
+                }
                 Poll::Ready(())
             }
-            Poll::Ready(Err(e)) => {
+            Poll::Ready(Ok(Err(e))) => {
                 log::trace!(
                     "Outcome is failed {:?} for {}",
                     e,
@@ -308,15 +316,25 @@ impl Future for HandleMessage {
                         .frame()
                         .target
                         .as_ref()
-                        .map(|t| t.address.as_ref().map(|s| s.as_ref()).unwrap_or(""))
+                        .and_then(|t| t.address().map(|s| s.as_ref()))
                         .unwrap_or("")
                 );
 
-                let delivery_id = this.delivery_id;
-                settle(
-                    &mut this.link,
-                    delivery_id,
-                    DeliveryState::Rejected(Rejected { error: Some(e) }),
+                let _ = this.link.close_with_error(e);
+                Poll::Ready(())
+            }
+            Poll::Ready(Err(_)) => {
+                log::error!(
+                    "Link service panicked for {}",
+                    this.link
+                        .frame()
+                        .target
+                        .as_ref()
+                        .and_then(|t| t.address().map(|s| s.as_ref()))
+                        .unwrap_or("")
+                );
+                let _ = this.link.close_with_error(
+                    AmqpError::internal_error().description("Link service panicked"),
                 );
                 Poll::Ready(())
             }
@@ -325,12 +343,14 @@ impl Future for HandleMessage {
 }
 
 fn settle(link: &mut ReceiverLink, id: DeliveryNumber, state: DeliveryState) {
+    // in second mode delivery is settled by remote sender first
+    let settled = link.rcv_settle_mode() == ReceiverSettleMode::First;
     let disposition = Disposition {
         state: Some(state),
         role: Role::Receiver,
         first: id,
         last: None,
-        settled: true,
+        settled,
         batchable: false,
     };
     link.send_disposition(disposition);
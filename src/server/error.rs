@@ -2,7 +2,7 @@ use derive_more::Display;
 use ntex::util::{ByteString, Either};
 
 use crate::codec::{protocol, AmqpCodecError, AmqpFrame, ProtocolIdError, SaslFrame};
-use crate::error::AmqpProtocolError;
+use crate::error::{AmqpProtocolError, ConfigError};
 
 /// Errors which can occur when attempting to handle amqp connection.
 #[derive(Debug, Display)]
@@ -19,6 +19,9 @@ pub enum ServerError<E> {
     /// Amqp protocol error
     #[display(fmt = "Amqp protocol error: {:?}", _0)]
     Protocol(AmqpProtocolError),
+    /// Invalid connection configuration
+    #[display(fmt = "Configuration error: {}", _0)]
+    Config(ConfigError),
     /// Control service init error
     #[display(fmt = "Control service init error")]
     ControlServiceError,
@@ -27,6 +30,9 @@ pub enum ServerError<E> {
     PublishServiceError,
     /// Peer disconnect
     Disconnected,
+    /// Server is shutting down
+    #[display(fmt = "Server is shutting down")]
+    Shutdown,
 }
 
 impl<E> From<AmqpCodecError> for ServerError<E> {
@@ -74,6 +80,10 @@ pub enum HandshakeError {
     /// Sasl error code
     #[display(fmt = "Sasl error code: {:?}", _0)]
     Sasl(protocol::SaslCode),
+    #[from(ignore)]
+    /// Connection is rejected by handshake service
+    #[display(fmt = "Connection is rejected: {:?}", _0)]
+    Rejected(protocol::Error),
     #[display(fmt = "Peer disconnected")]
     Disconnected,
     /// Unexpected io error
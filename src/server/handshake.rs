@@ -2,12 +2,13 @@ use std::rc::Rc;
 
 use ntex::codec::{AsyncRead, AsyncWrite};
 use ntex::framed::State;
+use ntex::util::ByteString;
 
-use crate::codec::protocol::{Frame, Open};
+use crate::codec::protocol::{Error, Frame, Open, SaslMechanism};
 use crate::codec::{AmqpCodec, AmqpFrame};
 use crate::{connection::Connection, Configuration};
 
-use super::{error::HandshakeError, sasl::Sasl};
+use super::{error::HandshakeError, sasl::Identity, sasl::Sasl};
 
 /// Connection handshake
 pub enum Handshake<Io> {
@@ -17,11 +18,7 @@ pub enum Handshake<Io> {
 
 impl<Io> Handshake<Io> {
     pub(crate) fn new_plain(io: Io, state: State, local_config: Rc<Configuration>) -> Self {
-        Handshake::Amqp(HandshakeAmqp {
-            io,
-            state,
-            local_config,
-        })
+        Handshake::Amqp(HandshakeAmqp::new(io, state, local_config, None, None))
     }
 
     pub(crate) fn new_sasl(io: Io, state: State, local_config: Rc<Configuration>) -> Self {
@@ -34,9 +31,37 @@ pub struct HandshakeAmqp<Io> {
     io: Io,
     state: State,
     local_config: Rc<Configuration>,
+    identity: Option<Identity>,
+    sasl_mechanism: Option<SaslMechanism>,
 }
 
 impl<Io> HandshakeAmqp<Io> {
+    pub(crate) fn new(
+        io: Io,
+        state: State,
+        local_config: Rc<Configuration>,
+        identity: Option<Identity>,
+        sasl_mechanism: Option<SaslMechanism>,
+    ) -> Self {
+        HandshakeAmqp {
+            io,
+            state,
+            local_config,
+            identity,
+            sasl_mechanism,
+        }
+    }
+
+    /// Identity of the client authenticated by server's sasl authenticator
+    pub fn identity(&self) -> Option<&Identity> {
+        self.identity.as_ref()
+    }
+
+    /// Sasl mechanism used for client authentication, `None` for plain amqp
+    pub fn sasl_mechanism(&self) -> Option<&SaslMechanism> {
+        self.sasl_mechanism.as_ref()
+    }
+
     /// Returns reference to io object
     pub fn get_ref(&self) -> &Io {
         &self.io
@@ -54,7 +79,8 @@ impl<Io: AsyncRead + AsyncWrite + Unpin> HandshakeAmqp<Io> {
         let mut io = self.io;
         let state = self.state;
         let local_config = self.local_config;
-        let codec = AmqpCodec::<AmqpFrame>::new();
+        let codec =
+            AmqpCodec::<AmqpFrame>::new().max_size(local_config.handshake_max_frame_size as usize);
 
         let frame = state
             .next(&mut io, &codec)
@@ -71,6 +97,7 @@ impl<Io: AsyncRead + AsyncWrite + Unpin> HandshakeAmqp<Io> {
                 trace!("Got open frame: {:?}", frame);
                 let remote_config = (&frame).into();
                 let sink = Connection::new(state.clone(), &local_config, &remote_config);
+                sink.set_sasl(self.sasl_mechanism, self.identity);
                 Ok(HandshakeAmqpOpened {
                     frame,
                     io,
@@ -96,24 +123,6 @@ pub struct HandshakeAmqpOpened<Io> {
 }
 
 impl<Io> HandshakeAmqpOpened<Io> {
-    pub(crate) fn new(
-        frame: Open,
-        io: Io,
-        sink: Connection,
-        state: State,
-        local_config: Rc<Configuration>,
-        remote_config: Configuration,
-    ) -> Self {
-        Self {
-            frame,
-            io,
-            sink,
-            state,
-            local_config,
-            remote_config,
-        }
-    }
-
     /// Get reference to remote `Open` frame
     pub fn frame(&self) -> &Open {
         &self.frame
@@ -144,29 +153,94 @@ impl<Io> HandshakeAmqpOpened<Io> {
         &self.sink
     }
 
+    /// Container id of connection which requests exclusive access
+    fn sole_container_id(&self) -> Option<ByteString> {
+        if self.local_config.sole_connection_for_container
+            && self.remote_config.sole_connection_for_container
+        {
+            Some(self.frame.container_id.clone())
+        } else {
+            None
+        }
+    }
+
     /// Ack connect message and set state
     pub fn ack<St>(self, st: St) -> HandshakeAck<Io, St> {
         HandshakeAck {
-            st,
+            container_id: self.sole_container_id(),
+            st: Ok(st),
+            io: self.io,
+            sink: self.sink,
+            state: self.state,
+            idle_timeout: self.remote_config.timeout_remote_millis(),
+            open: self.local_config.to_open(),
+        }
+    }
+
+    /// Reject connect message
+    ///
+    /// Server sends `Open` frame followed by `Close` frame with provided error,
+    /// so peer gets a reason for closed connection.
+    pub fn reject<St, E>(self, error: E) -> HandshakeAck<Io, St>
+    where
+        Error: From<E>,
+    {
+        HandshakeAck {
+            container_id: self.sole_container_id(),
+            st: Err(error.into()),
             io: self.io,
             sink: self.sink,
             state: self.state,
-            idle_timeout: self.remote_config.timeout_remote_secs(),
+            idle_timeout: self.remote_config.timeout_remote_millis(),
+            open: self.local_config.to_open(),
         }
     }
 }
 
 /// Handshake ack message
 pub struct HandshakeAck<Io, St> {
-    st: St,
+    st: Result<St, Error>,
+    container_id: Option<ByteString>,
     io: Io,
     sink: Connection,
     state: State,
-    idle_timeout: usize,
+    idle_timeout: u64,
+    open: Open,
 }
 
 impl<Io, St> HandshakeAck<Io, St> {
-    pub(crate) fn into_inner(self) -> (St, Io, Connection, State, usize) {
-        (self.st, self.io, self.sink, self.state, self.idle_timeout)
+    /// Modify `Open` frame that is sent to the peer
+    ///
+    /// Frame is built from local configuration, this method could be used
+    /// for setting per-connection properties or capabilities. Connection
+    /// limits are negotiated with local configuration and must not be changed.
+    pub fn modify_open<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut Open),
+    {
+        f(&mut self.open);
+        self
+    }
+
+    pub(crate) fn into_inner(
+        self,
+    ) -> (
+        Result<St, Error>,
+        Io,
+        Connection,
+        State,
+        u64,
+        Option<ByteString>,
+        Open,
+    ) {
+        (
+            self.st,
+            self.io,
+            self.sink,
+            self.state,
+            self.idle_timeout,
+            self.container_id,
+            self.open,
+        )
     }
 }
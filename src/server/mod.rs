@@ -5,10 +5,10 @@ mod service;
 
 pub use self::error::{HandshakeError, ServerError};
 pub use self::handshake::{Handshake, HandshakeAck, HandshakeAmqp, HandshakeAmqpOpened};
-pub use self::sasl::Sasl;
+pub use self::sasl::{Identity, Sasl, SaslAuthenticator};
 pub use self::service::Server;
 pub use crate::control::{ControlFrame, ControlFrameKind};
-pub use crate::error::{Error, LinkError};
+pub use crate::error::{Error, LinkError, PublishError};
 pub use crate::router::Router;
 pub use crate::state::State;
 pub use crate::types::{Link, Outcome, Transfer};
@@ -1,16 +1,149 @@
-use std::{fmt, rc::Rc};
+use std::time::{Duration, Instant};
+use std::{fmt, future::Future, pin::Pin, rc::Rc};
 
 use ntex::codec::{AsyncRead, AsyncWrite};
 use ntex::framed::State;
+use ntex::rt::time::sleep;
 use ntex::util::{ByteString, Bytes};
 
 use crate::codec::protocol::{
-    self, ProtocolId, SaslChallenge, SaslCode, SaslFrameBody, SaslMechanisms, SaslOutcome, Symbols,
+    self, ProtocolId, SaslChallenge, SaslCode, SaslFrameBody, SaslMechanism, SaslMechanisms,
+    SaslOutcome, Symbols,
 };
-use crate::codec::{AmqpCodec, AmqpFrame, ProtocolIdCodec, ProtocolIdError, SaslFrame};
+use crate::codec::types::Symbol;
+use crate::codec::{AmqpCodec, ProtocolIdCodec, ProtocolIdError, SaslFrame};
 
-use super::{handshake::HandshakeAmqpOpened, HandshakeError};
-use crate::{connection::Connection, Configuration};
+use super::{handshake::HandshakeAmqp, handshake::HandshakeAmqpOpened, HandshakeError};
+use crate::Configuration;
+
+/// Identity of the client authenticated during sasl negotiation
+#[derive(Clone, Debug, PartialEq)]
+pub struct Identity {
+    name: ByteString,
+}
+
+impl Identity {
+    /// Create identity with name of authenticated client
+    pub fn new<T: Into<ByteString>>(name: T) -> Self {
+        Identity { name: name.into() }
+    }
+
+    /// Name of authenticated client
+    pub fn name(&self) -> &ByteString {
+        &self.name
+    }
+}
+
+/// Credentials check for sasl negotiation handled by server
+///
+/// Server sends supported mechanisms, receives `sasl-init` frame and
+/// replies with `sasl-outcome` frame, only credentials check is delegated
+/// to the authenticator. See `Server::sasl_authenticator()`
+pub trait SaslAuthenticator: 'static {
+    type Future: Future<Output = Result<Identity, SaslCode>> + 'static;
+
+    /// Sasl mechanisms supported by authenticator
+    fn mechanisms(&self) -> Vec<SaslMechanism>;
+
+    /// Check credentials sent by client in `sasl-init` frame
+    fn authenticate(&self, mechanism: SaslMechanism, init: &protocol::SaslInit) -> Self::Future;
+}
+
+type AuthenticateFn = dyn Fn(
+    SaslMechanism,
+    &protocol::SaslInit,
+) -> Pin<Box<dyn Future<Output = Result<Identity, SaslCode>>>>;
+
+pub(crate) struct Authenticator {
+    mechanisms: Symbols,
+    authenticate: Box<AuthenticateFn>,
+}
+
+impl Authenticator {
+    pub(crate) fn new<A: SaslAuthenticator>(auth: A) -> Self {
+        let mechanisms = Symbols::from(
+            auth.mechanisms()
+                .into_iter()
+                .map(|m| m.into())
+                .collect::<Vec<_>>(),
+        );
+
+        Authenticator {
+            mechanisms,
+            authenticate: Box::new(move |mechanism, init| {
+                Box::pin(auth.authenticate(mechanism, init))
+            }),
+        }
+    }
+
+    /// Run sasl negotiation and wait for amqp protocol header
+    ///
+    /// Non-zero `failure_delay` enables uniform failure outcome.
+    pub(crate) async fn authenticate<Io>(
+        &self,
+        io: Io,
+        state: State,
+        local_config: Rc<Configuration>,
+        failure_delay: Duration,
+    ) -> Result<HandshakeAmqp<Io>, HandshakeError>
+    where
+        Io: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut sasl = Sasl::new(io, state, local_config);
+        sasl.mechanisms = self.mechanisms.clone();
+
+        let init = sasl.init().await?;
+        let deadline = if failure_delay.as_millis() != 0 {
+            Some(Instant::now() + failure_delay)
+        } else {
+            None
+        };
+
+        if !self
+            .mechanisms
+            .iter()
+            .any(|m| m.as_str() == init.mechanism())
+        {
+            let mechanism = init.mechanism().to_string();
+            failure(init, SaslCode::Auth, deadline).await?;
+            return Err(HandshakeError::UnsupportedSaslMechanism(mechanism));
+        }
+
+        match (self.authenticate)(init.sasl_mechanism(), &init.frame).await {
+            Ok(identity) => {
+                trace!("Sasl client is authenticated: {:?}", identity);
+                init.outcome(SaslCode::Ok)
+                    .await?
+                    .into_amqp(Some(identity))
+                    .await
+            }
+            Err(code) => {
+                trace!("Sasl authentication failed: {:?}", code);
+                failure(init, code, deadline).await?;
+                Err(HandshakeError::Sasl(code))
+            }
+        }
+    }
+}
+
+/// Send failed outcome, with deadline `Auth` code is sent once deadline is reached
+async fn failure<Io>(
+    init: SaslInit<Io>,
+    code: SaslCode,
+    deadline: Option<Instant>,
+) -> Result<(), HandshakeError>
+where
+    Io: AsyncRead + AsyncWrite + Unpin,
+{
+    let code = if let Some(deadline) = deadline {
+        sleep(deadline.saturating_duration_since(Instant::now())).await;
+        SaslCode::Auth
+    } else {
+        code
+    };
+    init.outcome(code).await?;
+    Ok(())
+}
 
 pub struct Sasl<Io> {
     io: Io,
@@ -53,8 +186,8 @@ where
     }
 
     /// Add supported sasl mechanism
-    pub fn mechanism<U: Into<String>>(mut self, symbol: U) -> Self {
-        self.mechanisms.push(ByteString::from(symbol.into()).into());
+    pub fn mechanism<U: Into<SaslMechanism>>(mut self, mechanism: U) -> Self {
+        self.mechanisms.push(mechanism.into().into());
         self
     }
 
@@ -68,12 +201,15 @@ where
             ..
         } = self;
 
-        let frame = SaslMechanisms {
+        let frame: SaslFrame = SaslMechanisms {
             sasl_server_mechanisms: mechanisms,
         }
         .into();
+        #[cfg(feature = "sasl-trace")]
+        crate::utils::trace_sasl("outgoing", &frame.body);
 
-        let codec = AmqpCodec::<SaslFrame>::new();
+        let codec =
+            AmqpCodec::<SaslFrame>::new().max_size(local_config.handshake_max_frame_size as usize);
         state
             .send(&mut io, &codec, frame)
             .await
@@ -83,6 +219,8 @@ where
             .await
             .map_err(HandshakeError::from)?
             .ok_or(HandshakeError::Disconnected)?;
+        #[cfg(feature = "sasl-trace")]
+        crate::utils::trace_sasl("incoming", &frame.body);
 
         match frame.body {
             SaslFrameBody::SaslInit(frame) => Ok(SaslInit {
@@ -123,6 +261,11 @@ where
         self.frame.mechanism.as_str()
     }
 
+    /// Parsed sasl mechanism
+    pub fn sasl_mechanism(&self) -> SaslMechanism {
+        self.frame.sasl_mechanism()
+    }
+
     /// Sasl initial response
     pub fn initial_response(&self) -> Option<&[u8]> {
         self.frame.initial_response.as_ref().map(|b| b.as_ref())
@@ -157,7 +300,9 @@ where
         let state = self.state;
         let codec = self.codec;
         let local_config = self.local_config;
-        let frame = SaslChallenge { challenge }.into();
+        let frame: SaslFrame = SaslChallenge { challenge }.into();
+        #[cfg(feature = "sasl-trace")]
+        crate::utils::trace_sasl("outgoing", &frame.body);
 
         state
             .send(&mut io, &codec, frame)
@@ -168,10 +313,13 @@ where
             .await
             .map_err(HandshakeError::from)?
             .ok_or(HandshakeError::Disconnected)?;
+        #[cfg(feature = "sasl-trace")]
+        crate::utils::trace_sasl("incoming", &frame.body);
 
         match frame.body {
             SaslFrameBody::SaslResponse(frame) => Ok(SaslResponse {
                 frame,
+                mechanism: self.frame.mechanism,
                 io,
                 state,
                 codec,
@@ -187,12 +335,15 @@ where
         let state = self.state;
         let codec = self.codec;
         let local_config = self.local_config;
+        let mechanism = self.frame.mechanism;
 
-        let frame = SaslOutcome {
+        let frame: SaslFrame = SaslOutcome {
             code,
             additional_data: None,
         }
         .into();
+        #[cfg(feature = "sasl-trace")]
+        crate::utils::trace_sasl("outgoing", &frame.body);
         state
             .send(&mut io, &codec, frame)
             .await
@@ -202,12 +353,14 @@ where
             io,
             state,
             local_config,
+            mechanism,
         })
     }
 }
 
 pub struct SaslResponse<Io> {
     frame: protocol::SaslResponse,
+    mechanism: Symbol,
     io: Io,
     state: State,
     codec: AmqpCodec<SaslFrame>,
@@ -237,12 +390,15 @@ where
         let state = self.state;
         let codec = self.codec;
         let local_config = self.local_config;
+        let mechanism = self.mechanism;
 
-        let frame = SaslOutcome {
+        let frame: SaslFrame = SaslOutcome {
             code,
             additional_data: None,
         }
         .into();
+        #[cfg(feature = "sasl-trace")]
+        crate::utils::trace_sasl("outgoing", &frame.body);
         state
             .send(&mut io, &codec, frame)
             .await
@@ -257,6 +413,7 @@ where
             io,
             state,
             local_config,
+            mechanism,
         })
     }
 }
@@ -265,6 +422,7 @@ pub struct SaslSuccess<Io> {
     io: Io,
     state: State,
     local_config: Rc<Configuration>,
+    mechanism: Symbol,
 }
 
 impl<Io> SaslSuccess<Io>
@@ -283,6 +441,14 @@ where
 
     /// Wait for connection open frame
     pub async fn open(self) -> Result<HandshakeAmqpOpened<Io>, HandshakeError> {
+        self.into_amqp(None).await?.open().await
+    }
+
+    /// Wait for amqp protocol header
+    pub(crate) async fn into_amqp(
+        self,
+        identity: Option<Identity>,
+    ) -> Result<HandshakeAmqp<Io>, HandshakeError> {
         let mut io = self.io;
         let state = self.state;
 
@@ -300,34 +466,13 @@ where
                     .await
                     .map_err(HandshakeError::from)?;
 
-                // Wait for connection open frame
-                let codec = AmqpCodec::<AmqpFrame>::new();
-                let frame = state
-                    .next(&mut io, &codec)
-                    .await
-                    .map_err(HandshakeError::from)?
-                    .ok_or(HandshakeError::Disconnected)?;
-
-                let frame = frame.into_parts().1;
-                match frame {
-                    protocol::Frame::Open(frame) => {
-                        trace!("Got open frame: {:?}", frame);
-
-                        let local_config = self.local_config;
-                        let remote_config = (&frame).into();
-                        let sink = Connection::new(state.clone(), &local_config, &remote_config);
-
-                        Ok(HandshakeAmqpOpened::new(
-                            frame,
-                            io,
-                            sink,
-                            state,
-                            local_config,
-                            remote_config,
-                        ))
-                    }
-                    frame => Err(HandshakeError::Unexpected(Box::new(frame))),
-                }
+                Ok(HandshakeAmqp::new(
+                    io,
+                    state,
+                    self.local_config,
+                    identity,
+                    Some(SaslMechanism::from(&self.mechanism)),
+                ))
             }
             proto => Err(ProtocolIdError::Unexpected {
                 exp: ProtocolId::Amqp,
@@ -1,15 +1,22 @@
-use std::{fmt, future::Future, marker, pin::Pin, rc::Rc, task::Context, task::Poll, time};
+use std::task::{Context, Poll};
+use std::{cell::Cell, cell::RefCell, fmt, future::Future, marker, pin::Pin, rc::Rc, time};
 
 use ntex::codec::{AsyncRead, AsyncWrite};
 use ntex::framed::{Dispatcher as FramedDispatcher, State as IoState, Timer};
+use ntex::rt::time::{sleep, Sleep};
 use ntex::service::{IntoServiceFactory, Service, ServiceFactory};
+use ntex::task::LocalWaker;
+use ntex::util::{ByteString, HashMap, PoolId};
 
-use crate::codec::{protocol::ProtocolId, AmqpCodec, AmqpFrame, ProtocolIdCodec, ProtocolIdError};
+use crate::codec::protocol::{self, Close, ProtocolId};
+use crate::codec::{AmqpCodec, AmqpFrame, ProtocolIdCodec, ProtocolIdError};
 use crate::dispatcher::Dispatcher;
+use crate::error::AmqpError;
 use crate::types::Link;
 use crate::{default::DefaultControlService, Configuration, Connection, ControlFrame, State};
 
 use super::handshake::{Handshake, HandshakeAck};
+use super::sasl::{Authenticator, SaslAuthenticator};
 use super::{Error, HandshakeError, ServerError};
 
 /// Server dispatcher factory
@@ -17,26 +24,43 @@ pub struct Server<Io, St, H, Ctl> {
     handshake: H,
     control: Ctl,
     config: Rc<Configuration>,
-    max_size: usize,
     lw: u16,
     read_hw: u16,
     write_hw: u16,
+    pool: PoolId,
     handshake_timeout: u64,
     disconnect_timeout: u16,
+    shutdown_timeout: u64,
+    require_sasl: bool,
+    authenticator: Option<Rc<Authenticator>>,
+    sasl_failure_delay: u64,
+    on_connected: Option<Rc<ConnectedFn<St>>>,
     _t: marker::PhantomData<(Io, St)>,
 }
 
+type ConnectedFn<St> = dyn Fn(&mut St, &Connection);
+
 pub(super) struct ServerInner<St, Ctl, Pb> {
     control: Ctl,
     publish: Pb,
     config: Rc<Configuration>,
-    max_size: usize,
     handshake_timeout: u64,
     disconnect_timeout: u16,
+    shutdown_timeout: u64,
+    require_sasl: bool,
+    authenticator: Option<Rc<Authenticator>>,
+    sasl_failure_delay: u64,
+    on_connected: Option<Rc<ConnectedFn<St>>>,
     lw: u16,
     read_hw: u16,
     write_hw: u16,
+    pool: PoolId,
     time: Timer,
+    containers: RefCell<HashMap<ByteString, Connection>>,
+    connections: RefCell<slab::Slab<Connection>>,
+    shutdown: Cell<bool>,
+    shutdown_timer: RefCell<Option<Pin<Box<Sleep>>>>,
+    shutdown_waker: LocalWaker,
     _t: marker::PhantomData<St>,
 }
 
@@ -57,11 +81,16 @@ where
             handshake: handshake.into_factory(),
             handshake_timeout: 5000,
             disconnect_timeout: 3,
+            shutdown_timeout: 5000,
+            require_sasl: false,
+            authenticator: None,
+            sasl_failure_delay: 0,
+            on_connected: None,
             lw: 1024,
             read_hw: 8 * 1024,
             write_hw: 8 * 1024,
+            pool: PoolId::DEFAULT,
             control: DefaultControlService::default(),
-            max_size: 0,
             config: Rc::new(Configuration::default()),
             _t: marker::PhantomData,
         }
@@ -70,6 +99,9 @@ where
 
 impl<Io, St, H, Ctl> Server<Io, St, H, Ctl> {
     /// Provide connection configuration
+    ///
+    /// Configuration is checked with `Configuration::validate()` for each
+    /// connection, invalid configuration fails connection with `ServerError::Config`.
     pub fn config(mut self, config: Configuration) -> Self {
         self.config = Rc::new(config);
         self
@@ -77,16 +109,30 @@ impl<Io, St, H, Ctl> Server<Io, St, H, Ctl> {
 
     /// Set max inbound frame size.
     ///
-    /// If max size is set to `0`, size is unlimited.
-    /// By default max size is set to `0`
+    /// Sets `max_frame_size` of connection configuration, size is
+    /// advertised to remote peer in `Open` frame. If max size is set
+    /// to `0`, size is unlimited.
+    #[deprecated(since = "0.5.0", note = "Use Configuration::max_frame_size()")]
     pub fn max_size(mut self, size: usize) -> Self {
-        self.max_size = size;
+        let size = if size == 0 || size > std::u32::MAX as usize {
+            std::u32::MAX
+        } else {
+            size as u32
+        };
+        Rc::make_mut(&mut self.config).max_frame_size = size;
         self
     }
 
     /// Set handshake timeout in millis.
     ///
-    /// By default handshake timeuot is 5 seconds.
+    /// Timeout is a single deadline for the whole handshake, protocol
+    /// negotiation, sasl exchange and `Open` frame, it is not reset by
+    /// individual handshake steps. Connection gets dropped with
+    /// `HandshakeError::Timeout` once deadline is reached. Tls handshake
+    /// is performed by acceptor before amqp handshake and is bounded by
+    /// acceptor's own timeout. Zero value disables timeout.
+    ///
+    /// By default handshake timeout is 5 seconds.
     pub fn handshake_timeout(mut self, timeout: u64) -> Self {
         self.handshake_timeout = timeout;
         self
@@ -105,9 +151,78 @@ impl<Io, St, H, Ctl> Server<Io, St, H, Ctl> {
         self
     }
 
+    /// Set server shutdown timeout in milliseconds.
+    ///
+    /// On shutdown server sends `Close` frame to all active connections
+    /// and waits for close handshake completion. Connections that are not
+    /// closed within this time get dropped.
+    ///
+    /// To disable timeout set value to 0.
+    ///
+    /// By default shutdown timeout is set to 5 seconds.
+    pub fn shutdown_timeout(mut self, timeout: u64) -> Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Require sasl authentication.
+    ///
+    /// Connections that start with plain amqp protocol header get rejected.
+    /// By default plain amqp connections are allowed.
+    pub fn require_sasl(mut self) -> Self {
+        self.require_sasl = true;
+        self
+    }
+
+    /// Handle sasl negotiation with provided authenticator.
+    ///
+    /// Server performs sasl frames exchange and passes authenticated
+    /// connection to handshake service as `Handshake::Amqp`,
+    /// client identity is available via `HandshakeAmqp::identity()`.
+    /// Authenticator implies `require_sasl()`, plain amqp connections
+    /// would bypass authentication and get rejected.
+    pub fn sasl_authenticator<A: SaslAuthenticator>(mut self, authenticator: A) -> Self {
+        self.authenticator = Some(Rc::new(Authenticator::new(authenticator)));
+        self.require_sasl = true;
+        self
+    }
+
+    /// Set delay of failed sasl authentication outcome in milliseconds.
+    ///
+    /// Failed authentication is replied with uniform `SaslCode::Auth` code
+    /// not earlier than the delay after `sasl-init` frame is received,
+    /// so response timing and code do not reveal failure reason.
+    /// Applies to authentication handled by `sasl_authenticator()`,
+    /// delay is part of handshake timeout.
+    ///
+    /// By default delay is disabled.
+    pub fn sasl_failure_delay(mut self, delay: u64) -> Self {
+        self.sasl_failure_delay = delay;
+        self
+    }
+
+    /// Set callback for established connections.
+    ///
+    /// Callback is called after `Open` frame is confirmed and before
+    /// connection dispatcher starts, it could be used for registering
+    /// per-connection resources.
+    pub fn on_connected<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&mut St, &Connection) + 'static,
+    {
+        self.on_connected = Some(Rc::new(f));
+        self
+    }
+
     #[inline]
     /// Set read/write buffer params
     ///
+    /// Larger read buffer reduces syscalls of bulk transfers at the cost of
+    /// memory per connection. Params are applied to memory pool set by
+    /// `memory_pool()`, default memory pool params are not changed.
+    /// Pool params are global, they are shared by all clients and servers
+    /// that use the same memory pool.
+    ///
     /// By default read buffer is 8kb, write buffer is 8kb
     pub fn buffer_params(
         mut self,
@@ -118,14 +233,34 @@ impl<Io, St, H, Ctl> Server<Io, St, H, Ctl> {
         self.read_hw = max_read_buf_size;
         self.write_hw = max_write_buf_size;
         self.lw = min_buf_size;
+        self.apply_pool_params();
         self
     }
 
+    /// Set memory pool for read and write buffers of connections
+    ///
+    /// By default `PoolId::DEFAULT` pool is used
+    pub fn memory_pool(mut self, id: PoolId) -> Self {
+        self.pool = id;
+        self.apply_pool_params();
+        self
+    }
+
+    /// Apply buffer params to memory pool, default pool is not changed
+    fn apply_pool_params(&self) {
+        if self.pool != PoolId::DEFAULT {
+            self.pool
+                .set_read_params(self.read_hw as u32, self.lw as u32)
+                .set_write_params(self.write_hw as u32, self.lw as u32);
+        }
+    }
+
     #[inline]
     #[doc(hidden)]
     #[deprecated(since = "0.4.3")]
     pub fn low_watermark(mut self, lw: u16) -> Self {
         self.lw = lw;
+        self.apply_pool_params();
         self
     }
 
@@ -134,6 +269,7 @@ impl<Io, St, H, Ctl> Server<Io, St, H, Ctl> {
     #[deprecated(since = "0.4.3")]
     pub fn read_high_watermark(mut self, hw: u16) -> Self {
         self.read_hw = hw;
+        self.apply_pool_params();
         self
     }
 
@@ -142,6 +278,7 @@ impl<Io, St, H, Ctl> Server<Io, St, H, Ctl> {
     #[deprecated(since = "0.4.3")]
     pub fn write_high_watermark(mut self, hw: u16) -> Self {
         self.write_hw = hw;
+        self.apply_pool_params();
         self
     }
 }
@@ -172,11 +309,16 @@ where
             handshake: self.handshake,
             handshake_timeout: self.handshake_timeout,
             disconnect_timeout: self.disconnect_timeout,
+            shutdown_timeout: self.shutdown_timeout,
+            require_sasl: self.require_sasl,
+            authenticator: self.authenticator,
+            sasl_failure_delay: self.sasl_failure_delay,
+            on_connected: self.on_connected,
             control: service.into_factory(),
-            max_size: self.max_size,
             lw: self.lw,
             read_hw: self.read_hw,
             write_hw: self.write_hw,
+            pool: self.pool,
             _t: marker::PhantomData,
         }
     }
@@ -207,11 +349,21 @@ where
                 publish: service.into_factory(),
                 control: self.control,
                 disconnect_timeout: self.disconnect_timeout,
-                max_size: self.max_size,
+                shutdown_timeout: self.shutdown_timeout,
+                require_sasl: self.require_sasl,
+                authenticator: self.authenticator,
+                sasl_failure_delay: self.sasl_failure_delay,
+                on_connected: self.on_connected,
                 lw: self.lw,
                 read_hw: self.read_hw,
                 write_hw: self.write_hw,
+                pool: self.pool,
                 time: Timer::with(time::Duration::from_secs(1)),
+                containers: RefCell::new(HashMap::default()),
+                connections: RefCell::new(slab::Slab::new()),
+                shutdown: Cell::new(false),
+                shutdown_timer: RefCell::new(None),
+                shutdown_waker: LocalWaker::new(),
                 _t: marker::PhantomData,
             }),
             _t: marker::PhantomData,
@@ -219,6 +371,87 @@ where
     }
 }
 
+impl<St, Ctl, Pb> ServerInner<St, Ctl, Pb> {
+    /// Create io state of new connection
+    fn io_state(&self) -> IoState {
+        let state = IoState::with_params(
+            self.read_hw,
+            self.write_hw,
+            self.lw,
+            self.disconnect_timeout,
+        );
+        if self.pool != PoolId::DEFAULT {
+            state.set_memory_pool(self.pool.pool_ref());
+        }
+        state
+    }
+
+    /// Register exclusive connection for container, previous connection gets closed
+    fn register_container(&self, id: ByteString, sink: &Connection) {
+        if let Some(prev) = self.containers.borrow_mut().insert(id, sink.clone()) {
+            log::trace!("Close previous connection of the container");
+            let _ = prev.close_with_error(
+                AmqpError::new(protocol::AmqpError::ResourceLocked)
+                    .description("Connection is replaced by new connection of the container"),
+            );
+        }
+    }
+
+    fn unregister_container(&self, id: &ByteString, sink: &Connection) {
+        let mut containers = self.containers.borrow_mut();
+        if containers.get(id).map(|c| c.ptr_eq(sink)).unwrap_or(false) {
+            containers.remove(id);
+        }
+    }
+
+    /// Register active connection, connection gets closed if server is shutting down
+    fn register_connection(&self, sink: &Connection) -> usize {
+        if self.shutdown.get() {
+            let _ = sink.close();
+        }
+        self.connections.borrow_mut().insert(sink.clone())
+    }
+
+    fn unregister_connection(&self, idx: usize) {
+        let mut connections = self.connections.borrow_mut();
+        connections.remove(idx);
+        if connections.is_empty() {
+            self.shutdown_waker.wake();
+        }
+    }
+
+    /// Close all active connections and wait for completion
+    fn poll_drain(&self, cx: &mut Context<'_>) -> Poll<()> {
+        if !self.shutdown.get() {
+            self.shutdown.set(true);
+            log::trace!(
+                "Server is shutting down, close {} connections",
+                self.connections.borrow().len()
+            );
+            for (_, sink) in self.connections.borrow().iter() {
+                let _ = sink.close();
+            }
+            if self.shutdown_timeout != 0 {
+                *self.shutdown_timer.borrow_mut() = Some(Box::pin(sleep(
+                    time::Duration::from_millis(self.shutdown_timeout),
+                )));
+            }
+        }
+
+        if self.connections.borrow().is_empty() {
+            return Poll::Ready(());
+        }
+        if let Some(ref mut timer) = *self.shutdown_timer.borrow_mut() {
+            if timer.as_mut().poll(cx).is_ready() {
+                log::trace!("Connections are not closed in shutdown timeout");
+                return Poll::Ready(());
+            }
+        }
+        self.shutdown_waker.register(cx.waker());
+        Poll::Pending
+    }
+}
+
 struct ServerImpl<Io, St, H, Ctl, Pb> {
     handshake: H,
     inner: Rc<ServerInner<St, Ctl, Pb>>,
@@ -297,23 +530,29 @@ where
 
     #[inline]
     fn poll_shutdown(&self, cx: &mut Context<'_>, is_error: bool) -> Poll<()> {
+        if self.inner.poll_drain(cx).is_pending() {
+            return Poll::Pending;
+        }
         self.handshake.as_ref().poll_shutdown(cx, is_error)
     }
 
     fn call(&self, req: Self::Request) -> Self::Future {
+        if self.inner.shutdown.get() {
+            return Box::pin(async { Err(ServerError::Shutdown) });
+        }
+        if let Err(err) = self.inner.config.validate() {
+            error!("Invalid amqp configuration: {}", err);
+            return Box::pin(async move { Err(ServerError::Config(err)) });
+        }
+
         let timeout = self.inner.handshake_timeout;
         let keepalive = self.inner.config.idle_time_out / 1000;
         let disconnect_timeout = self.inner.disconnect_timeout;
         let inner = self.inner.clone();
-        let fut = handshake(
-            req,
-            self.inner.max_size,
-            self.handshake.clone(),
-            self.inner.clone(),
-        );
+        let fut = handshake(req, self.handshake.clone(), self.inner.clone());
 
         Box::pin(async move {
-            let (io, state, codec, sink, st, idle_timeout) = if timeout == 0 {
+            let (io, state, codec, sink, st, idle_timeout, container_id) = if timeout == 0 {
                 fut.await?
             } else {
                 ntex::rt::time::timeout(time::Duration::from_millis(timeout), fut)
@@ -333,21 +572,39 @@ where
                 ServerError::ControlServiceError
             })?;
 
-            let dispatcher = Dispatcher::new(st, sink, pb_srv, ctl_srv, idle_timeout)
+            if let Some(ref id) = container_id {
+                inner.register_container(id.clone(), &sink);
+            }
+            let idx = inner.register_connection(&sink);
+
+            let dispatcher = Dispatcher::new(st, sink.clone(), pb_srv, ctl_srv, idle_timeout)
                 .map(|_| Option::<AmqpFrame>::None);
 
-            FramedDispatcher::new(io, codec, state, dispatcher, inner.time.clone())
+            let result = FramedDispatcher::new(io, codec, state, dispatcher, inner.time.clone())
                 .keepalive_timeout(keepalive as u16)
                 .disconnect_timeout(disconnect_timeout)
                 .await
                 .map_err(|_| ServerError::Disconnected)
+                .and_then(|_| {
+                    if sink.0.is_closing() {
+                        Ok(())
+                    } else {
+                        // unexpected eof
+                        Err(ServerError::Disconnected)
+                    }
+                });
+
+            if let Some(ref id) = container_id {
+                inner.unregister_container(id, &sink);
+            }
+            inner.unregister_connection(idx);
+            result
         })
     }
 }
 
 async fn handshake<Io, St, H, Ctl, Pb>(
     mut io: Io,
-    max_size: usize,
     handshake: Rc<H>,
     inner: Rc<ServerInner<St, Ctl, Pb>>,
 ) -> Result<
@@ -358,6 +615,7 @@ async fn handshake<Io, St, H, Ctl, Pb>(
         Connection,
         State<St>,
         usize,
+        Option<ByteString>,
     ),
     ServerError<H::Error>,
 >
@@ -368,12 +626,7 @@ where
     Ctl: ServiceFactory<Config = State<St>, Request = ControlFrame, Response = ()> + 'static,
     Pb: ServiceFactory<Config = State<St>, Request = Link<St>, Response = ()> + 'static,
 {
-    let state = IoState::with_params(
-        inner.read_hw,
-        inner.write_hw,
-        inner.lw,
-        inner.disconnect_timeout,
-    );
+    let state = inner.io_state();
 
     let protocol = state
         .next(&mut io, &ProtocolIdCodec)
@@ -384,7 +637,20 @@ where
             HandshakeError::Disconnected
         })?;
 
-    let (io, sink, state, codec, st, idle_timeout) = match protocol {
+    let (io, sink, state, codec, st, idle_timeout, container_id) = match protocol {
+        ProtocolId::Amqp if inner.require_sasl => {
+            log::trace!("Plain amqp connection is rejected, sasl is required");
+            // reply with supported protocol header before closing connection
+            state
+                .send(&mut io, &ProtocolIdCodec, ProtocolId::AmqpSasl)
+                .await
+                .map_err(HandshakeError::from)?;
+            return Err(HandshakeError::from(ProtocolIdError::Unexpected {
+                exp: ProtocolId::AmqpSasl,
+                got: ProtocolId::Amqp,
+            })
+            .into());
+        }
         // start amqp processing
         ProtocolId::Amqp | ProtocolId::AmqpSasl => {
             state
@@ -392,29 +658,52 @@ where
                 .await
                 .map_err(HandshakeError::from)?;
 
-            let ack = handshake
-                .call(if protocol == ProtocolId::Amqp {
-                    Handshake::new_plain(io, state, inner.config.clone())
-                } else {
-                    Handshake::new_sasl(io, state, inner.config.clone())
-                })
-                .await
-                .map_err(ServerError::Service)?;
+            let req = if protocol == ProtocolId::Amqp {
+                Handshake::new_plain(io, state, inner.config.clone())
+            } else if let Some(ref auth) = inner.authenticator {
+                let delay = time::Duration::from_millis(inner.sasl_failure_delay);
+                Handshake::Amqp(
+                    auth.authenticate(io, state, inner.config.clone(), delay)
+                        .await?,
+                )
+            } else {
+                Handshake::new_sasl(io, state, inner.config.clone())
+            };
 
-            let (st, mut io, sink, state, idle_timeout) = ack.into_inner();
+            let ack = handshake.call(req).await.map_err(ServerError::Service)?;
 
-            let codec = AmqpCodec::new().max_size(max_size);
+            let (st, mut io, sink, state, idle_timeout, container_id, local) = ack.into_inner();
+
+            // inbound limit is the same max frame size that is advertised in `Open`
+            let codec = AmqpCodec::new().max_size(inner.config.max_frame_size as usize);
 
             // confirm Open
-            let local = inner.config.to_open();
             state
                 .send(&mut io, &codec, AmqpFrame::new(0, local.into()))
                 .await
                 .map_err(HandshakeError::from)?;
 
-            let st = State::new(st);
+            let st = match st {
+                Ok(mut st) => {
+                    if let Some(ref f) = inner.on_connected {
+                        (*f)(&mut st, &sink);
+                    }
+                    State::new(st)
+                }
+                Err(err) => {
+                    log::trace!("Connection is rejected: {:?}", err);
+                    let close = Close {
+                        error: Some(err.clone()),
+                    };
+                    state
+                        .send(&mut io, &codec, AmqpFrame::new(0, close.into()))
+                        .await
+                        .map_err(HandshakeError::from)?;
+                    return Err(HandshakeError::Rejected(err).into());
+                }
+            };
 
-            (io, sink, state, codec, st, idle_timeout)
+            (io, sink, state, codec, st, idle_timeout, container_id)
         }
         ProtocolId::AmqpTls => {
             return Err(HandshakeError::from(ProtocolIdError::Unexpected {
@@ -425,5 +714,5 @@ where
         }
     };
 
-    Ok((io, state, codec, sink, st, idle_timeout))
+    Ok((io, state, codec, sink, st, idle_timeout, container_id))
 }
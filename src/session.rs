@@ -1,14 +1,16 @@
 use std::collections::VecDeque;
 use std::future::Future;
+use std::ops::Range;
+use std::time::{Duration, Instant};
 
 use ntex::channel::oneshot;
-use ntex::util::{BufMut, ByteString, Bytes, BytesMut, Either, HashMap, Ready};
+use ntex::util::{BufMut, ByteString, Bytes, BytesMut, Either, HashMap};
 use slab::Slab;
 
 use ntex_amqp_codec::protocol::{
-    Accepted, Attach, DeliveryNumber, DeliveryState, Detach, Disposition, Error, Flow, Frame,
-    Handle, MessageFormat, ReceiverSettleMode, Role, SenderSettleMode, Transfer, TransferBody,
-    TransferNumber,
+    Accepted, AmqpError, Attach, DeliveryNumber, DeliveryState, Detach, Disposition, End, Error,
+    Flow, Frame, Handle, MessageFormat, ReceiverSettleMode, Role, SenderSettleMode, SessionError,
+    Transfer, TransferBody, TransferNumber,
 };
 use ntex_amqp_codec::AmqpFrame;
 
@@ -17,6 +19,9 @@ use crate::connection::Connection;
 use crate::error::AmqpProtocolError;
 use crate::rcvlink::{ReceiverLink, ReceiverLinkBuilder, ReceiverLinkInner};
 use crate::sndlink::{SenderLink, SenderLinkBuilder, SenderLinkInner};
+use crate::span::Span;
+use crate::transaction::Coordinator;
+use crate::utils::{serial_lt, serial_range};
 use crate::DeliveryPromise;
 
 const INITIAL_OUTGOING_ID: TransferNumber = 0;
@@ -37,8 +42,29 @@ impl Session {
         Session { inner }
     }
 
+    /// Connection of the session
+    pub fn connection(&self) -> &Connection {
+        &self.inner.get_ref().sink
+    }
+
+    /// End session
+    ///
+    /// Unsettled incoming deliveries are handled according to session's
+    /// `UnsettledPolicy` before `End` frame is sent.
     pub fn close(&self) -> impl Future<Output = Result<(), AmqpProtocolError>> {
-        Ready::Ok(())
+        let rx = self.inner.get_mut().end();
+
+        async move {
+            match rx.await {
+                Ok(res) => res,
+                Err(_) => Err(AmqpProtocolError::Disconnected),
+            }
+        }
+    }
+
+    /// Set policy for unsettled incoming deliveries on session end
+    pub fn set_unsettled_policy(&self, policy: UnsettledPolicy) {
+        self.inner.get_mut().unsettled_policy = policy;
     }
 
     pub fn get_sender_link(&self, name: &str) -> Option<&SenderLink> {
@@ -83,6 +109,45 @@ impl Session {
         ReceiverLinkBuilder::new(name, address, self.inner.clone())
     }
 
+    /// Open sender link to the address with default settings
+    ///
+    /// Address is used as link name, numeric suffix is appended
+    /// if the name is already in use.
+    pub fn open_sender<T: Into<ByteString>>(
+        &mut self,
+        address: T,
+    ) -> impl Future<Output = Result<SenderLink, AmqpProtocolError>> {
+        let address = address.into();
+        let builder = self
+            .build_sender_link(address.clone(), address)
+            .auto_suffix_name();
+        builder.open()
+    }
+
+    /// Open receiver link from the address with default settings
+    ///
+    /// Address is used as link name, numeric suffix is appended
+    /// if the name is already in use.
+    pub fn open_receiver<T: Into<ByteString>>(
+        &mut self,
+        address: T,
+    ) -> impl Future<Output = Result<ReceiverLink, AmqpProtocolError>> {
+        let address = address.into();
+        let builder = self
+            .build_receiver_link(address.clone(), address)
+            .auto_suffix_name();
+        builder.open()
+    }
+
+    /// Attach sender link to the transaction coordinator
+    pub fn coordinator<U: Into<ByteString>>(
+        &mut self,
+        name: U,
+    ) -> impl Future<Output = Result<Coordinator, AmqpProtocolError>> {
+        let builder = SenderLinkBuilder::coordinator(name.into(), self.inner.clone());
+        async move { builder.open().await.map(Coordinator::new) }
+    }
+
     /// Detach receiver link
     pub fn detach_receiver_link(
         &mut self,
@@ -116,6 +181,97 @@ impl Session {
     ) -> impl Future<Output = Result<Disposition, AmqpProtocolError>> {
         self.inner.get_mut().wait_disposition(id)
     }
+
+    /// Session state and established links
+    pub fn info(&self) -> SessionInfo {
+        self.inner.get_ref().info()
+    }
+}
+
+/// Handling of unsettled incoming deliveries on session end
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UnsettledPolicy {
+    /// End session without settlement, remote peer decides about deliveries
+    Abort,
+    /// Settle deliveries with outcome already chosen by application before session end
+    ///
+    /// Deliveries without outcome, i.e. not yet read by application, are left unsettled.
+    Settle,
+}
+
+impl Default for UnsettledPolicy {
+    fn default() -> Self {
+        UnsettledPolicy::Settle
+    }
+}
+
+/// Session builder
+pub struct SessionBuilder {
+    sink: Connection,
+    unsettled_policy: UnsettledPolicy,
+}
+
+impl SessionBuilder {
+    pub(crate) fn new(sink: Connection) -> Self {
+        SessionBuilder {
+            sink,
+            unsettled_policy: UnsettledPolicy::default(),
+        }
+    }
+
+    /// Set policy for unsettled incoming deliveries on session end
+    ///
+    /// By default deliveries with chosen outcome are settled.
+    pub fn unsettled_policy(mut self, policy: UnsettledPolicy) -> Self {
+        self.unsettled_policy = policy;
+        self
+    }
+
+    /// Open session
+    pub async fn open(self) -> Result<Session, AmqpProtocolError> {
+        let session = self.sink.open_session().await?;
+        session.set_unsettled_policy(self.unsettled_policy);
+        Ok(session)
+    }
+}
+
+/// Session state snapshot
+#[derive(Clone, Debug)]
+pub struct SessionInfo {
+    /// Local channel id
+    pub channel_id: u16,
+    /// Remote channel id
+    pub remote_channel_id: u16,
+    pub next_outgoing_id: TransferNumber,
+    pub next_incoming_id: TransferNumber,
+    pub remote_incoming_window: u32,
+    pub remote_outgoing_window: u32,
+    /// Number of transfers waiting for session window
+    pub pending_transfers: usize,
+    /// Number of incoming delivery ids skipped by remote peer
+    ///
+    /// Delivery ids are assigned sequentially per session, gap in
+    /// received sequence indicates lost deliveries.
+    pub missed_deliveries: u64,
+    /// Established links
+    pub links: Vec<LinkInfo>,
+}
+
+/// Link state snapshot
+#[derive(Clone, Debug)]
+pub struct LinkInfo {
+    /// Local link handle
+    pub handle: Handle,
+    pub name: ByteString,
+    pub role: Role,
+    /// Source address
+    pub source: Option<ByteString>,
+    /// Target address
+    pub target: Option<ByteString>,
+    /// Current link credit
+    pub link_credit: u32,
+    /// Current delivery count
+    pub delivery_count: u32,
 }
 
 #[derive(Debug)]
@@ -160,15 +316,34 @@ pub(crate) struct SessionInner {
     next_incoming_id: TransferNumber,
     remote_outgoing_window: u32,
     remote_incoming_window: u32,
+    /// Max link handle accepted by remote peer
+    remote_handle_max: Handle,
 
-    unsettled_deliveries: HashMap<DeliveryNumber, DeliveryPromise>,
+    unsettled_deliveries: HashMap<DeliveryNumber, (Handle, DeliveryPromise)>,
 
     links: Slab<Either<SenderLinkState, ReceiverLinkState>>,
     links_by_name: HashMap<ByteString, usize>,
     remote_handles: HashMap<Handle, usize>,
+    /// Links suspended by remote peer, by link name
+    suspended_links: HashMap<ByteString, SuspendedLink>,
     pending_transfers: VecDeque<PendingTransfer>,
+    /// Link with incomplete multi-frame delivery, transfers of
+    /// other links are held back until delivery's last frame is sent
+    transfer_lane: Option<Handle>,
     disposition_subscribers: HashMap<DeliveryNumber, oneshot::Sender<Disposition>>,
+    unsettled_policy: UnsettledPolicy,
+    /// Expected delivery id of next incoming delivery
+    next_delivery_id: Option<DeliveryNumber>,
+    missed_deliveries: u64,
     error: Option<AmqpProtocolError>,
+    pub(crate) span: Span,
+}
+
+/// Link suspended by remote peer
+struct SuspendedLink {
+    idx: usize,
+    /// Link is detached if it is not resumed until this time
+    expires: Option<Instant>,
 }
 
 struct PendingTransfer {
@@ -178,6 +353,7 @@ struct PendingTransfer {
     state: TransferState,
     tag: Option<Bytes>,
     settled: Option<bool>,
+    delivery_state: Option<DeliveryState>,
     message_format: Option<MessageFormat>,
 }
 
@@ -190,6 +366,11 @@ pub(crate) enum TransferState {
 }
 
 impl TransferState {
+    /// First transfer of the delivery, consumes link credit
+    pub(crate) fn is_first(&self) -> bool {
+        matches!(self, TransferState::First(_) | TransferState::Only(_))
+    }
+
     fn more(&self) -> bool {
         match self {
             TransferState::Only(_) | TransferState::Last => false,
@@ -199,6 +380,7 @@ impl TransferState {
 }
 
 impl SessionInner {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         id: usize,
         local: bool,
@@ -207,7 +389,10 @@ impl SessionInner {
         next_incoming_id: DeliveryNumber,
         remote_incoming_window: u32,
         remote_outgoing_window: u32,
+        remote_handle_max: Handle,
     ) -> SessionInner {
+        let span = sink.0.get_ref().span.session(id as u16);
+
         SessionInner {
             id,
             local,
@@ -216,14 +401,21 @@ impl SessionInner {
             remote_channel_id,
             remote_incoming_window,
             remote_outgoing_window,
+            remote_handle_max,
             next_outgoing_id: INITIAL_OUTGOING_ID,
             unsettled_deliveries: HashMap::default(),
             links: Slab::new(),
             links_by_name: HashMap::default(),
             remote_handles: HashMap::default(),
+            suspended_links: HashMap::default(),
             pending_transfers: VecDeque::new(),
+            transfer_lane: None,
             disposition_subscribers: HashMap::default(),
+            unsettled_policy: UnsettledPolicy::default(),
+            next_delivery_id: None,
+            missed_deliveries: 0,
             error: None,
+            span,
         }
     }
 
@@ -232,34 +424,104 @@ impl SessionInner {
         self.id as u16
     }
 
+    /// Established links without activity for longer than timeout
+    pub(crate) fn idle_links(
+        &self,
+        now: Instant,
+        timeout: Duration,
+    ) -> Vec<Either<SenderLink, ReceiverLink>> {
+        self.links
+            .iter()
+            .filter_map(|(_, st)| match st {
+                Either::Left(SenderLinkState::Established(ref link))
+                    if link.inner.get_ref().is_idle(now, timeout) =>
+                {
+                    Some(Either::Left(link.clone()))
+                }
+                Either::Right(ReceiverLinkState::Established(ref link))
+                    if link.inner.get_ref().is_idle(now, timeout) =>
+                {
+                    Some(Either::Right(link.clone()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub(crate) fn info(&self) -> SessionInfo {
+        let links = self
+            .links
+            .iter()
+            .filter_map(|(_, st)| match st {
+                Either::Left(SenderLinkState::Established(ref link)) => {
+                    Some(link.inner.get_ref().info())
+                }
+                Either::Right(ReceiverLinkState::Established(ref link)) => {
+                    Some(link.inner.get_ref().info())
+                }
+                _ => None,
+            })
+            .collect();
+
+        SessionInfo {
+            links,
+            channel_id: self.id(),
+            remote_channel_id: self.remote_channel_id,
+            next_outgoing_id: self.next_outgoing_id,
+            next_incoming_id: self.next_incoming_id,
+            remote_incoming_window: self.remote_incoming_window,
+            remote_outgoing_window: self.remote_outgoing_window,
+            pending_transfers: self.pending_transfers.len(),
+            missed_deliveries: self.missed_deliveries,
+        }
+    }
+
     /// Set error. New operations will return error.
     pub(crate) fn set_error(&mut self, err: AmqpProtocolError) {
         log::trace!("Connection is failed, dropping state: {:?}", err);
 
         // drop pending transfers
+        self.transfer_lane = None;
         for tr in self.pending_transfers.drain(..) {
             if let TransferState::First(tx) | TransferState::Only(tx) = tr.state {
                 let _ = tx.send(Err(err.clone()));
             }
         }
 
+        // drop sent but not settled transfers
+        for (_, (_, tx)) in self.unsettled_deliveries.drain() {
+            let _ = tx.send(Err(err.clone()));
+        }
+        self.disposition_subscribers.clear();
+
         // drop links
         self.links_by_name.clear();
+        self.remote_handles.clear();
         for (_, st) in self.links.iter_mut() {
             match st {
-                Either::Left(SenderLinkState::Opening(_)) => (),
+                Either::Left(SenderLinkState::Opening(ref mut tx)) => {
+                    if let Some(tx) = tx.take() {
+                        let _ = tx.send(Err(err.clone()));
+                    }
+                }
                 Either::Left(SenderLinkState::Established(ref mut link)) => {
                     link.inner.get_mut().detached(err.clone())
                 }
-                Either::Left(SenderLinkState::Closing(ref mut link)) => {
-                    if let Some(tx) = link.take() {
+                Either::Left(SenderLinkState::Closing(ref mut tx))
+                | Either::Right(ReceiverLinkState::Closing(ref mut tx)) => {
+                    if let Some(tx) = tx.take() {
+                        let _ = tx.send(Err(err.clone()));
+                    }
+                }
+                Either::Right(ReceiverLinkState::OpeningLocal(ref mut item)) => {
+                    if let Some((_, tx)) = item.take() {
                         let _ = tx.send(Err(err.clone()));
                     }
                 }
                 Either::Right(ReceiverLinkState::Established(ref mut link)) => {
                     link.remote_closed(None)
                 }
-                _ => (),
+                Either::Right(ReceiverLinkState::Opening(_)) => (),
             }
         }
         self.links.clear();
@@ -267,6 +529,58 @@ impl SessionInner {
         self.error = Some(err);
     }
 
+    /// Settle unsettled deliveries and send `End` frame
+    pub(crate) fn end(&mut self) -> oneshot::Receiver<Result<(), AmqpProtocolError>> {
+        let (tx, rx) = oneshot::channel();
+        if let Some(ref err) = self.error {
+            let _ = tx.send(Err(err.clone()));
+            return rx;
+        }
+
+        if self.unsettled_policy == UnsettledPolicy::Settle {
+            let mut outcomes = Vec::new();
+            for (_, st) in self.links.iter() {
+                if let Either::Right(ReceiverLinkState::Established(ref link)) = st {
+                    outcomes.extend(link.inner.get_mut().take_outcomes());
+                }
+            }
+            log::trace!("Settle {} unsettled deliveries", outcomes.len());
+            for (id, state) in outcomes {
+                let disp = Disposition {
+                    role: Role::Receiver,
+                    first: id,
+                    last: None,
+                    settled: true,
+                    state: Some(state),
+                    batchable: false,
+                };
+                self.post_frame(disp.into());
+            }
+        }
+
+        self.set_error(AmqpProtocolError::SessionEnded(None));
+        self.post_frame(End { error: None }.into());
+        self.sink.0.get_mut().end_session(self.id, tx);
+        rx
+    }
+
+    /// End session with `amqp:session:unattached-handle` error
+    fn unattached_handle(&mut self, handle: Handle) {
+        let err = Error {
+            condition: SessionError::UnattachedHandle.into(),
+            description: Some(ByteString::from(format!(
+                "Link handle {} is not attached",
+                handle
+            ))),
+            info: None,
+        };
+        self.set_error(AmqpProtocolError::SessionEnded(Some(err.clone())));
+        self.post_frame(End { error: Some(err) }.into());
+
+        let (tx, _) = oneshot::channel();
+        self.sink.0.get_mut().end_session(self.id, tx);
+    }
+
     fn wait_disposition(
         &mut self,
         id: DeliveryNumber,
@@ -318,6 +632,7 @@ impl SessionInner {
         }
 
         link.get_mut().id = token;
+        link.get_mut().span = self.span.link(token as u32);
         self.remote_handles.insert(attach.handle(), token);
         entry.insert(Either::Left(SenderLinkState::Established(SenderLink::new(
             link.clone(),
@@ -351,10 +666,12 @@ impl SessionInner {
         attach: Attach,
     ) -> ReceiverLink {
         let handle = attach.handle();
+        let properties = attach.properties.clone();
         let entry = self.links.vacant_entry();
         let token = entry.key();
 
         let inner = Cell::new(ReceiverLinkInner::new(cell, token as u32, attach));
+        inner.get_mut().remote_properties = properties;
         entry.insert(Either::Right(ReceiverLinkState::Opening(Some(
             inner.clone(),
         ))));
@@ -366,13 +683,19 @@ impl SessionInner {
         &mut self,
         cell: Cell<SessionInner>,
         mut frame: Attach,
-    ) -> oneshot::Receiver<Result<ReceiverLink, AmqpProtocolError>> {
+        auto_suffix: bool,
+    ) -> Result<oneshot::Receiver<Result<ReceiverLink, AmqpProtocolError>>, AmqpProtocolError> {
+        frame.name = self.link_name(&frame.name, auto_suffix)?;
         let (tx, rx) = oneshot::channel();
 
+        self.check_handle()?;
         let entry = self.links.vacant_entry();
         let token = entry.key();
 
         let inner = Cell::new(ReceiverLinkInner::new(cell, token as u32, frame.clone()));
+        inner
+            .get_mut()
+            .set_max_message_size(frame.max_message_size.unwrap_or(0));
         entry.insert(Either::Right(ReceiverLinkState::OpeningLocal(Some((
             inner, tx,
         )))));
@@ -381,7 +704,7 @@ impl SessionInner {
 
         self.links_by_name.insert(frame.name.clone(), token);
         self.post_frame(Frame::Attach(frame));
-        rx
+        Ok(rx)
     }
 
     pub(crate) fn confirm_receiver_link(&mut self, token: Handle, attach: &Attach) {
@@ -394,13 +717,13 @@ impl SessionInner {
                             handle: token as Handle,
                             role: Role::Receiver,
                             snd_settle_mode: attach.snd_settle_mode(),
-                            rcv_settle_mode: ReceiverSettleMode::First,
+                            rcv_settle_mode: attach.rcv_settle_mode(),
                             source: attach.source.clone(),
                             target: attach.target.clone(),
                             unsettled: None,
                             incomplete_unsettled: false,
                             initial_delivery_count: Some(0),
-                            max_message_size: Some(65536),
+                            max_message_size: l.get_ref().max_message_size(),
                             offered_capabilities: None,
                             desired_capabilities: None,
                             properties: None,
@@ -425,6 +748,7 @@ impl SessionInner {
         error: Option<Error>,
         tx: oneshot::Sender<Result<(), AmqpProtocolError>>,
     ) {
+        let suspended = self.is_suspended(id as usize);
         if let Some(Either::Right(link)) = self.links.get_mut(id as usize) {
             match link {
                 ReceiverLinkState::Opening(_inner) => {
@@ -435,7 +759,12 @@ impl SessionInner {
                     };
                     self.post_frame(detach.into());
                     let _ = tx.send(Ok(()));
-                    let _ = self.links.remove(id as usize);
+                    self.remove_link(id as usize);
+                }
+                ReceiverLinkState::Established(_) if suspended => {
+                    // remote endpoint is already detached
+                    let _ = tx.send(Ok(()));
+                    self.remove_link(id as usize);
                 }
                 ReceiverLinkState::Established(_) => {
                     let detach = Detach {
@@ -448,7 +777,7 @@ impl SessionInner {
                 }
                 ReceiverLinkState::Closing(_) => {
                     let _ = tx.send(Ok(()));
-                    let _ = self.links.remove(id as usize);
+                    self.remove_link(id as usize);
                     error!("Unexpected receiver link state: closing - {}", id);
                 }
                 ReceiverLinkState::OpeningLocal(_inner) => unimplemented!(),
@@ -466,6 +795,7 @@ impl SessionInner {
         error: Option<Error>,
         tx: oneshot::Sender<Result<(), AmqpProtocolError>>,
     ) {
+        let suspended = self.is_suspended(id);
         if let Some(Either::Left(link)) = self.links.get_mut(id) {
             match link {
                 SenderLinkState::Opening(_) => {
@@ -477,6 +807,11 @@ impl SessionInner {
                     *link = SenderLinkState::Closing(Some(tx));
                     self.post_frame(detach.into());
                 }
+                SenderLinkState::Established(_) if suspended => {
+                    // remote endpoint is already detached
+                    let _ = tx.send(Ok(()));
+                    self.remove_link(id);
+                }
                 SenderLinkState::Established(_) => {
                     let detach = Detach {
                         handle: id as u32,
@@ -519,10 +854,17 @@ impl SessionInner {
     }
 
     pub(crate) fn handle_frame(&mut self, frame: Frame) {
+        let span = self.span.clone();
+        let _enter = span.enter();
+
         if self.error.is_none() {
             match frame {
                 Frame::Flow(flow) => self.apply_flow(&flow),
                 Frame::Disposition(disp) => {
+                    // remote sender settles deliveries of receiver links
+                    if disp.role == Role::Sender && disp.settled {
+                        self.settle_incoming_deliveries(&disp);
+                    }
                     if let Some(sender) = self.disposition_subscribers.remove(&disp.first) {
                         let _ = sender.send(disp);
                     } else {
@@ -534,6 +876,7 @@ impl SessionInner {
                         *idx
                     } else {
                         error!("Transfer's link {:?} is unknown", transfer.handle());
+                        self.unattached_handle(transfer.handle());
                         return;
                     };
 
@@ -558,6 +901,15 @@ impl SessionInner {
                                 ReceiverLinkState::Established(link) => {
                                     // self.outgoing_window -= 1;
                                     let _ = self.next_incoming_id.wrapping_add(1);
+                                    if let Some(id) = transfer.delivery_id() {
+                                        if !transfer.resume() {
+                                            track_delivery_id(
+                                                &mut self.next_delivery_id,
+                                                &mut self.missed_deliveries,
+                                                id,
+                                            );
+                                        }
+                                    }
                                     link.inner.get_mut().handle_transfer(transfer);
                                 }
                                 ReceiverLinkState::Closing(_) => (),
@@ -583,6 +935,18 @@ impl SessionInner {
     pub(crate) fn handle_attach(&mut self, attach: &Attach, cell: Cell<SessionInner>) -> bool {
         let name = attach.name();
 
+        if let Some(idx) = self.suspended_links.get(name).map(|link| link.idx) {
+            // remote peer must resume link with opposite role
+            let is_sender = matches!(self.links.get(idx), Some(Either::Left(_)));
+            if is_sender == (attach.role == Role::Receiver) {
+                self.suspended_links.remove(name);
+                self.resume_link(idx, attach);
+            } else {
+                self.refuse_resume(attach);
+            }
+            return true;
+        }
+
         if let Some(index) = self.links_by_name.get(name) {
             match self.links.get_mut(*index) {
                 Some(Either::Left(item)) => {
@@ -596,13 +960,8 @@ impl SessionInner {
 
                         self.remote_handles.insert(attach.handle(), *index);
                         let delivery_count = attach.initial_delivery_count.unwrap_or(0);
-                        let link = Cell::new(SenderLinkInner::new(
-                            *index,
-                            name.clone(),
-                            attach.handle(),
-                            delivery_count,
-                            cell,
-                        ));
+                        let link =
+                            Cell::new(SenderLinkInner::new(*index, attach, delivery_count, cell));
                         let local_sender = std::mem::replace(
                             item,
                             SenderLinkState::Established(SenderLink::new(link.clone())),
@@ -624,6 +983,7 @@ impl SessionInner {
                         if let ReceiverLinkState::OpeningLocal(opt_item) = item {
                             if let Some((link, tx)) = opt_item.take() {
                                 self.remote_handles.insert(attach.handle(), *index);
+                                link.get_mut().remote_properties = attach.properties.clone();
 
                                 *item =
                                     ReceiverLinkState::Established(ReceiverLink::new(link.clone()));
@@ -654,11 +1014,16 @@ impl SessionInner {
         } else if self.links.contains(detach.handle() as usize) {
             detach.handle() as usize
         } else {
-            // should not happen, error
             log::info!("Detaching unknown link: {:?}", detach);
+            self.unattached_handle(detach.handle());
             return;
         };
 
+        // link is suspended, remote peer could resume it with new attach
+        if !detach.closed() && self.suspend_link(idx) {
+            return;
+        }
+
         let remove = if let Some(link) = self.links.get_mut(idx) {
             match link {
                 Either::Left(link) => match link {
@@ -683,7 +1048,10 @@ impl SessionInner {
 
                         // drop pending transfers
                         let mut idx = 0;
-                        let handle = link.inner.get_ref().remote_handle();
+                        let handle = link.inner.get_ref().id();
+                        if self.transfer_lane == Some(handle) {
+                            self.transfer_lane = None;
+                        }
                         while idx < self.pending_transfers.len() {
                             if self.pending_transfers[idx].link_handle == handle {
                                 let tr = self.pending_transfers.remove(idx).unwrap();
@@ -753,8 +1121,160 @@ impl SessionInner {
         };
 
         if remove {
-            self.links.remove(idx);
-            self.remote_handles.remove(&detach.handle());
+            self.remove_link(idx);
+        }
+    }
+
+    /// Suspend established link, link state is preserved until remote peer reattaches it
+    ///
+    /// Link is detached if it is not resumed within terminus time-out, or within link
+    /// idle time-out if terminus time-out is not set. Otherwise it stays until session ends.
+    fn suspend_link(&mut self, idx: usize) -> bool {
+        let (name, terminus_timeout) = match self.links.get(idx) {
+            Some(Either::Left(SenderLinkState::Established(link))) => {
+                let inner = link.inner.get_mut();
+                inner.suspended();
+                (inner.link_name().clone(), inner.terminus_timeout())
+            }
+            Some(Either::Right(ReceiverLinkState::Established(link))) => {
+                let inner = link.inner.get_mut();
+                inner.suspended();
+                (link.frame().name().clone(), inner.terminus_timeout())
+            }
+            _ => return false,
+        };
+        trace!("Link is suspended by remote peer: {:?} {}", name, idx);
+
+        // pending transfers could not be sent to detached link
+        let handle = idx as Handle;
+        if self.transfer_lane == Some(handle) {
+            self.transfer_lane = None;
+        }
+        let err = AmqpProtocolError::LinkDetached(None);
+        let mut i = 0;
+        while i < self.pending_transfers.len() {
+            if self.pending_transfers[i].link_handle == handle {
+                let tr = self.pending_transfers.remove(i).unwrap();
+                if let TransferState::First(tx) | TransferState::Only(tx) = tr.state {
+                    let _ = tx.send(Err(err.clone()));
+                }
+            } else {
+                i += 1;
+            }
+        }
+
+        // terminus time-out takes precedence over link idle time-out
+        let timeout = if terminus_timeout > 0 {
+            Duration::from_secs(terminus_timeout as u64)
+        } else {
+            Duration::from_millis(self.sink.0.get_ref().link_idle_timeout)
+        };
+        let expires = if timeout.as_millis() > 0 {
+            Some(Instant::now() + timeout)
+        } else {
+            None
+        };
+
+        self.remote_handles.retain(|_, token| *token != idx);
+        self.suspended_links
+            .insert(name, SuspendedLink { idx, expires });
+
+        let detach = Detach {
+            handle,
+            closed: false,
+            error: None,
+        };
+        self.post_frame(detach.into());
+        true
+    }
+
+    /// Refuse remote attach of suspended link with the same role
+    fn refuse_resume(&mut self, attach: &Attach) {
+        trace!(
+            "Link role does not match suspended link: {:?} {:?}",
+            attach.name(),
+            attach.role
+        );
+        let detach = Detach {
+            handle: attach.handle(),
+            closed: true,
+            error: Some(Error {
+                condition: AmqpError::InvalidField.into(),
+                description: Some(ByteString::from_static(
+                    "Link role does not match suspended link",
+                )),
+                info: None,
+            }),
+        };
+        self.post_frame(detach.into());
+    }
+
+    /// Suspended links that are not resumed within their time-out
+    pub(crate) fn expired_links(&self, now: Instant) -> Vec<Either<SenderLink, ReceiverLink>> {
+        self.suspended_links
+            .values()
+            .filter(|link| link.expires.map(|t| t <= now).unwrap_or(false))
+            .filter_map(|link| match self.links.get(link.idx) {
+                Some(Either::Left(SenderLinkState::Established(ref link))) => {
+                    Some(Either::Left(link.clone()))
+                }
+                Some(Either::Right(ReceiverLinkState::Established(ref link))) => {
+                    Some(Either::Right(link.clone()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub(crate) fn has_suspended_links(&self) -> bool {
+        !self.suspended_links.is_empty()
+    }
+
+    /// Resume link suspended by remote peer
+    fn resume_link(&mut self, idx: usize, attach: &Attach) {
+        let (role, delivery_count, max_message_size) = match self.links.get(idx) {
+            Some(Either::Left(SenderLinkState::Established(link))) => {
+                link.inner.get_mut().resumed(attach);
+                (Role::Sender, Some(link.delivery_count()), None)
+            }
+            Some(Either::Right(ReceiverLinkState::Established(link))) => (
+                Role::Receiver,
+                None,
+                link.inner.get_ref().max_message_size(),
+            ),
+            _ => {
+                error!("Inconsistent session state, bug");
+                return;
+            }
+        };
+        trace!(
+            "Link is resumed by remote peer: {:?} {} -> {}",
+            attach.name(),
+            idx,
+            attach.handle()
+        );
+
+        self.remote_handles.insert(attach.handle(), idx);
+        let frame = Attach {
+            name: attach.name.clone(),
+            handle: idx as Handle,
+            role,
+            snd_settle_mode: attach.snd_settle_mode(),
+            rcv_settle_mode: attach.rcv_settle_mode(),
+            source: attach.source.clone(),
+            target: attach.target.clone(),
+            unsettled: None,
+            incomplete_unsettled: false,
+            initial_delivery_count: delivery_count,
+            max_message_size,
+            offered_capabilities: None,
+            desired_capabilities: None,
+            properties: None,
+        };
+        self.post_frame(frame.into());
+
+        if let Some(Either::Right(ReceiverLinkState::Established(link))) = self.links.get(idx) {
+            link.inner.get_mut().resumed();
         }
     }
 
@@ -773,35 +1293,88 @@ impl SessionInner {
             );
         }
 
-        if from == to {
-            if let Some(val) = self.unsettled_deliveries.remove(&from) {
-                if !disposition.settled {
-                    let mut disp = disposition.clone();
-                    disp.role = Role::Sender;
-                    disp.settled = true;
-                    disp.state = Some(DeliveryState::Accepted(Accepted {}));
-                    self.post_frame(Frame::Disposition(disp));
+        // range is set by remote peer, walk it only if it is shorter than unsettled set
+        let ids: Vec<DeliveryNumber> =
+            if (to.wrapping_sub(from) as usize) < self.unsettled_deliveries.len() {
+                serial_range(from, to).collect()
+            } else {
+                let mut ids: Vec<_> = self
+                    .unsettled_deliveries
+                    .keys()
+                    .copied()
+                    .filter(|k| k.wrapping_sub(from) <= to.wrapping_sub(from))
+                    .collect();
+                ids.sort_by_key(|k| k.wrapping_sub(from));
+                ids
+            };
+
+        // settled deliveries, grouped to ranges of the same link
+        let mut ranges: Vec<(Handle, Range<DeliveryNumber>)> = Vec::new();
+        for k in ids {
+            if let Some((handle, val)) = self.unsettled_deliveries.remove(&k) {
+                let _ = val.send(Ok(disposition.clone()));
+                match ranges.last_mut() {
+                    Some((hnd, ref mut range)) if *hnd == handle && range.end == k => {
+                        range.end = k.wrapping_add(1)
+                    }
+                    _ => ranges.push((handle, k..k.wrapping_add(1))),
                 }
-                let _ = val.send(Ok(disposition));
             }
-        } else {
-            if !disposition.settled {
-                let mut disp = disposition.clone();
-                disp.role = Role::Sender;
-                disp.settled = true;
-                disp.state = Some(DeliveryState::Accepted(Accepted {}));
-                self.post_frame(Frame::Disposition(disp));
+        }
+
+        if !disposition.settled && (from != to || !ranges.is_empty()) {
+            let mut disp = disposition.clone();
+            disp.role = Role::Sender;
+            disp.settled = true;
+            disp.state = Some(DeliveryState::Accepted(Accepted {}));
+            self.post_frame(Frame::Disposition(disp));
+        }
+
+        // notify link subscribers
+        if let Some(state) = disposition.state {
+            let callbacks: Vec<_> = ranges
+                .into_iter()
+                .filter_map(|(handle, range)| {
+                    if let Some(Either::Left(SenderLinkState::Established(ref link))) =
+                        self.links.get(handle as usize)
+                    {
+                        link.inner
+                            .get_ref()
+                            .on_disposition
+                            .clone()
+                            .map(|f| (f, range))
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+
+            for (f, range) in callbacks {
+                (*f)(range, state.clone());
             }
+        }
+    }
 
-            for k in from..=to {
-                if let Some(val) = self.unsettled_deliveries.remove(&k) {
-                    let _ = val.send(Ok(disposition.clone()));
-                }
+    /// Settle received deliveries that are left unsettled by receiver links
+    fn settle_incoming_deliveries(&mut self, disposition: &Disposition) {
+        for (_, link) in self.links.iter_mut() {
+            if let Either::Right(ReceiverLinkState::Established(ref link)) = link {
+                link.inner
+                    .get_mut()
+                    .remote_settled(disposition.first, disposition.last);
             }
         }
     }
 
     pub(crate) fn apply_flow(&mut self, flow: &Flow) {
+        if let Some(handle) = flow.handle() {
+            if !self.remote_handles.contains_key(&handle) {
+                error!("Flow's link {:?} is unknown", handle);
+                self.unattached_handle(handle);
+                return;
+            }
+        }
+
         // # AMQP1.0 2.5.6
         self.next_incoming_id = flow.next_outgoing_id();
         self.remote_outgoing_window = flow.outgoing_window();
@@ -809,8 +1382,8 @@ impl SessionInner {
         self.remote_incoming_window = flow
             .next_incoming_id()
             .unwrap_or(INITIAL_OUTGOING_ID)
-            .saturating_add(flow.incoming_window())
-            .saturating_sub(self.next_outgoing_id);
+            .wrapping_add(flow.incoming_window())
+            .wrapping_sub(self.next_outgoing_id);
 
         trace!(
             "Session received credit {:?}. window: {}, pending: {}",
@@ -819,33 +1392,26 @@ impl SessionInner {
             self.pending_transfers.len()
         );
 
-        while let Some(t) = self.pending_transfers.pop_front() {
-            self.send_transfer(
-                t.link_handle,
-                t.idx,
-                t.body,
-                t.state,
-                t.tag,
-                t.settled,
-                t.message_format,
-            );
-            if self.remote_outgoing_window == 0 {
-                break;
-            }
-        }
+        self.flush_pending_transfers();
 
         // apply link flow
-        if let Some(Either::Left(link)) = flow
+        match flow
             .handle()
             .and_then(|h| self.remote_handles.get(&h).copied())
             .and_then(|h| self.links.get_mut(h))
         {
-            match link {
+            Some(Either::Left(link)) => match link {
                 SenderLinkState::Established(ref mut link) => {
                     link.inner.get_mut().apply_flow(&flow);
                 }
                 _ => warn!("Received flow frame"),
+            },
+            Some(Either::Right(ReceiverLinkState::Established(ref link))) => {
+                if let Some(available) = flow.available() {
+                    link.inner.get_mut().set_available(available);
+                }
             }
+            _ => (),
         }
         if flow.echo() {
             self.send_flow();
@@ -894,17 +1460,52 @@ impl SessionInner {
         self.post_frame(flow.into());
     }
 
+    pub(crate) fn snd_link_flow(
+        &mut self,
+        handle: u32,
+        delivery_count: u32,
+        credit: u32,
+        available: u32,
+    ) {
+        let flow = Flow {
+            next_incoming_id: if self.local {
+                Some(self.next_incoming_id)
+            } else {
+                None
+            },
+            incoming_window: std::u32::MAX,
+            next_outgoing_id: self.next_outgoing_id,
+            outgoing_window: self.remote_incoming_window,
+            handle: Some(handle),
+            delivery_count: Some(delivery_count),
+            link_credit: Some(credit),
+            available: Some(available),
+            drain: false,
+            echo: false,
+            properties: None,
+        };
+        self.post_frame(flow.into());
+    }
+
     pub(crate) fn post_frame(&mut self, frame: Frame) {
         self.sink
             .post_frame(AmqpFrame::new(self.remote_channel_id, frame));
     }
 
+    fn post_transfer(&mut self, frame: Frame) {
+        self.sink
+            .post_transfer(AmqpFrame::new(self.remote_channel_id, frame));
+    }
+
     pub(crate) fn open_sender_link(
         &mut self,
         mut frame: Attach,
-    ) -> oneshot::Receiver<Result<SenderLink, AmqpProtocolError>> {
+        auto_suffix: bool,
+    ) -> Result<oneshot::Receiver<Result<SenderLink, AmqpProtocolError>>, AmqpProtocolError> {
+        frame.name = self.link_name(&frame.name, auto_suffix)?;
         let (tx, rx) = oneshot::channel();
 
+        self.check_handle()?;
         let entry = self.links.vacant_entry();
         let token = entry.key();
         entry.insert(Either::Left(SenderLinkState::Opening(Some(tx))));
@@ -913,7 +1514,54 @@ impl SessionInner {
 
         self.links_by_name.insert(frame.name.clone(), token);
         self.post_frame(Frame::Attach(frame));
-        rx
+        Ok(rx)
+    }
+
+    fn is_suspended(&self, idx: usize) -> bool {
+        self.suspended_links.values().any(|link| link.idx == idx)
+    }
+
+    /// Check that handle of new link does not exceed remote peer's `handle-max`
+    fn check_handle(&self) -> Result<(), AmqpProtocolError> {
+        if self.links.vacant_key() > self.remote_handle_max as usize {
+            Err(AmqpProtocolError::TooManyLinks(self.remote_handle_max))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check link name, link names must be unique within session
+    ///
+    /// If `auto_suffix` is set, numeric suffix is appended to the name in use.
+    fn link_name(
+        &self,
+        name: &ByteString,
+        auto_suffix: bool,
+    ) -> Result<ByteString, AmqpProtocolError> {
+        if !self.links_by_name.contains_key(name) {
+            Ok(name.clone())
+        } else if auto_suffix {
+            let mut idx = 1;
+            loop {
+                let name = ByteString::from(format!("{}-{}", name, idx));
+                if !self.links_by_name.contains_key(&name) {
+                    return Ok(name);
+                }
+                idx += 1;
+            }
+        } else {
+            Err(AmqpProtocolError::LinkNameInUse(name.clone()))
+        }
+    }
+
+    /// Remove link and its name
+    fn remove_link(&mut self, idx: usize) {
+        self.links.remove(idx);
+        self.links_by_name.retain(|_, token| *token != idx);
+        self.suspended_links.retain(|_, link| link.idx != idx);
+        // release remote handle, it could be reused by remote peer
+        // and slab entry could be reused by new link
+        self.remote_handles.retain(|_, token| *token != idx);
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -925,11 +1573,18 @@ impl SessionInner {
         state: TransferState,
         tag: Option<Bytes>,
         settled: Option<bool>,
+        delivery_state: Option<DeliveryState>,
         message_format: Option<MessageFormat>,
     ) {
-        if self.remote_incoming_window == 0 {
+        if self.remote_incoming_window == 0
+            || !self.sink.has_write_budget()
+            || self.sink.0.get_ref().transfers_paused
+            || !self.pending_transfers.is_empty()
+            || self.is_lane_busy(link_handle)
+        {
             log::trace!(
-                "Remote window is 0, push to pending queue, hnd:{:?}",
+                "Remote window is {} or transfers are held back, push to pending queue, hnd:{:?}",
+                self.remote_incoming_window,
                 link_handle
             );
             self.pending_transfers.push_back(PendingTransfer {
@@ -939,20 +1594,82 @@ impl SessionInner {
                 state,
                 tag,
                 settled,
+                delivery_state,
                 message_format,
             });
+            if self.remote_incoming_window > 0 && !self.sink.0.get_ref().transfers_paused {
+                self.sink.request_flush();
+            }
         } else {
-            let frame =
-                self.prepare_transfer(link_handle, body, state, tag, settled, message_format);
+            let frame = self.prepare_transfer(
+                link_handle,
+                body,
+                state,
+                tag,
+                settled,
+                delivery_state,
+                message_format,
+            );
             log::trace!(
                 "Sending transfer over {} window: {}",
                 link_handle,
                 self.remote_incoming_window
             );
-            self.post_frame(frame);
+            self.post_transfer(frame);
         }
     }
 
+    /// Write pending transfers within write budget
+    ///
+    /// If budget is exhausted, rest of the transfers get written
+    /// on next dispatcher poll, so large backlog does not starve
+    /// reads and heartbeats.
+    pub(crate) fn flush_pending_transfers(&mut self) {
+        if self.sink.0.get_ref().transfers_paused {
+            return;
+        }
+
+        while self.remote_incoming_window > 0 && self.sink.has_write_budget() {
+            // incomplete delivery keeps the lane until its last frame
+            let next = if let Some(hnd) = self.transfer_lane {
+                self.pending_transfers
+                    .iter()
+                    .position(|t| t.link_handle == hnd)
+            } else {
+                Some(0)
+            };
+
+            if let Some(t) = next.and_then(|idx| self.pending_transfers.remove(idx)) {
+                let frame = self.prepare_transfer(
+                    t.link_handle,
+                    t.body,
+                    t.state,
+                    t.tag,
+                    t.settled,
+                    t.delivery_state,
+                    t.message_format,
+                );
+                self.post_transfer(frame);
+            } else {
+                break;
+            }
+        }
+
+        if self.remote_incoming_window > 0
+            && !self.sink.has_write_budget()
+            && !self.pending_transfers.is_empty()
+        {
+            self.sink.request_flush();
+        }
+    }
+
+    /// Check if other link's multi-frame delivery is in progress
+    fn is_lane_busy(&self, link_handle: Handle) -> bool {
+        self.transfer_lane
+            .map(|hnd| hnd != link_handle)
+            .unwrap_or(false)
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub(crate) fn prepare_transfer(
         &mut self,
@@ -961,12 +1678,15 @@ impl SessionInner {
         tr_state: TransferState,
         delivery_tag: Option<Bytes>,
         settled: Option<bool>,
+        delivery_state: Option<DeliveryState>,
         message_format: Option<MessageFormat>,
     ) -> Frame {
         self.remote_incoming_window -= 1;
 
         let settled2 = settled.clone().unwrap_or(false);
-        let state = if settled2 {
+        let state = if delivery_state.is_some() {
+            delivery_state
+        } else if settled2 {
             Some(DeliveryState::Accepted(Accepted {}))
         } else {
             None
@@ -991,7 +1711,7 @@ impl SessionInner {
         match tr_state {
             TransferState::First(promise) | TransferState::Only(promise) => {
                 let delivery_id = self.next_outgoing_id;
-                self.next_outgoing_id += 1;
+                self.next_outgoing_id = self.next_outgoing_id.wrapping_add(1);
 
                 transfer.delivery_id = Some(delivery_id);
                 transfer.delivery_tag = if let Some(tag) = delivery_tag {
@@ -1004,7 +1724,11 @@ impl SessionInner {
 
                 transfer.more = more;
                 transfer.batchable = more;
-                self.unsettled_deliveries.insert(delivery_id, promise);
+                if more {
+                    self.transfer_lane = Some(link_handle);
+                }
+                self.unsettled_deliveries
+                    .insert(delivery_id, (link_handle, promise));
             }
             TransferState::Continue => {
                 transfer.more = true;
@@ -1012,9 +1736,30 @@ impl SessionInner {
             }
             TransferState::Last => {
                 transfer.more = false;
+                self.transfer_lane = None;
             }
         }
 
         Frame::Transfer(transfer)
     }
 }
+
+/// Check incoming delivery id against expected one and count skipped ids
+fn track_delivery_id(next: &mut Option<DeliveryNumber>, missed: &mut u64, id: DeliveryNumber) {
+    match *next {
+        Some(expected) if serial_lt(expected, id) => {
+            let gap = id.wrapping_sub(expected);
+            log::warn!(
+                "Gap in incoming delivery ids, expected {} got {}, missed {}",
+                expected,
+                id,
+                gap
+            );
+            *missed += gap as u64;
+            *next = Some(id.wrapping_add(1));
+        }
+        // continuation frame or retransmitted delivery
+        Some(expected) if id != expected => (),
+        _ => *next = Some(id.wrapping_add(1)),
+    }
+}
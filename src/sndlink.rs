@@ -1,18 +1,29 @@
 use std::collections::VecDeque;
 use std::future::Future;
+use std::ops::Range;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 use ntex::channel::{condition, oneshot};
 use ntex::util::{ByteString, Bytes, BytesMut, Either, Ready};
 use ntex_amqp_codec::protocol::{
-    Attach, DeliveryNumber, DeliveryState, Disposition, Error, Flow, MessageFormat,
-    ReceiverSettleMode, Role, SenderSettleMode, SequenceNo, Target, TerminusDurability,
-    TerminusExpiryPolicy, TransferBody,
+    Accepted, Attach, Coordinator, DeliveryNumber, DeliveryState, Disposition, Error, Fields, Flow,
+    Frame, MessageFormat, ReceiverSettleMode, Role, Seconds, SenderSettleMode, SequenceNo, Source,
+    Symbols, Target, TargetTerminus, TerminusDurability, TerminusExpiryPolicy, Transfer,
+    TransferBody,
 };
-use ntex_amqp_codec::Encode;
+use ntex_amqp_codec::types::Symbol;
+use ntex_amqp_codec::{AmqpFrame, Encode};
+use uuid::Uuid;
 
 use crate::cell::Cell;
-use crate::error::AmqpProtocolError;
-use crate::session::{Session, SessionInner, TransferState};
+#[cfg(feature = "compress")]
+use crate::compress::ContentEncoding;
+use crate::error::{AmqpProtocolError, NoCredit};
+use crate::session::{LinkInfo, Session, SessionInner, TransferState};
+use crate::span::Span;
+use crate::types::DeliveryResult;
+use crate::utils::serial_lt;
 use crate::{Delivery, Handle};
 
 #[derive(Clone)]
@@ -28,18 +39,51 @@ impl std::fmt::Debug for SenderLink {
     }
 }
 
+type DispositionFn = dyn Fn(Range<DeliveryNumber>, DeliveryState);
+
+/// Delivery tag generation strategy of sender link
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DeliveryTag {
+    /// Link delivery counter encoded as 4 bytes little-endian
+    Counter,
+    /// Random v4 uuid, 16 bytes
+    Uuid,
+}
+
+impl Default for DeliveryTag {
+    fn default() -> Self {
+        DeliveryTag::Counter
+    }
+}
+
 pub(crate) struct SenderLinkInner {
     pub(crate) id: usize,
     idx: u32,
     name: ByteString,
+    /// Name of attach frame, `name` is source address for links attached by remote peer
+    link_name: ByteString,
+    source: Option<ByteString>,
+    target: Option<ByteString>,
+    properties: Option<Fields>,
+    /// Source terminus time-out
+    terminus_timeout: Seconds,
     session: Session,
     remote_handle: Handle,
     delivery_count: SequenceNo,
     link_credit: u32,
+    max_chunk_size: usize,
+    #[cfg(feature = "compress")]
+    content_encoding: Option<ContentEncoding>,
     pending_transfers: VecDeque<PendingTransfer>,
     error: Option<AmqpProtocolError>,
     closed: bool,
     on_close: condition::Condition,
+    last_activity: Instant,
+    available: u32,
+    delivery_tag: DeliveryTag,
+    tag_counter: u32,
+    pub(crate) on_disposition: Option<Rc<DispositionFn>>,
+    pub(crate) span: Span,
 }
 
 struct PendingTransfer {
@@ -48,6 +92,7 @@ struct PendingTransfer {
     body: Option<TransferBody>,
     state: TransferState,
     settle: Option<bool>,
+    delivery_state: Option<DeliveryState>,
     message_format: Option<MessageFormat>,
 }
 
@@ -68,10 +113,25 @@ impl SenderLink {
         self.inner.remote_handle
     }
 
+    /// Link credit granted by remote receiver
+    pub fn credit(&self) -> u32 {
+        self.inner.get_ref().link_credit
+    }
+
+    /// Number of deliveries sent by the link, serial number
+    pub fn delivery_count(&self) -> u32 {
+        self.inner.get_ref().delivery_count
+    }
+
     pub fn session(&self) -> &Session {
         &self.inner.get_ref().session
     }
 
+    /// Link properties sent by remote peer in `Attach` frame
+    pub fn remote_properties(&self) -> Option<&Fields> {
+        self.inner.get_ref().properties.as_ref()
+    }
+
     pub fn session_mut(&mut self) -> &mut Session {
         &mut self.inner.get_mut().session
     }
@@ -80,7 +140,7 @@ impl SenderLink {
     where
         T: Into<TransferBody>,
     {
-        self.inner.get_mut().send(body, None)
+        self.inner.get_mut().send(body, None, None, None)
     }
 
     pub fn send_with_tag<T>(
@@ -91,13 +151,130 @@ impl SenderLink {
     where
         T: Into<TransferBody>,
     {
-        self.inner.get_mut().send(body, Some(tag))
+        self.inner.get_mut().send(body, Some(tag), None, None)
+    }
+
+    /// Send message with generated delivery tag
+    ///
+    /// Returns delivery tag of the message, it could be used
+    /// for correlation with delivery state set by remote peer.
+    pub fn send_tagged<T>(&self, body: T) -> (Bytes, Delivery)
+    where
+        T: Into<TransferBody>,
+    {
+        let inner = self.inner.get_mut();
+        let tag = inner.next_tag();
+        let delivery = inner.send(body, Some(tag.clone()), None, None);
+        (tag, delivery)
+    }
+
+    /// Send message with specified `message-format`
+    ///
+    /// Format overrides message format of `Message` body, by default
+    /// format of `Message` body is used and raw `Bytes` bodies are sent without format.
+    pub fn send_with_format<T>(
+        &self,
+        body: T,
+        format: MessageFormat,
+    ) -> impl Future<Output = Result<Disposition, AmqpProtocolError>>
+    where
+        T: Into<TransferBody>,
+    {
+        self.inner.get_mut().send(body, None, None, Some(format))
+    }
+
+    /// Set max body size of single transfer frame.
+    ///
+    /// Larger messages are sent as a set of transfers. Chunk size
+    /// never exceeds remote max frame size, zero value means
+    /// chunk size is derived from remote max frame size.
+    pub fn set_max_chunk_size(&self, size: usize) {
+        self.inner.get_mut().max_chunk_size = size;
+    }
+
+    /// Set delivery tag generation strategy
+    ///
+    /// Tags are generated for deliveries sent without explicit tag.
+    /// By default link delivery counter is used.
+    pub fn set_delivery_tag(&self, tag: DeliveryTag) {
+        self.inner.get_mut().delivery_tag = tag;
+    }
+
+    #[cfg(feature = "compress")]
+    /// Compress bodies of sent messages.
+    ///
+    /// Data sections of `Message` bodies are compressed and `content-encoding`
+    /// property is set, raw `Bytes` bodies are sent as is.
+    pub fn set_content_encoding(&self, encoding: Option<ContentEncoding>) {
+        self.inner.get_mut().content_encoding = encoding;
+    }
+
+    /// Send message only if link has credit for it
+    ///
+    /// Unlike `send()` message is not queued while link has no credit,
+    /// `NoCredit` error is returned immediately instead.
+    pub fn try_send<T>(&self, body: T) -> Result<Delivery, NoCredit>
+    where
+        T: Into<TransferBody>,
+    {
+        let inner = self.inner.get_mut();
+        // failed link resolves delivery with link error
+        if inner.error.is_none() && !inner.has_credit() {
+            Err(NoCredit)
+        } else {
+            Ok(inner.send(body, None, None, None))
+        }
+    }
+
+    /// Send message and wait for remote peer to settle delivery
+    ///
+    /// Future resolves with delivery result set by remote peer. Use
+    /// `DeliveryResult::into_result()` to report rejected delivery as error.
+    pub fn deliver<T>(
+        &self,
+        body: T,
+    ) -> impl Future<Output = Result<DeliveryResult, AmqpProtocolError>>
+    where
+        T: Into<TransferBody>,
+    {
+        let delivery = self.inner.get_mut().send(body, None, None, None);
+
+        async move {
+            let mut disp = delivery.await?;
+            match disp.state.take() {
+                Some(state) => Ok(state.into()),
+                None => Err(AmqpProtocolError::Unexpected(Box::new(Frame::Disposition(
+                    disp,
+                )))),
+            }
+        }
     }
 
     pub fn settle_message(&self, id: DeliveryNumber, state: DeliveryState) {
         self.inner.get_mut().settle_message(id, state)
     }
 
+    /// Set callback for delivery states received from remote peer
+    ///
+    /// Callback is called for each incoming `Disposition` frame with range
+    /// of delivery ids of this link settled by the frame and its delivery state.
+    pub fn on_disposition<F>(&self, f: F)
+    where
+        F: Fn(Range<DeliveryNumber>, DeliveryState) + 'static,
+    {
+        self.inner.get_mut().on_disposition = Some(Rc::new(f));
+    }
+
+    /// Advertise number of messages sender has ready to send
+    ///
+    /// Sends `Flow` frame with `available` field, remote receiver
+    /// could use it for sizing link credit.
+    pub fn set_available(&self, available: u32) {
+        let inner = self.inner.get_mut();
+        inner.available = available;
+        inner.post_flow();
+    }
+
     pub fn close(&self) -> impl Future<Output = Result<(), AmqpProtocolError>> {
         self.inner.get_mut().close(None)
     }
@@ -120,23 +297,38 @@ impl SenderLink {
 impl SenderLinkInner {
     pub(crate) fn new(
         id: usize,
-        name: ByteString,
-        handle: Handle,
+        attach: &Attach,
         delivery_count: SequenceNo,
         session: Cell<SessionInner>,
     ) -> SenderLinkInner {
+        let span = session.get_ref().span.link(id as u32);
+
         SenderLinkInner {
             id,
-            name,
+            name: attach.name().clone(),
+            link_name: attach.name().clone(),
+            source: attach.source.as_ref().and_then(|s| s.address.clone()),
+            target: attach.target.as_ref().and_then(|t| t.address().cloned()),
+            properties: attach.properties.clone(),
+            terminus_timeout: attach.source.as_ref().map(|s| s.timeout).unwrap_or(0),
+            remote_handle: attach.handle(),
             delivery_count,
             idx: 0,
             session: Session::new(session),
-            remote_handle: handle,
             link_credit: 0,
+            max_chunk_size: 0,
+            #[cfg(feature = "compress")]
+            content_encoding: None,
             pending_transfers: VecDeque::new(),
             error: None,
             closed: false,
             on_close: condition::Condition::new(),
+            last_activity: Instant::now(),
+            available: 0,
+            delivery_tag: DeliveryTag::default(),
+            tag_counter: 0,
+            on_disposition: None,
+            span,
         }
     }
 
@@ -148,19 +340,34 @@ impl SenderLinkInner {
             }
         }
         let delivery_count = frame.initial_delivery_count.unwrap_or(0);
+        let span = session.get_ref().span.clone();
 
         SenderLinkInner {
             delivery_count,
             id: 0,
             idx: 0,
             name: name.unwrap_or_else(ByteString::default),
+            link_name: frame.name().clone(),
+            source: frame.source.as_ref().and_then(|s| s.address.clone()),
+            target: frame.target.as_ref().and_then(|t| t.address().cloned()),
+            properties: frame.properties.clone(),
+            terminus_timeout: frame.source.as_ref().map(|s| s.timeout).unwrap_or(0),
             session: Session::new(session),
             remote_handle: frame.handle(),
             link_credit: 0,
+            max_chunk_size: 0,
+            #[cfg(feature = "compress")]
+            content_encoding: None,
             pending_transfers: VecDeque::new(),
             error: None,
             closed: false,
             on_close: condition::Condition::new(),
+            last_activity: Instant::now(),
+            available: 0,
+            delivery_tag: DeliveryTag::default(),
+            tag_counter: 0,
+            on_disposition: None,
+            span,
         }
     }
 
@@ -168,6 +375,18 @@ impl SenderLinkInner {
         self.id as u32
     }
 
+    /// Link has credit and nothing to send for longer than timeout
+    pub(crate) fn is_idle(&self, now: Instant, timeout: Duration) -> bool {
+        !self.closed
+            && self.link_credit > 0
+            && self.pending_transfers.is_empty()
+            && now.saturating_duration_since(self.last_activity) >= timeout
+    }
+
+    pub(crate) fn terminus_timeout(&self) -> Seconds {
+        self.terminus_timeout
+    }
+
     pub(crate) fn remote_handle(&self) -> Handle {
         self.remote_handle
     }
@@ -176,6 +395,38 @@ impl SenderLinkInner {
         &self.name
     }
 
+    pub(crate) fn link_name(&self) -> &ByteString {
+        &self.link_name
+    }
+
+    /// Link is suspended by remote peer
+    ///
+    /// Link credit is revoked, new deliveries wait for credit
+    /// granted after link is resumed.
+    pub(crate) fn suspended(&mut self) {
+        trace!("Sender link {:?} is suspended", self.name);
+        self.link_credit = 0;
+    }
+
+    /// Link is resumed by remote peer
+    pub(crate) fn resumed(&mut self, attach: &Attach) {
+        trace!("Sender link {:?} is resumed", self.name);
+        self.remote_handle = attach.handle();
+        self.last_activity = Instant::now();
+    }
+
+    pub(crate) fn info(&self) -> LinkInfo {
+        LinkInfo {
+            handle: self.id as Handle,
+            name: self.name.clone(),
+            role: Role::Sender,
+            source: self.source.clone(),
+            target: self.target.clone(),
+            link_credit: self.link_credit,
+            delivery_count: self.delivery_count,
+        }
+    }
+
     pub(crate) fn detached(&mut self, err: AmqpProtocolError) {
         trace!("Detaching sender link {:?} with error {:?}", self.name, err);
 
@@ -218,6 +469,10 @@ impl SenderLinkInner {
     }
 
     pub(crate) fn apply_flow(&mut self, flow: &Flow) {
+        let span = self.span.clone();
+        let _enter = span.enter();
+        self.last_activity = Instant::now();
+
         // #2.7.6
         if let Some(credit) = flow.link_credit() {
             trace!(
@@ -228,53 +483,110 @@ impl SenderLinkInner {
                 self.delivery_count
             );
 
-            let delta = flow
-                .delivery_count
-                .unwrap_or(0)
-                .saturating_add(credit)
-                .saturating_sub(self.delivery_count);
+            // delivery count is a serial number and may wrap around
+            let limit = flow.delivery_count.unwrap_or(0).wrapping_add(credit);
+            let delta = if serial_lt(limit, self.delivery_count) {
+                0
+            } else {
+                limit.wrapping_sub(self.delivery_count)
+            };
             self.link_credit += delta;
 
             let session = self.session.inner.get_mut();
 
-            // credit became available => drain pending_transfers
-            while self.link_credit > 0 {
-                if let Some(transfer) = self.pending_transfers.pop_front() {
+            // credit became available => drain pending_transfers,
+            // continuation transfers do not consume link credit
+            while let Some(transfer) = self.pending_transfers.front() {
+                if transfer.state.is_first() {
+                    if self.link_credit == 0 {
+                        break;
+                    }
                     self.link_credit -= 1;
-                    self.delivery_count = self.delivery_count.saturating_add(1);
-                    session.send_transfer(
-                        self.id as u32,
-                        transfer.idx,
-                        transfer.body,
-                        transfer.state,
-                        transfer.tag,
-                        transfer.settle,
-                        transfer.message_format,
-                    );
-                } else {
-                    break;
+                    self.delivery_count = self.delivery_count.wrapping_add(1);
                 }
+                let transfer = self.pending_transfers.pop_front().unwrap();
+                session.send_transfer(
+                    self.id as u32,
+                    transfer.idx,
+                    transfer.body,
+                    transfer.state,
+                    transfer.tag,
+                    transfer.settle,
+                    transfer.delivery_state,
+                    transfer.message_format,
+                );
             }
         }
 
         if flow.echo() {
-            // todo: send flow
+            self.post_flow();
+        }
+    }
+
+    /// Generate delivery tag for new delivery
+    fn next_tag(&mut self) -> Bytes {
+        match self.delivery_tag {
+            DeliveryTag::Counter => {
+                let tag = self.tag_counter;
+                self.tag_counter = self.tag_counter.wrapping_add(1);
+                Bytes::copy_from_slice(&tag.to_le_bytes())
+            }
+            DeliveryTag::Uuid => Bytes::copy_from_slice(Uuid::new_v4().as_bytes()),
+        }
+    }
+
+    /// Link has credit and no deliveries waiting for it
+    fn has_credit(&self) -> bool {
+        self.link_credit > 0 && self.pending_transfers.is_empty()
+    }
+
+    /// Send sender's view of link state to remote peer
+    fn post_flow(&mut self) {
+        if !self.closed {
+            self.session.inner.get_mut().snd_link_flow(
+                self.id as u32,
+                self.delivery_count,
+                self.link_credit,
+                self.available,
+            );
         }
     }
 
-    pub(crate) fn send<T: Into<TransferBody>>(&mut self, body: T, tag: Option<Bytes>) -> Delivery {
+    pub(crate) fn send<T: Into<TransferBody>>(
+        &mut self,
+        body: T,
+        tag: Option<Bytes>,
+        delivery_state: Option<DeliveryState>,
+        message_format: Option<MessageFormat>,
+    ) -> Delivery {
         if let Some(ref err) = self.error {
             Delivery::Resolved(Err(err.clone()))
         } else {
-            let body = body.into();
-            let message_format = body.message_format();
+            #[allow(unused_mut)]
+            let mut body = body.into();
+            #[cfg(feature = "compress")]
+            {
+                if let (Some(enc), TransferBody::Message(msg)) = (self.content_encoding, &mut body)
+                {
+                    if let Err(e) = enc.compress_message(msg) {
+                        log::error!("Cannot compress message body, sent as is: {}", e);
+                    }
+                }
+            }
+            let message_format = message_format.or_else(|| body.message_format());
+            let tag = Some(tag.unwrap_or_else(|| self.next_tag()));
             let (delivery_tx, delivery_rx) = oneshot::channel();
 
             let max_frame_size = self.session.inner.get_ref().max_frame_size();
-            let max_frame_size = if max_frame_size > 2048 {
-                max_frame_size - 2048
-            } else if max_frame_size == 0 {
+            let max_frame_size = if max_frame_size == 0 {
                 usize::MAX
+            } else {
+                // space left for body after frame header and transfer performative
+                let overhead = transfer_overhead(&tag, &delivery_state, message_format);
+                std::cmp::max(max_frame_size.saturating_sub(overhead), 1)
+            };
+            let max_frame_size = if self.max_chunk_size > 0 {
+                std::cmp::min(self.max_chunk_size, max_frame_size)
             } else {
                 max_frame_size
             };
@@ -295,6 +607,7 @@ impl SenderLinkInner {
                     chunk.into(),
                     tag,
                     TransferState::First(delivery_tx),
+                    delivery_state,
                     message_format,
                 );
 
@@ -303,19 +616,32 @@ impl SenderLinkInner {
 
                     // last chunk
                     if body.is_empty() {
-                        self.send_inner(chunk.into(), None, TransferState::Last, message_format);
+                        self.send_inner(
+                            chunk.into(),
+                            None,
+                            TransferState::Last,
+                            None,
+                            message_format,
+                        );
                         break;
                     } else {
                         self.send_inner(
                             chunk.into(),
                             None,
                             TransferState::Continue,
+                            None,
                             message_format,
                         );
                     }
                 }
             } else {
-                self.send_inner(body, tag, TransferState::Only(delivery_tx), message_format);
+                self.send_inner(
+                    body,
+                    tag,
+                    TransferState::Only(delivery_tx),
+                    delivery_state,
+                    message_format,
+                );
             }
 
             Delivery::Pending(delivery_rx)
@@ -327,11 +653,15 @@ impl SenderLinkInner {
         body: TransferBody,
         tag: Option<Bytes>,
         state: TransferState,
+        delivery_state: Option<DeliveryState>,
         message_format: Option<MessageFormat>,
     ) {
-        if self.link_credit == 0 {
+        self.last_activity = Instant::now();
+
+        // continuation transfers follow first transfer of the delivery
+        if (state.is_first() && self.link_credit == 0) || !self.pending_transfers.is_empty() {
             log::trace!(
-                "Sender link credit is 0, push to pending queue hnd:{} {:?}, queue size: {}",
+                "No link credit, push to pending queue hnd:{} {:?}, queue size: {}",
                 self.id as u32,
                 tag,
                 self.pending_transfers.len()
@@ -339,14 +669,17 @@ impl SenderLinkInner {
             self.pending_transfers.push_back(PendingTransfer {
                 tag,
                 state,
+                delivery_state,
                 message_format,
                 settle: Some(false),
                 body: Some(body),
                 idx: self.idx,
             });
         } else {
-            self.link_credit -= 1;
-            self.delivery_count = self.delivery_count.saturating_add(1);
+            if state.is_first() {
+                self.link_credit -= 1;
+                self.delivery_count = self.delivery_count.wrapping_add(1);
+            }
             self.session.inner.get_mut().send_transfer(
                 self.id as u32,
                 self.idx,
@@ -354,6 +687,7 @@ impl SenderLinkInner {
                 state,
                 tag,
                 None,
+                delivery_state,
                 message_format,
             );
         }
@@ -376,6 +710,7 @@ impl SenderLinkInner {
 pub struct SenderLinkBuilder {
     frame: Attach,
     session: Cell<SessionInner>,
+    auto_suffix: bool,
 }
 
 impl SenderLinkBuilder {
@@ -396,7 +731,7 @@ impl SenderLinkBuilder {
             snd_settle_mode: SenderSettleMode::Mixed,
             rcv_settle_mode: ReceiverSettleMode::First,
             source: None,
-            target: Some(target),
+            target: Some(target.into()),
             unsettled: None,
             incomplete_unsettled: false,
             initial_delivery_count: None,
@@ -406,7 +741,34 @@ impl SenderLinkBuilder {
             properties: None,
         };
 
-        SenderLinkBuilder { frame, session }
+        SenderLinkBuilder {
+            frame,
+            session,
+            auto_suffix: false,
+        }
+    }
+
+    pub(crate) fn coordinator(name: ByteString, session: Cell<SessionInner>) -> Self {
+        let source = Source {
+            address: None,
+            durable: TerminusDurability::None,
+            expiry_policy: TerminusExpiryPolicy::SessionEnd,
+            timeout: 0,
+            dynamic: false,
+            dynamic_node_properties: None,
+            distribution_mode: None,
+            filter: None,
+            default_outcome: None,
+            outcomes: Some(Symbols::from(vec![
+                Symbol::from_static("amqp:accepted:list"),
+                Symbol::from_static("amqp:rejected:list"),
+            ])),
+            capabilities: None,
+        };
+        let mut builder = SenderLinkBuilder::new(name, ByteString::default(), session);
+        builder.frame.source = Some(source);
+        builder.frame.target = Some(Coordinator { capabilities: None }.into());
+        builder
     }
 
     pub fn max_message_size(mut self, size: u64) -> Self {
@@ -414,6 +776,55 @@ impl SenderLinkBuilder {
         self
     }
 
+    /// Set link properties, i.e. `priority` or broker specific `x-opt-*` values
+    pub fn properties(mut self, properties: Fields) -> Self {
+        self.frame.properties = Some(properties);
+        self
+    }
+
+    /// Set durability of the target terminus.
+    ///
+    /// Default is `TerminusDurability::None`
+    pub fn durable(self, durable: TerminusDurability) -> Self {
+        self.with_target(|target| target.durable = durable)
+    }
+
+    /// Set expiry policy of the target terminus.
+    ///
+    /// Default is `TerminusExpiryPolicy::SessionEnd`
+    pub fn expiry_policy(self, policy: TerminusExpiryPolicy) -> Self {
+        self.with_target(|target| target.expiry_policy = policy)
+    }
+
+    /// Set time in seconds the target terminus is retained after expiry policy triggers.
+    pub fn expiry_timeout(self, timeout: Seconds) -> Self {
+        self.with_target(|target| target.timeout = timeout)
+    }
+
+    /// Set capabilities of the target terminus.
+    pub fn capabilities(self, capabilities: Symbols) -> Self {
+        self.with_target(|target| target.capabilities = Some(capabilities))
+    }
+
+    fn with_target<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut Target),
+    {
+        if let Some(TargetTerminus::Target(ref mut target)) = self.frame.target {
+            f(target);
+        }
+        self
+    }
+
+    /// Append numeric suffix to link name if name is already in use.
+    ///
+    /// By default opening link with name in use fails,
+    /// final name is available via `SenderLink::name()`
+    pub fn auto_suffix_name(mut self) -> Self {
+        self.auto_suffix = true;
+        self
+    }
+
     pub fn with_frame<F>(mut self, f: F) -> Self
     where
         F: FnOnce(&mut Attach),
@@ -423,7 +834,11 @@ impl SenderLinkBuilder {
     }
 
     pub async fn open(self) -> Result<SenderLink, AmqpProtocolError> {
-        let result = self.session.get_mut().open_sender_link(self.frame).await;
+        let result = self
+            .session
+            .get_mut()
+            .open_sender_link(self.frame, self.auto_suffix)?
+            .await;
 
         match result {
             Ok(Ok(link)) => Ok(link),
@@ -432,3 +847,30 @@ impl SenderLinkBuilder {
         }
     }
 }
+
+/// Encoded size of frame header and largest transfer performative of the delivery
+fn transfer_overhead(
+    tag: &Option<Bytes>,
+    state: &Option<DeliveryState>,
+    message_format: Option<MessageFormat>,
+) -> usize {
+    let transfer = Transfer {
+        handle: Handle::MAX,
+        delivery_id: Some(DeliveryNumber::MAX),
+        delivery_tag: tag.clone(),
+        message_format: Some(message_format.unwrap_or(MessageFormat::MAX)),
+        settled: Some(false),
+        more: true,
+        rcv_settle_mode: Some(ReceiverSettleMode::Second),
+        state: Some(
+            state
+                .clone()
+                .unwrap_or(DeliveryState::Accepted(Accepted {})),
+        ),
+        resume: true,
+        aborted: true,
+        batchable: true,
+        body: None,
+    };
+    AmqpFrame::new(u16::MAX, Frame::Transfer(transfer)).encoded_size()
+}
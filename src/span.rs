@@ -0,0 +1,59 @@
+//! Logging context of connection, session and link
+//!
+//! With `tracing` feature enabled, context is a `tracing` span. Log records
+//! emitted while span is entered carry connection id, channel id and link handle.
+#[cfg(feature = "tracing")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(feature = "tracing")]
+static CONNECTION_ID: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(feature = "tracing")]
+#[derive(Clone, Debug)]
+pub(crate) struct Span(tracing::Span);
+
+#[cfg(feature = "tracing")]
+impl Span {
+    pub(crate) fn connection() -> Span {
+        let id = CONNECTION_ID.fetch_add(1, Ordering::Relaxed);
+        Span(tracing::debug_span!(parent: None, "amqp", conn = id))
+    }
+
+    pub(crate) fn session(&self, channel: u16) -> Span {
+        Span(tracing::debug_span!(parent: &self.0, "session", channel))
+    }
+
+    pub(crate) fn link(&self, handle: u32) -> Span {
+        Span(tracing::debug_span!(parent: &self.0, "link", handle))
+    }
+
+    pub(crate) fn enter(&self) -> tracing::span::Entered<'_> {
+        self.0.enter()
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+#[derive(Clone, Debug)]
+pub(crate) struct Span;
+
+#[cfg(not(feature = "tracing"))]
+impl Span {
+    pub(crate) fn connection() -> Span {
+        Span
+    }
+
+    pub(crate) fn session(&self, _: u16) -> Span {
+        Span
+    }
+
+    pub(crate) fn link(&self, _: u32) -> Span {
+        Span
+    }
+
+    pub(crate) fn enter(&self) -> Entered {
+        Entered
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct Entered;
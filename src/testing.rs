@@ -0,0 +1,352 @@
+//! In-memory transport for testing client and server without sockets
+use std::{cell::RefCell, cmp, fmt, io, pin::Pin, rc::Rc, task::Context, task::Poll};
+
+use ntex::codec::{AsyncRead, AsyncWrite, ReadBuf};
+use ntex::connect::{self, Address, Connect};
+use ntex::service::{Service, ServiceFactory};
+use ntex::task::LocalWaker;
+use ntex::util::BytesMut;
+
+use crate::client::{Client, ConnectError, Connector};
+use crate::codec::protocol::{Flow, Frame};
+use crate::codec::{AmqpFrame, Decode};
+use crate::default::DefaultControlService;
+use crate::server::{Handshake, HandshakeAck, Server};
+
+/// Protocol header length
+const PROTOCOL_HEADER_LEN: usize = 8;
+
+#[derive(Default)]
+struct Pipe {
+    buf: BytesMut,
+    closed: bool,
+    waker: LocalWaker,
+}
+
+/// In-memory duplex stream
+///
+/// Data written to one side of the pair is readable from the other side.
+/// Dropping or shutting down any side closes both directions.
+pub struct Io {
+    read: Rc<RefCell<Pipe>>,
+    write: Rc<RefCell<Pipe>>,
+    log: Option<FrameLog>,
+}
+
+impl Io {
+    /// Create pair of connected streams
+    pub fn pair() -> (Io, Io) {
+        let p1 = Rc::new(RefCell::new(Pipe::default()));
+        let p2 = Rc::new(RefCell::new(Pipe::default()));
+
+        (
+            Io {
+                read: p1.clone(),
+                write: p2.clone(),
+                log: None,
+            },
+            Io {
+                read: p2,
+                write: p1,
+                log: None,
+            },
+        )
+    }
+
+    /// Record amqp frames written to this side of the stream
+    pub fn record(&mut self) -> FrameLog {
+        self.log.get_or_insert_with(FrameLog::default).clone()
+    }
+
+    /// Check if stream is closed
+    pub fn is_closed(&self) -> bool {
+        self.read.borrow().closed
+    }
+
+    fn close(&self) {
+        for pipe in &[&self.read, &self.write] {
+            let mut pipe = pipe.borrow_mut();
+            pipe.closed = true;
+            pipe.waker.wake();
+        }
+    }
+}
+
+impl Drop for Io {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl fmt::Debug for Io {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Io")
+            .field("closed", &self.is_closed())
+            .finish()
+    }
+}
+
+impl AsyncRead for Io {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let mut pipe = self.read.borrow_mut();
+
+        if !pipe.buf.is_empty() {
+            let size = cmp::min(pipe.buf.len(), buf.remaining());
+            let data = pipe.buf.split_to(size);
+            buf.put_slice(&data);
+            Poll::Ready(Ok(()))
+        } else if pipe.closed {
+            // eof
+            Poll::Ready(Ok(()))
+        } else {
+            pipe.waker.register(cx.waker());
+            Poll::Pending
+        }
+    }
+}
+
+impl AsyncWrite for Io {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut pipe = self.write.borrow_mut();
+
+        if pipe.closed {
+            Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "In-memory stream is closed",
+            )))
+        } else {
+            pipe.buf.extend_from_slice(buf);
+            pipe.waker.wake();
+            if let Some(ref log) = self.log {
+                log.write(buf);
+            }
+            Poll::Ready(Ok(buf.len()))
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.close();
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[derive(Default)]
+struct FrameLogInner {
+    buf: BytesMut,
+    frames: Vec<AmqpFrame>,
+}
+
+/// Log of amqp frames written to in-memory stream
+///
+/// Protocol headers and sasl frames are skipped.
+#[derive(Clone, Default)]
+pub struct FrameLog(Rc<RefCell<FrameLogInner>>);
+
+impl FrameLog {
+    /// Recorded frames
+    pub fn frames(&self) -> Vec<AmqpFrame> {
+        self.0.borrow().frames.clone()
+    }
+
+    /// Recorded `Flow` frames
+    pub fn flows(&self) -> Vec<Flow> {
+        self.0
+            .borrow()
+            .frames
+            .iter()
+            .filter_map(|frame| match frame.performative() {
+                Frame::Flow(flow) => Some(flow.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Recorded `Flow` frames of the link
+    pub fn link_flows(&self, handle: u32) -> Vec<Flow> {
+        self.flows()
+            .into_iter()
+            .filter(|flow| flow.handle == Some(handle))
+            .collect()
+    }
+
+    /// Remove recorded frames
+    pub fn clear(&self) {
+        self.0.borrow_mut().frames.clear();
+    }
+
+    fn write(&self, data: &[u8]) {
+        let mut inner = self.0.borrow_mut();
+        inner.buf.extend_from_slice(data);
+
+        loop {
+            let buf = &inner.buf;
+            if buf.len() >= 4 && &buf[..4] == b"AMQP" {
+                if buf.len() < PROTOCOL_HEADER_LEN {
+                    return;
+                }
+                let _ = inner.buf.split_to(PROTOCOL_HEADER_LEN);
+                continue;
+            }
+            if buf.len() < PROTOCOL_HEADER_LEN {
+                return;
+            }
+
+            let size = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+            if size < PROTOCOL_HEADER_LEN {
+                log::error!("Cannot record frame with invalid size: {}", size);
+                inner.buf.clear();
+                return;
+            }
+            if buf.len() < size {
+                return;
+            }
+
+            let data = inner.buf.split_to(size);
+            // amqp frame type, sasl frames are skipped
+            if data[5] == 0 {
+                if let Ok((_, frame)) = AmqpFrame::decode(&data[4..]) {
+                    inner.frames.push(frame);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for FrameLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrameLog")
+            .field("frames", &self.0.borrow().frames)
+            .finish()
+    }
+}
+
+/// Frames recorded on both sides of in-memory connection
+#[derive(Clone, Debug)]
+pub struct Recorder {
+    /// Frames sent by client
+    pub sent: FrameLog,
+    /// Frames received by client
+    pub received: FrameLog,
+}
+
+/// Server that opens amqp connections with unit state
+///
+/// Handshake service accepts `Handshake::Amqp` and rejects `Handshake::Sasl`.
+/// Server could be configured further, `Server::finish()` creates
+/// service factory for `connect()`.
+pub fn plain_server<Io>() -> Server<
+    Io,
+    (),
+    impl ServiceFactory<
+            Config = (),
+            Request = Handshake<Io>,
+            Response = HandshakeAck<Io, ()>,
+            Error = (),
+            InitError = (),
+        > + 'static,
+    DefaultControlService<(), ()>,
+>
+where
+    Io: AsyncRead + AsyncWrite + Unpin + 'static,
+{
+    Server::new(|conn: Handshake<Io>| async move {
+        match conn {
+            Handshake::Amqp(conn) => {
+                let conn = conn.open().await.map_err(|_| ())?;
+                Ok(conn.ack(()))
+            }
+            Handshake::Sasl(_) => Err(()),
+        }
+    })
+}
+
+/// Connect client to the server over in-memory transport
+///
+/// `server` is a service factory created by `Server::finish()`,
+/// server side of the connection gets spawned on current runtime.
+pub async fn connect<F>(server: F) -> Result<Client<Io>, ConnectError>
+where
+    F: ServiceFactory<Config = (), Request = Io, Response = ()>,
+    F::Service: 'static,
+    F::Error: fmt::Debug,
+    F::InitError: fmt::Debug,
+{
+    connect_with(server, &Connector::<&'static str, ()>::new()).await
+}
+
+/// Connect client to the server over in-memory transport with custom connector settings
+pub async fn connect_with<F, A, T>(
+    server: F,
+    connector: &Connector<A, T>,
+) -> Result<Client<Io>, ConnectError>
+where
+    F: ServiceFactory<Config = (), Request = Io, Response = ()>,
+    F::Service: 'static,
+    F::Error: fmt::Debug,
+    F::InitError: fmt::Debug,
+    A: Address,
+    T: Service<Request = Connect<A>, Error = connect::ConnectError>,
+    T::Response: AsyncRead + AsyncWrite + Unpin + 'static,
+{
+    let (client, server_io) = Io::pair();
+    spawn_server(server, server_io).await?;
+    connector.negotiate(client).await
+}
+
+/// Connect client to the server over in-memory transport and record frames
+///
+/// Recorded frames could be used for checking flow control state
+/// of the connection, i.e. sequence of `Flow` frames sent by the server.
+pub async fn connect_recorded<F, A, T>(
+    server: F,
+    connector: &Connector<A, T>,
+) -> Result<(Client<Io>, Recorder), ConnectError>
+where
+    F: ServiceFactory<Config = (), Request = Io, Response = ()>,
+    F::Service: 'static,
+    F::Error: fmt::Debug,
+    F::InitError: fmt::Debug,
+    A: Address,
+    T: Service<Request = Connect<A>, Error = connect::ConnectError>,
+    T::Response: AsyncRead + AsyncWrite + Unpin + 'static,
+{
+    let (mut client, mut server_io) = Io::pair();
+    let recorder = Recorder {
+        sent: client.record(),
+        received: server_io.record(),
+    };
+    spawn_server(server, server_io).await?;
+    let client = connector.negotiate(client).await?;
+    Ok((client, recorder))
+}
+
+async fn spawn_server<F>(server: F, io: Io) -> Result<(), ConnectError>
+where
+    F: ServiceFactory<Config = (), Request = Io, Response = ()>,
+    F::Service: 'static,
+    F::Error: fmt::Debug,
+    F::InitError: fmt::Debug,
+{
+    let srv = server.new_service(()).await.map_err(|e| {
+        log::error!("In-memory server init error: {:?}", e);
+        ConnectError::Disconnected
+    })?;
+    ntex::rt::spawn(async move {
+        if let Err(e) = srv.call(io).await {
+            log::trace!("In-memory server connection error: {:?}", e);
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,196 @@
+use std::{cell::RefCell, future::Future};
+
+use ntex::util::{Bytes, BytesMut};
+use ntex_amqp_codec::protocol::{
+    self, Declare, DeliveryNumber, DeliveryState, Discharge, Disposition, Frame, Role,
+    TransactionalState, TransferBody, TxnId,
+};
+use ntex_amqp_codec::{types::Descriptor, Encode};
+
+use crate::error::AmqpProtocolError;
+use crate::{ReceiverLink, SenderLink};
+
+/// Sender link attached to the transaction coordinator
+#[derive(Clone, Debug)]
+pub struct Coordinator {
+    link: SenderLink,
+}
+
+impl Coordinator {
+    pub(crate) fn new(link: SenderLink) -> Coordinator {
+        Coordinator { link }
+    }
+
+    /// Coordinator link
+    pub fn link(&self) -> &SenderLink {
+        &self.link
+    }
+
+    /// Declare new transaction
+    pub async fn declare(&self) -> Result<Transaction, AmqpProtocolError> {
+        let disp = self
+            .link
+            .send(amqp_value(&Declare { global_id: None }))
+            .await?;
+
+        match disp.state {
+            Some(DeliveryState::Declared(ref declared)) => Ok(Transaction {
+                id: declared.txn_id.clone(),
+                link: self.link.clone(),
+                acquired: RefCell::new(Vec::new()),
+            }),
+            Some(DeliveryState::Rejected(ref rejected)) => {
+                Err(AmqpProtocolError::Transaction(rejected.error.clone()))
+            }
+            _ => Err(AmqpProtocolError::Unexpected(Box::new(Frame::Disposition(
+                disp,
+            )))),
+        }
+    }
+
+    /// Close coordinator link
+    ///
+    /// Transactions that are not discharged get rolled back by remote peer.
+    pub fn close(&self) -> impl Future<Output = Result<(), AmqpProtocolError>> {
+        self.link.close()
+    }
+}
+
+/// Declared transaction
+///
+/// Dropping transaction without discharge leaves it to the remote peer,
+/// it gets rolled back when coordinator link closes.
+#[derive(Debug)]
+pub struct Transaction {
+    id: TxnId,
+    link: SenderLink,
+    /// Deliveries acquired by the transaction, settled on commit
+    acquired: RefCell<Vec<(ReceiverLink, DeliveryNumber, protocol::Outcome)>>,
+}
+
+impl Transaction {
+    /// Transaction id assigned by coordinator
+    pub fn id(&self) -> &TxnId {
+        &self.id
+    }
+
+    /// Transactional delivery state for this transaction
+    pub fn state(&self, outcome: Option<protocol::Outcome>) -> DeliveryState {
+        DeliveryState::TransactionalState(TransactionalState {
+            txn_id: self.id.clone(),
+            outcome,
+        })
+    }
+
+    /// Send message over sender link as part of this transaction
+    pub fn send<T>(
+        &self,
+        link: &SenderLink,
+        body: T,
+    ) -> impl Future<Output = Result<Disposition, AmqpProtocolError>>
+    where
+        T: Into<TransferBody>,
+    {
+        link.inner
+            .get_mut()
+            .send(body, None, Some(self.state(None)), None)
+    }
+
+    /// Settle received message with outcome as part of this transaction
+    pub fn settle_message(
+        &self,
+        link: &ReceiverLink,
+        id: DeliveryNumber,
+        outcome: protocol::Outcome,
+    ) {
+        link.send_disposition(Disposition {
+            role: Role::Receiver,
+            first: id,
+            last: None,
+            settled: true,
+            state: Some(self.state(Some(outcome))),
+            batchable: false,
+        });
+    }
+
+    /// Acquire received message with outcome as part of this transaction
+    ///
+    /// Disposition with transactional state is sent unsettled, delivery
+    /// gets settled with plain outcome once transaction is committed.
+    /// Delivery stays unsettled if transaction is rolled back.
+    pub fn acquire(&self, link: &ReceiverLink, id: DeliveryNumber, outcome: protocol::Outcome) {
+        link.send_disposition(Disposition {
+            role: Role::Receiver,
+            first: id,
+            last: None,
+            settled: false,
+            state: Some(self.state(Some(outcome.clone()))),
+            batchable: false,
+        });
+        self.acquired.borrow_mut().push((link.clone(), id, outcome));
+    }
+
+    /// Commit transaction
+    pub async fn commit(self) -> Result<(), AmqpProtocolError> {
+        self.discharge(false).await
+    }
+
+    /// Rollback transaction
+    pub async fn rollback(self) -> Result<(), AmqpProtocolError> {
+        self.discharge(true).await
+    }
+
+    /// Discharge transaction, `fail` flag rolls back transaction
+    pub async fn discharge(self, fail: bool) -> Result<(), AmqpProtocolError> {
+        let disp = self
+            .link
+            .send(amqp_value(&Discharge {
+                txn_id: self.id.clone(),
+                fail: Some(fail),
+            }))
+            .await?;
+
+        match disp.state {
+            Some(DeliveryState::Accepted(_)) => {
+                if !fail {
+                    for (link, id, outcome) in self.acquired.borrow_mut().drain(..) {
+                        link.send_disposition(Disposition {
+                            role: Role::Receiver,
+                            first: id,
+                            last: None,
+                            settled: true,
+                            state: Some(outcome_state(outcome)),
+                            batchable: false,
+                        });
+                    }
+                }
+                Ok(())
+            }
+            Some(DeliveryState::Rejected(ref rejected)) => {
+                Err(AmqpProtocolError::Transaction(rejected.error.clone()))
+            }
+            _ => Err(AmqpProtocolError::Unexpected(Box::new(Frame::Disposition(
+                disp,
+            )))),
+        }
+    }
+}
+
+/// Delivery state of settled outcome, transaction is already discharged
+fn outcome_state(outcome: protocol::Outcome) -> DeliveryState {
+    match outcome {
+        protocol::Outcome::Accepted(st) => DeliveryState::Accepted(st),
+        protocol::Outcome::Rejected(st) => DeliveryState::Rejected(st),
+        protocol::Outcome::Released(st) => DeliveryState::Released(st),
+        protocol::Outcome::Modified(st) => DeliveryState::Modified(st),
+        protocol::Outcome::Declared(st) => DeliveryState::Declared(st),
+    }
+}
+
+/// Encode control message as amqp-value body section
+fn amqp_value<T: Encode>(value: &T) -> Bytes {
+    let mut buf = BytesMut::with_capacity(value.encoded_size() + 3);
+    Descriptor::Ulong(119).encode(&mut buf);
+    value.encode(&mut buf);
+    buf.freeze()
+}
@@ -1,12 +1,16 @@
-use std::fmt;
+use std::{convert::TryFrom, fmt, time::SystemTime, time::UNIX_EPOCH};
 
 use ntex::router::Path;
 use ntex::util::{ByteString, Bytes};
 
 use crate::codec::protocol::{
-    self, Accepted, Attach, DeliveryState, Error, Rejected, TransferBody,
+    self, Accepted, Attach, DeliveryState, Error, Fields, Modified, Rejected, Released, Seconds,
+    Source, Target, TargetTerminus, TerminusDurability, TerminusExpiryPolicy, TransferBody,
 };
-use crate::codec::{AmqpParseError, Decode};
+use crate::codec::types::{Variant, VecSymbolMap};
+use crate::codec::{AmqpParseError, Decode, Message};
+use crate::error::{AmqpProtocolError, BodyError};
+use crate::server::Identity;
 use crate::{rcvlink::ReceiverLink, session::Session, Handle, State};
 
 pub struct Link<S> {
@@ -36,6 +40,45 @@ impl<S> Link<S> {
         self.link.frame()
     }
 
+    /// Target address requested by remote peer
+    pub fn address(&self) -> Option<&ByteString> {
+        self.link.frame().target().and_then(|t| t.address())
+    }
+
+    /// Source terminus of the attach frame
+    pub fn source(&self) -> Option<&Source> {
+        self.link.frame().source()
+    }
+
+    /// Target terminus of the attach frame
+    ///
+    /// Returns `None` for transaction coordinator target
+    pub fn target(&self) -> Option<&Target> {
+        match self.link.frame().target() {
+            Some(TargetTerminus::Target(ref target)) => Some(target),
+            _ => None,
+        }
+    }
+
+    /// Durability of target terminus requested by remote peer
+    pub fn durable(&self) -> TerminusDurability {
+        self.target()
+            .map(|t| t.durable)
+            .unwrap_or(TerminusDurability::None)
+    }
+
+    /// Expiry policy of target terminus requested by remote peer
+    pub fn expiry_policy(&self) -> TerminusExpiryPolicy {
+        self.target()
+            .map(|t| t.expiry_policy)
+            .unwrap_or(TerminusExpiryPolicy::SessionEnd)
+    }
+
+    /// Expiry timeout in seconds of target terminus requested by remote peer
+    pub fn expiry_timeout(&self) -> Seconds {
+        self.target().map(|t| t.timeout).unwrap_or(0)
+    }
+
     pub fn state(&self) -> &S {
         self.state.get_ref()
     }
@@ -52,6 +95,14 @@ impl<S> Link<S> {
         self.link.session_mut()
     }
 
+    /// Identity of the client authenticated during connection handshake
+    ///
+    /// Returns `None` for anonymous connection, attach could be rejected
+    /// with `amqp:unauthorized-access` error.
+    pub fn identity(&self) -> Option<&Identity> {
+        self.link.session().connection().identity()
+    }
+
     pub fn receiver(&self) -> &ReceiverLink {
         &self.link
     }
@@ -94,14 +145,92 @@ pub enum Outcome {
     Accept,
     Reject,
     Error(Error),
+    /// Message is modified, message annotations are merged into the message by the sender
+    Modified(Modified),
+    /// Message is released, sender could redeliver it
+    Release,
 }
 
 impl Outcome {
+    /// Reject message with error and info fields
+    ///
+    /// Info fields are sent with `rejected` outcome and could carry
+    /// machine-readable details of the rejection.
+    #[allow(clippy::mutable_key_type)]
+    pub fn reject_with_info<E>(error: E, info: Fields) -> Outcome
+    where
+        Error: From<E>,
+    {
+        let mut error = Error::from(error);
+        error.info = Some(info);
+        Outcome::Error(error)
+    }
+
     pub(crate) fn into_delivery_state(self) -> DeliveryState {
         match self {
             Outcome::Accept => DeliveryState::Accepted(Accepted {}),
             Outcome::Reject => DeliveryState::Rejected(Rejected { error: None }),
             Outcome::Error(e) => DeliveryState::Rejected(Rejected { error: Some(e) }),
+            Outcome::Modified(m) => DeliveryState::Modified(m),
+            Outcome::Release => DeliveryState::Released(Released {}),
+        }
+    }
+}
+
+/// Outcome of delivery as settled by remote peer
+#[derive(Debug)]
+pub enum DeliveryResult {
+    Accepted,
+    /// Message is rejected, peer could provide error condition
+    Rejected(Option<Error>),
+    /// Message is released, it is safe to redeliver it
+    Released,
+    /// Message is modified by remote peer
+    Modified {
+        delivery_failed: bool,
+        undeliverable_here: bool,
+        message_annotations: Option<Fields>,
+    },
+    /// Non-terminal or transactional delivery state
+    Other(DeliveryState),
+}
+
+impl DeliveryResult {
+    /// Check if message could be sent again
+    ///
+    /// Released messages could be redelivered, modified messages
+    /// could be redelivered unless peer marked them undeliverable.
+    pub fn is_redeliverable(&self) -> bool {
+        match self {
+            DeliveryResult::Released => true,
+            DeliveryResult::Modified {
+                undeliverable_here, ..
+            } => !undeliverable_here,
+            _ => false,
+        }
+    }
+
+    /// Report rejected delivery as `AmqpProtocolError::Rejected` error
+    pub fn into_result(self) -> Result<DeliveryResult, AmqpProtocolError> {
+        match self {
+            DeliveryResult::Rejected(err) => Err(AmqpProtocolError::Rejected(err)),
+            res => Ok(res),
+        }
+    }
+}
+
+impl From<DeliveryState> for DeliveryResult {
+    fn from(state: DeliveryState) -> Self {
+        match state {
+            DeliveryState::Accepted(_) => DeliveryResult::Accepted,
+            DeliveryState::Rejected(rejected) => DeliveryResult::Rejected(rejected.error),
+            DeliveryState::Released(_) => DeliveryResult::Released,
+            DeliveryState::Modified(modified) => DeliveryResult::Modified {
+                delivery_failed: modified.delivery_failed.unwrap_or(false),
+                undeliverable_here: modified.undeliverable_here.unwrap_or(false),
+                message_annotations: modified.message_annotations,
+            },
+            state => DeliveryResult::Other(state),
         }
     }
 }
@@ -127,6 +256,11 @@ impl<S> Transfer<S> {
         &self.frame
     }
 
+    /// Message format of the delivery, `0` if not specified
+    pub fn message_format(&self) -> u32 {
+        self.frame.message_format.unwrap_or(0)
+    }
+
     pub fn body(&self) -> Option<&Bytes> {
         match self.frame.body {
             Some(TransferBody::Data(ref b)) => Some(b),
@@ -134,6 +268,16 @@ impl<S> Transfer<S> {
         }
     }
 
+    /// Take transfer body without copying
+    ///
+    /// Body of multi-frame transfer is already reassembled into single buffer.
+    pub fn into_body(self) -> Option<Bytes> {
+        match self.frame.body {
+            Some(TransferBody::Data(b)) => Some(b),
+            _ => None,
+        }
+    }
+
     pub fn load_message<T: Decode>(&self) -> Result<T, AmqpParseError> {
         if let Some(TransferBody::Data(ref b)) = self.frame.body {
             Ok(T::decode(b)?.1)
@@ -141,6 +285,158 @@ impl<S> Transfer<S> {
             Err(AmqpParseError::UnexpectedType("body"))
         }
     }
+
+    /// Delivery annotations of the transferred message.
+    ///
+    /// Delivery annotations are scoped to the delivery, message annotations
+    /// are available via `load_message::<Message>()`.
+    pub fn delivery_annotations(&self) -> Result<Option<VecSymbolMap>, AmqpParseError> {
+        match self.frame.body {
+            Some(TransferBody::Data(ref b)) => Ok(Message::decode(b)?.1.delivery_annotations),
+            Some(TransferBody::Message(ref msg)) => Ok(msg.delivery_annotations.clone()),
+            None => Err(AmqpParseError::UnexpectedType("body")),
+        }
+    }
+
+    /// Properties of the transferred message.
+    pub fn properties(&self) -> Result<Option<protocol::Properties>, AmqpParseError> {
+        match self.frame.body {
+            Some(TransferBody::Data(ref b)) => Ok(Message::decode(b)?.1.properties),
+            Some(TransferBody::Message(ref msg)) => Ok(msg.properties.clone()),
+            None => Err(AmqpParseError::UnexpectedType("body")),
+        }
+    }
+
+    /// `group-id` property, group the message belongs to.
+    pub fn group_id(&self) -> Result<Option<ByteString>, AmqpParseError> {
+        Ok(self.properties()?.and_then(|props| props.group_id))
+    }
+
+    /// `group-sequence` property, position of the message within its group.
+    pub fn group_sequence(&self) -> Result<Option<protocol::SequenceNo>, AmqpParseError> {
+        Ok(self.properties()?.and_then(|props| props.group_sequence))
+    }
+
+    /// `reply-to-group-id` property, group of reply messages.
+    pub fn reply_to_group_id(&self) -> Result<Option<ByteString>, AmqpParseError> {
+        Ok(self.properties()?.and_then(|props| props.reply_to_group_id))
+    }
+
+    /// Check if transferred message is expired.
+    ///
+    /// Expiry time is `absolute-expiry-time` property, or `creation-time`
+    /// property plus header `ttl`. Message without expiry time never expires.
+    pub fn is_expired(&self) -> bool {
+        is_expired(self.frame.body.as_ref())
+    }
+
+    /// Load message body as concatenated content of data sections.
+    ///
+    /// Large payloads can be split into several data sections of one message.
+    pub fn body_data(&self) -> Result<Bytes, BodyError> {
+        data_sections(self.message()?)
+    }
+
+    /// Load message body as string.
+    ///
+    /// Body is either amqp-value string or data sections with `text/*` content type.
+    /// Message is decoded from transfer payload and data sections are concatenated,
+    /// so string can not borrow from transfer. Returned `ByteString` shares
+    /// payload buffer if body is a single section.
+    pub fn body_as_str(&self) -> Result<ByteString, BodyError> {
+        let msg = self.message()?;
+        if let Some(Variant::String(ref s)) = msg.body.value {
+            return Ok(s.to_bytes_str());
+        }
+        check_content_type(&msg, "text/")?;
+        ByteString::try_from(data_sections(msg)?).map_err(|_| BodyError::Utf8)
+    }
+
+    /// Deserialize json message body.
+    ///
+    /// Body is data sections with `application/json` content type.
+    #[cfg(feature = "json")]
+    pub fn body_as_json<T: serde::de::DeserializeOwned>(&self) -> Result<T, BodyError> {
+        let msg = self.message()?;
+        check_content_type(&msg, "application/json")?;
+        Ok(serde_json::from_slice(&data_sections(msg)?)?)
+    }
+
+    #[cfg(feature = "compress")]
+    /// Load message body and decompress it.
+    ///
+    /// Body is concatenated data sections, decompressed according to
+    /// `content-encoding` property. Body without content encoding is
+    /// returned as is, unsupported encoding is an error.
+    pub fn decoded_body(&self) -> Result<Bytes, BodyError> {
+        let msg = self.message()?;
+        let encoding = msg
+            .properties()
+            .and_then(|props| props.content_encoding())
+            .map(|enc| enc.as_str().to_string());
+        let data = data_sections(msg)?;
+
+        if let Some(name) = encoding {
+            crate::ContentEncoding::from_name(&name)
+                .ok_or_else(|| BodyError::ContentEncoding(ByteString::from(name.as_str())))?
+                .decompress(&data)
+                .map_err(BodyError::Decompress)
+        } else {
+            Ok(data)
+        }
+    }
+
+    fn message(&self) -> Result<Message, BodyError> {
+        match self.frame.body {
+            Some(TransferBody::Data(ref b)) => Ok(Message::decode(b)?.1),
+            Some(TransferBody::Message(ref msg)) => Ok(msg.as_ref().clone()),
+            None => Err(AmqpParseError::UnexpectedType("body").into()),
+        }
+    }
+}
+
+pub(crate) fn is_expired(body: Option<&TransferBody>) -> bool {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0);
+
+    match body {
+        Some(TransferBody::Data(ref b)) => Message::decode(b)
+            .map(|(_, msg)| message_expired(&msg, now))
+            .unwrap_or(false),
+        Some(TransferBody::Message(ref msg)) => message_expired(msg, now),
+        None => false,
+    }
+}
+
+fn message_expired(msg: &Message, now: i64) -> bool {
+    let props = msg.properties();
+    let expiry = props
+        .and_then(|props| props.absolute_expiry_time)
+        .map(|t| t.timestamp_millis())
+        .or_else(|| {
+            let created = props.and_then(|props| props.creation_time)?;
+            let ttl = msg.header().and_then(|hdr| hdr.ttl)?;
+            Some(created.timestamp_millis() + ttl as i64)
+        });
+    expiry.map(|expiry| expiry <= now).unwrap_or(false)
+}
+
+/// Message without content type is accepted
+fn check_content_type(msg: &Message, expected: &str) -> Result<(), BodyError> {
+    match msg.properties().and_then(|props| props.content_type()) {
+        Some(ct) if !ct.as_str().starts_with(expected) => {
+            Err(BodyError::ContentType(ByteString::from(ct.as_str())))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn data_sections(msg: Message) -> Result<Bytes, BodyError> {
+    msg.body
+        .data_concat()
+        .ok_or_else(|| AmqpParseError::UnexpectedType("data").into())
 }
 
 impl<S> fmt::Debug for Transfer<S> {
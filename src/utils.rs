@@ -1,3 +1,7 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::task::{Context, Poll};
+use std::{any::Any, future::Future, pin::Pin};
+
 /// Unwrap result and return `err` future
 ///
 /// Err(e) get converted to err(e)
@@ -9,3 +13,105 @@ macro_rules! try_ready_err {
         }
     };
 }
+
+pin_project_lite::pin_project! {
+    /// Future that catches panics of inner future
+    pub(crate) struct CatchUnwind<F> {
+        #[pin]
+        fut: F,
+    }
+}
+
+impl<F: Future> Future for CatchUnwind<F> {
+    type Output = Result<F::Output, Box<dyn Any + Send>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let fut = self.project().fut;
+        match panic::catch_unwind(AssertUnwindSafe(|| fut.poll(cx))) {
+            Ok(Poll::Ready(res)) => Poll::Ready(Ok(res)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(e) => Poll::Ready(Err(e)),
+        }
+    }
+}
+
+/// Wrap future, panic during poll resolves future with error
+pub(crate) fn catch_unwind<F: Future>(fut: F) -> CatchUnwind<F> {
+    CatchUnwind { fut }
+}
+
+/// Serial number comparison (RFC 1982), check if `a` precedes `b`
+pub(crate) fn serial_lt(a: u32, b: u32) -> bool {
+    a != b && b.wrapping_sub(a) < (1 << 31)
+}
+
+/// Serial numbers from `first` to `last` inclusive, range may wrap around `u32::MAX`
+///
+/// If `last` precedes `first`, range contains `first` only.
+pub(crate) fn serial_range(first: u32, last: u32) -> impl Iterator<Item = u32> {
+    let len = if serial_lt(last, first) {
+        0
+    } else {
+        last.wrapping_sub(first)
+    };
+    (0..=len).map(move |idx| first.wrapping_add(idx))
+}
+
+/// Log sasl frame on trace level, credentials are redacted
+#[cfg(feature = "sasl-trace")]
+pub(crate) fn trace_sasl(dir: &str, body: &crate::codec::protocol::SaslFrameBody) {
+    use crate::codec::protocol::SaslFrameBody;
+
+    let len = |data: Option<&ntex::util::Bytes>| data.map(|d| d.len()).unwrap_or(0);
+
+    match body {
+        SaslFrameBody::SaslMechanisms(frame) => {
+            log::trace!("{} sasl-mechanisms: {:?}", dir, frame.mechanisms())
+        }
+        SaslFrameBody::SaslInit(frame) => log::trace!(
+            "{} sasl-init: mechanism: {:?}, hostname: {:?}, initial-response: <{} bytes>",
+            dir,
+            frame.mechanism,
+            frame.hostname,
+            len(frame.initial_response.as_ref())
+        ),
+        SaslFrameBody::SaslChallenge(frame) => {
+            log::trace!("{} sasl-challenge: <{} bytes>", dir, frame.challenge.len())
+        }
+        SaslFrameBody::SaslResponse(frame) => {
+            log::trace!("{} sasl-response: <{} bytes>", dir, frame.response.len())
+        }
+        SaslFrameBody::SaslOutcome(frame) => log::trace!(
+            "{} sasl-outcome: code: {:?}, additional-data: <{} bytes>",
+            dir,
+            frame.code,
+            len(frame.additional_data.as_ref())
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serial_lt() {
+        assert!(serial_lt(1, 2));
+        assert!(!serial_lt(2, 1));
+        assert!(!serial_lt(1, 1));
+        assert!(serial_lt(u32::MAX, 0));
+        assert!(serial_lt(u32::MAX - 1, 1));
+        assert!(!serial_lt(1, u32::MAX));
+    }
+
+    #[test]
+    fn test_serial_range() {
+        assert_eq!(serial_range(5, 5).collect::<Vec<_>>(), vec![5]);
+        assert_eq!(serial_range(1, 3).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(
+            serial_range(u32::MAX - 1, 1).collect::<Vec<_>>(),
+            vec![u32::MAX - 1, u32::MAX, 0, 1]
+        );
+        assert_eq!(serial_range(3, 1).collect::<Vec<_>>(), vec![3]);
+    }
+}
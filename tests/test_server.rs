@@ -2,8 +2,8 @@ use std::convert::TryFrom;
 
 use ntex::codec::{AsyncRead, AsyncWrite};
 use ntex::server::test_server;
-use ntex::service::{fn_factory_with_config, Service};
-use ntex::{http::Uri, util::Ready};
+use ntex::service::{fn_factory_with_config, fn_service, Service};
+use ntex::{http::Uri, util::Bytes, util::Ready};
 use ntex_amqp::{client, error::LinkError, server, types};
 
 async fn server(
@@ -23,6 +23,24 @@ async fn server(
     Err(LinkError::force_detach().description("unimplemented"))
 }
 
+async fn accept_link(
+    _link: types::Link<()>,
+) -> Result<
+    Box<
+        dyn Service<
+                Request = types::Transfer<()>,
+                Response = types::Outcome,
+                Error = LinkError,
+                Future = Ready<types::Outcome, LinkError>,
+            > + 'static,
+    >,
+    LinkError,
+> {
+    Ok(Box::new(fn_service(|_: types::Transfer<()>| {
+        Ready::Ok(types::Outcome::Accept)
+    })))
+}
+
 #[ntex::test]
 async fn test_simple() -> std::io::Result<()> {
     std::env::set_var("RUST_LOG", "ntex=trace,ntex_amqp=trace");
@@ -126,3 +144,3916 @@ async fn test_sasl() -> std::io::Result<()> {
 
     Ok(())
 }
+
+struct PlainAuth;
+
+impl server::SaslAuthenticator for PlainAuth {
+    type Future = Ready<server::Identity, ntex_amqp_codec::protocol::SaslCode>;
+
+    fn mechanisms(&self) -> Vec<ntex_amqp_codec::protocol::SaslMechanism> {
+        vec![ntex_amqp_codec::protocol::SaslMechanism::Plain]
+    }
+
+    fn authenticate(
+        &self,
+        _: ntex_amqp_codec::protocol::SaslMechanism,
+        init: &ntex_amqp_codec::protocol::SaslInit,
+    ) -> Self::Future {
+        match init.initial_response.as_ref() {
+            Some(resp) if resp.as_ref() == b"\0user1\0password1" => {
+                Ready::Ok(server::Identity::new("user1"))
+            }
+            _ => Ready::Err(ntex_amqp_codec::protocol::SaslCode::Auth),
+        }
+    }
+}
+
+#[ntex::test]
+async fn test_sasl_authenticator() -> std::io::Result<()> {
+    use ntex_amqp_codec::protocol::{ProtocolId, SaslMechanism};
+
+    let srv = test_server(|| {
+        server::Server::new(|conn: server::Handshake<_>| async move {
+            match conn {
+                server::Handshake::Amqp(conn) => {
+                    assert_eq!(conn.identity().unwrap().name(), "user1");
+                    assert_eq!(conn.sasl_mechanism(), Some(&SaslMechanism::Plain));
+                    let conn = conn.open().await.unwrap();
+                    assert_eq!(conn.sink().sasl_mechanism(), Some(&SaslMechanism::Plain));
+                    Ok(conn.ack(()))
+                }
+                server::Handshake::Sasl(_) => Err(()),
+            }
+        })
+        .sasl_authenticator(PlainAuth)
+        .finish(
+            server::Router::<()>::new()
+                .service("test", fn_factory_with_config(server))
+                .finish(),
+        )
+    });
+
+    let uri = Uri::try_from(format!("amqp://{}:{}", srv.addr().ip(), srv.addr().port())).unwrap();
+
+    let client = client::Connector::new()
+        .connect_sasl(
+            uri.clone(),
+            client::SaslAuth {
+                authz_id: "".into(),
+                authn_id: "user1".into(),
+                password: "password1".into(),
+            },
+        )
+        .await
+        .unwrap();
+    let sink = client.sink();
+    assert_eq!(sink.protocol(), ProtocolId::AmqpSasl);
+    assert_eq!(sink.sasl_mechanism(), Some(&SaslMechanism::Plain));
+
+    let client = client::Connector::new()
+        .connect_sasl(
+            uri.clone(),
+            client::SaslAuth {
+                authz_id: "".into(),
+                authn_id: "user1".into(),
+                password: "wrong".into(),
+            },
+        )
+        .await;
+    assert!(client.is_err());
+
+    // authenticator makes sasl mandatory, plain amqp is refused
+    let client = client::Connector::new().connect(uri).await;
+    assert!(client.is_err());
+
+    Ok(())
+}
+
+async fn authenticated_link(
+    link: types::Link<()>,
+) -> Result<
+    Box<
+        dyn Service<
+                Request = types::Transfer<()>,
+                Response = types::Outcome,
+                Error = LinkError,
+                Future = Ready<types::Outcome, LinkError>,
+            > + 'static,
+    >,
+    LinkError,
+> {
+    use ntex_amqp_codec::protocol::{AmqpError, ErrorCondition};
+
+    if link.identity().is_none() {
+        return Err(LinkError::new(ErrorCondition::AmqpError(
+            AmqpError::UnauthorizedAccess,
+        )));
+    }
+    accept_link(link).await
+}
+
+#[ntex::test]
+async fn test_anonymous_link() -> std::io::Result<()> {
+    use ntex_amqp::error::AmqpProtocolError;
+    use ntex_amqp_codec::protocol::{AmqpError, ErrorCondition};
+
+    let srv = test_server(|| {
+        ntex_amqp::testing::plain_server().finish(
+            server::Router::<()>::new()
+                .service("test", fn_factory_with_config(authenticated_link))
+                .finish(),
+        )
+    });
+    let uri = Uri::try_from(format!("amqp://{}:{}", srv.addr().ip(), srv.addr().port())).unwrap();
+
+    // plain amqp connection without authenticator, links are rejected
+    let client = client::Connector::new().connect(uri).await.unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+    let mut session = sink.open_session().await.unwrap();
+    match session.open_sender("test").await {
+        Err(AmqpProtocolError::LinkDetached(Some(err))) => assert_eq!(
+            err.condition,
+            ErrorCondition::AmqpError(AmqpError::UnauthorizedAccess)
+        ),
+        res => panic!("Unexpected result: {:?}", res),
+    }
+
+    let srv = test_server(|| {
+        ntex_amqp::testing::plain_server()
+            .sasl_authenticator(PlainAuth)
+            .finish(
+                server::Router::<()>::new()
+                    .service("test", fn_factory_with_config(authenticated_link))
+                    .finish(),
+            )
+    });
+    let uri = Uri::try_from(format!("amqp://{}:{}", srv.addr().ip(), srv.addr().port())).unwrap();
+
+    let client = client::Connector::new()
+        .connect_sasl(
+            uri,
+            client::SaslAuth {
+                authz_id: "".into(),
+                authn_id: "user1".into(),
+                password: "password1".into(),
+            },
+        )
+        .await
+        .unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+    let mut session = sink.open_session().await.unwrap();
+    let link = session.open_sender("test").await.unwrap();
+    link.send(Bytes::from_static(b"data")).await.unwrap();
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_disconnect_during_send() -> std::io::Result<()> {
+    let srv = test_server(|| {
+        ntex_amqp::testing::plain_server().finish(
+            server::Router::<()>::new()
+                .service("test", fn_factory_with_config(accept_link))
+                .finish(),
+        )
+    });
+
+    let uri = Uri::try_from(format!("amqp://{}:{}", srv.addr().ip(), srv.addr().port())).unwrap();
+
+    let client = client::Connector::new().connect(uri).await.unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    let mut session = sink.open_session().await.unwrap();
+    let link = session
+        .build_sender_link("test", "test")
+        .open()
+        .await
+        .unwrap();
+
+    let delivery = link.send(Bytes::from_static(b"test"));
+    sink.force_close();
+    assert!(delivery.await.is_err());
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_in_memory_transport() -> std::io::Result<()> {
+    let srv = ntex_amqp::testing::plain_server().finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(accept_link))
+            .finish(),
+    );
+
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    let mut session = sink.open_session().await.unwrap();
+    let link = session
+        .build_sender_link("test", "test")
+        .open()
+        .await
+        .unwrap();
+
+    let disp = link.send(Bytes::from_static(b"test")).await.unwrap();
+    assert!(matches!(
+        disp.state,
+        Some(ntex_amqp_codec::protocol::DeliveryState::Accepted(_))
+    ));
+
+    let state = link.deliver(Bytes::from_static(b"test")).await.unwrap();
+    assert!(matches!(state, types::DeliveryResult::Accepted));
+
+    let link = session.open_sender("test").await.unwrap();
+    assert_eq!(link.name(), "test-1");
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_heartbeat() -> std::io::Result<()> {
+    use ntex::service::ServiceFactory;
+    use ntex_amqp_codec::protocol::{Frame, ProtocolId};
+    use ntex_amqp_codec::{AmqpCodec, AmqpFrame, ProtocolIdCodec};
+
+    let srv = ntex_amqp::testing::plain_server()
+        .finish(
+            server::Router::<()>::new()
+                .service("test", fn_factory_with_config(accept_link))
+                .finish(),
+        )
+        .new_service(())
+        .await
+        .unwrap();
+
+    let (mut io, server_io) = ntex_amqp::testing::Io::pair();
+    ntex::rt::spawn(async move {
+        let _ = srv.call(server_io).await;
+    });
+
+    let state = ntex::framed::State::new();
+    state
+        .send(&mut io, &ProtocolIdCodec, ProtocolId::Amqp)
+        .await
+        .unwrap();
+    state.next(&mut io, &ProtocolIdCodec).await.unwrap();
+
+    // client requires frames within 2 seconds
+    let codec = AmqpCodec::<AmqpFrame>::new();
+    let mut config = ntex_amqp::Configuration::default();
+    config.idle_timeout(2);
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, config.to_open().into()))
+        .await
+        .unwrap();
+    let frame = state.next(&mut io, &codec).await.unwrap().unwrap();
+    assert!(matches!(frame.performative(), Frame::Open(_)));
+
+    let frame = ntex::rt::time::timeout(
+        std::time::Duration::from_secs(2),
+        state.next(&mut io, &codec),
+    )
+    .await
+    .expect("heartbeat is not sent")
+    .unwrap()
+    .unwrap();
+    assert!(matches!(frame.performative(), Frame::Empty));
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_duplicate_begin() -> std::io::Result<()> {
+    use ntex::service::ServiceFactory;
+    use ntex_amqp_codec::protocol::{Begin, ConnectionError, ErrorCondition, Frame, ProtocolId};
+    use ntex_amqp_codec::{AmqpCodec, AmqpFrame, ProtocolIdCodec};
+
+    let srv = ntex_amqp::testing::plain_server()
+        .finish(
+            server::Router::<()>::new()
+                .service("test", fn_factory_with_config(accept_link))
+                .finish(),
+        )
+        .new_service(())
+        .await
+        .unwrap();
+
+    let (mut io, server_io) = ntex_amqp::testing::Io::pair();
+    ntex::rt::spawn(async move {
+        let _ = srv.call(server_io).await;
+    });
+
+    let state = ntex::framed::State::new();
+    state
+        .send(&mut io, &ProtocolIdCodec, ProtocolId::Amqp)
+        .await
+        .unwrap();
+    state.next(&mut io, &ProtocolIdCodec).await.unwrap();
+
+    let codec = AmqpCodec::<AmqpFrame>::new();
+    let open = ntex_amqp::Configuration::default().to_open();
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, open.into()))
+        .await
+        .unwrap();
+    state.next(&mut io, &codec).await.unwrap();
+
+    let begin = Begin {
+        remote_channel: None,
+        next_outgoing_id: 1,
+        incoming_window: std::u32::MAX,
+        outgoing_window: std::u32::MAX,
+        handle_max: std::u32::MAX,
+        offered_capabilities: None,
+        desired_capabilities: None,
+        properties: None,
+    };
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, begin.clone().into()))
+        .await
+        .unwrap();
+    let frame = state.next(&mut io, &codec).await.unwrap().unwrap();
+    assert!(matches!(frame.performative(), Frame::Begin(_)));
+
+    // rogue begin on established channel
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, begin.into()))
+        .await
+        .unwrap();
+    let frame = state.next(&mut io, &codec).await.unwrap().unwrap();
+    match frame.performative() {
+        Frame::Close(close) => assert_eq!(
+            close.error.as_ref().unwrap().condition,
+            ErrorCondition::ConnectionError(ConnectionError::FramingError)
+        ),
+        frame => panic!("Unexpected frame: {:?}", frame),
+    }
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_large_transfer() -> std::io::Result<()> {
+    const SIZE: usize = 1024 * 1024;
+
+    async fn large_link(
+        link: types::Link<()>,
+    ) -> Result<
+        Box<
+            dyn Service<
+                    Request = types::Transfer<()>,
+                    Response = types::Outcome,
+                    Error = LinkError,
+                    Future = Ready<types::Outcome, LinkError>,
+                > + 'static,
+        >,
+        LinkError,
+    > {
+        link.receiver().set_max_message_size(0);
+        link.receiver().set_max_partial_transfer_size(2 * SIZE);
+
+        Ok(Box::new(fn_service(|tr: types::Transfer<()>| {
+            Ready::Ok(if tr.body().map(|b| b.len()) == Some(SIZE) {
+                types::Outcome::Accept
+            } else {
+                types::Outcome::Reject
+            })
+        })))
+    }
+
+    let mut config = ntex_amqp::Configuration::default();
+    config.max_frame_size(16 * 1024);
+
+    let srv = ntex_amqp::testing::plain_server().config(config).finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(large_link))
+            .finish(),
+    );
+
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    let mut session = sink.open_session().await.unwrap();
+    let link = session.open_sender("test").await.unwrap();
+
+    let body = Bytes::from(vec![b'x'; SIZE]);
+    let state = link.deliver(body).await.unwrap();
+    assert!(matches!(state, types::DeliveryResult::Accepted));
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_publish_error() -> std::io::Result<()> {
+    use ntex_amqp::error::PublishError;
+
+    async fn publish_link(
+        _link: types::Link<()>,
+    ) -> Result<
+        Box<
+            dyn Service<
+                    Request = types::Transfer<()>,
+                    Response = types::Outcome,
+                    Error = PublishError,
+                    Future = Ready<types::Outcome, PublishError>,
+                > + 'static,
+        >,
+        LinkError,
+    > {
+        Ok(Box::new(fn_service(|tr: types::Transfer<()>| {
+            match tr.body().map(|b| b.as_ref()) {
+                Some(b"release") => Ready::Err(PublishError::Release),
+                Some(b"fatal") => Ready::Err(PublishError::Detach(
+                    LinkError::force_detach().text("fatal").into(),
+                )),
+                _ => Ready::Ok(types::Outcome::Accept),
+            }
+        })))
+    }
+
+    let srv = ntex_amqp::testing::plain_server().finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(publish_link))
+            .finish(),
+    );
+
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    let mut session = sink.open_session().await.unwrap();
+    let link = session.open_sender("test").await.unwrap();
+
+    // transient failure, link stays open
+    let state = link.deliver(Bytes::from_static(b"release")).await.unwrap();
+    assert!(matches!(state, types::DeliveryResult::Released));
+    let state = link.deliver(Bytes::from_static(b"data")).await.unwrap();
+    assert!(matches!(state, types::DeliveryResult::Accepted));
+
+    // fatal failure, link is detached
+    let on_close = link.on_close();
+    let _ = link.deliver(Bytes::from_static(b"fatal"));
+    on_close.await;
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_transfer_into_body() -> std::io::Result<()> {
+    const SIZE: usize = 8 * 1024;
+
+    async fn publish_link(
+        _link: types::Link<()>,
+    ) -> Result<
+        Box<
+            dyn Service<
+                    Request = types::Transfer<()>,
+                    Response = types::Outcome,
+                    Error = LinkError,
+                    Future = Ready<types::Outcome, LinkError>,
+                > + 'static,
+        >,
+        LinkError,
+    > {
+        Ok(Box::new(fn_service(|tr: types::Transfer<()>| {
+            let body = tr.into_body().unwrap();
+            if body.len() == SIZE && body.iter().all(|b| *b == b'x') {
+                Ready::Ok(types::Outcome::Accept)
+            } else {
+                Ready::Ok(types::Outcome::Reject)
+            }
+        })))
+    }
+
+    let srv = ntex_amqp::testing::plain_server().finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(publish_link))
+            .finish(),
+    );
+
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    let mut session = sink.open_session().await.unwrap();
+    let link = session.open_sender("test").await.unwrap();
+
+    // single frame transfer
+    let res = link.deliver(Bytes::from(vec![b'x'; SIZE])).await.unwrap();
+    assert!(matches!(res, types::DeliveryResult::Accepted));
+
+    // reassembled multi-frame transfer
+    link.set_max_chunk_size(1024);
+    let res = link.deliver(Bytes::from(vec![b'x'; SIZE])).await.unwrap();
+    assert!(matches!(res, types::DeliveryResult::Accepted));
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_data_sections() -> std::io::Result<()> {
+    use ntex_amqp::codec::Message;
+
+    async fn publish_link(
+        _link: types::Link<()>,
+    ) -> Result<
+        Box<
+            dyn Service<
+                    Request = types::Transfer<()>,
+                    Response = types::Outcome,
+                    Error = LinkError,
+                    Future = Ready<types::Outcome, LinkError>,
+                > + 'static,
+        >,
+        LinkError,
+    > {
+        Ok(Box::new(fn_service(|tr: types::Transfer<()>| {
+            match tr.body_data() {
+                Ok(data) if data == Bytes::from_static(b"first second third") => {
+                    Ready::Ok(types::Outcome::Accept)
+                }
+                _ => Ready::Ok(types::Outcome::Reject),
+            }
+        })))
+    }
+
+    let srv = ntex_amqp::testing::plain_server().finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(publish_link))
+            .finish(),
+    );
+
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    let mut session = sink.open_session().await.unwrap();
+    let link = session.open_sender("test").await.unwrap();
+
+    let mut msg = Message::with_body(Bytes::from_static(b"first "));
+    msg.add_data(Bytes::from_static(b"second "))
+        .add_data(Bytes::from_static(b"third"));
+    let res = link.deliver(msg).await.unwrap();
+    assert!(matches!(res, types::DeliveryResult::Accepted));
+
+    Ok(())
+}
+
+#[cfg(all(feature = "testing", feature = "compress"))]
+#[ntex::test]
+async fn test_content_encoding() -> std::io::Result<()> {
+    use ntex_amqp::codec::{types::Symbol, Message};
+    use ntex_amqp::error::BodyError;
+    use ntex_amqp::ContentEncoding;
+
+    async fn publish_link(
+        _link: types::Link<()>,
+    ) -> Result<
+        Box<
+            dyn Service<
+                    Request = types::Transfer<()>,
+                    Response = types::Outcome,
+                    Error = LinkError,
+                    Future = Ready<types::Outcome, LinkError>,
+                > + 'static,
+        >,
+        LinkError,
+    > {
+        Ok(Box::new(fn_service(|tr: types::Transfer<()>| {
+            match tr.decoded_body() {
+                Ok(data) if data == Bytes::from(vec![b'x'; 4096]) => {
+                    Ready::Ok(types::Outcome::Accept)
+                }
+                Err(BodyError::ContentEncoding(_)) => Ready::Ok(types::Outcome::Release),
+                _ => Ready::Ok(types::Outcome::Reject),
+            }
+        })))
+    }
+
+    let srv = ntex_amqp::testing::plain_server().finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(publish_link))
+            .finish(),
+    );
+
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    let mut session = sink.open_session().await.unwrap();
+    let link = session.open_sender("test").await.unwrap();
+
+    // plain body
+    let msg = Message::with_body(Bytes::from(vec![b'x'; 4096]));
+    let res = link.deliver(msg).await.unwrap();
+    assert!(matches!(res, types::DeliveryResult::Accepted));
+
+    // compressed bodies
+    for enc in &[ContentEncoding::Gzip, ContentEncoding::Deflate] {
+        link.set_content_encoding(Some(*enc));
+        let msg = Message::with_body(Bytes::from(vec![b'x'; 4096]));
+        let res = link.deliver(msg).await.unwrap();
+        assert!(matches!(res, types::DeliveryResult::Accepted));
+    }
+
+    // unknown encoding
+    link.set_content_encoding(None);
+    let mut msg = Message::with_body(Bytes::from(vec![b'x'; 4096]));
+    msg.set_properties(|props| props.content_encoding = Some(Symbol::from_static("br")));
+    let res = link.deliver(msg).await.unwrap();
+    assert!(matches!(res, types::DeliveryResult::Released));
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_publish_panic() -> std::io::Result<()> {
+    use ntex_amqp::codec::protocol::{AmqpError, ErrorCondition};
+    use ntex_amqp::error::AmqpProtocolError;
+
+    async fn publish_link(
+        _link: types::Link<()>,
+    ) -> Result<
+        Box<
+            dyn Service<
+                    Request = types::Transfer<()>,
+                    Response = types::Outcome,
+                    Error = LinkError,
+                    Future = Ready<types::Outcome, LinkError>,
+                > + 'static,
+        >,
+        LinkError,
+    > {
+        Ok(Box::new(fn_service(|tr: types::Transfer<()>| {
+            if tr.body().map(|b| b.as_ref()) == Some(b"panic") {
+                panic!("publish service failure");
+            }
+            Ready::Ok(types::Outcome::Accept)
+        })))
+    }
+
+    let srv = ntex_amqp::testing::plain_server().finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(publish_link))
+            .finish(),
+    );
+
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    let mut session = sink.open_session().await.unwrap();
+    let link1 = session.open_sender("test").await.unwrap();
+    let link2 = session.open_sender("test").await.unwrap();
+
+    // panic detaches failed link only
+    let on_close = link1.on_close();
+    let _ = link1.send(Bytes::from_static(b"panic"));
+    on_close.await;
+
+    match link1.send(Bytes::from_static(b"data")).await {
+        Err(AmqpProtocolError::LinkDetached(Some(err))) => assert_eq!(
+            err.condition,
+            ErrorCondition::AmqpError(AmqpError::InternalError)
+        ),
+        res => panic!("Unexpected result: {:?}", res),
+    }
+
+    let res = link2.deliver(Bytes::from_static(b"data")).await.unwrap();
+    assert!(matches!(res, types::DeliveryResult::Accepted));
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_delivery_result() -> std::io::Result<()> {
+    use ntex_amqp::codec::protocol::Modified;
+    use ntex_amqp::error::AmqpError;
+
+    async fn publish_link(
+        _link: types::Link<()>,
+    ) -> Result<
+        Box<
+            dyn Service<
+                    Request = types::Transfer<()>,
+                    Response = types::Outcome,
+                    Error = LinkError,
+                    Future = Ready<types::Outcome, LinkError>,
+                > + 'static,
+        >,
+        LinkError,
+    > {
+        Ok(Box::new(fn_service(|tr: types::Transfer<()>| {
+            Ready::Ok(match tr.body().map(|b| b.as_ref()) {
+                Some(b"release") => types::Outcome::Release,
+                Some(b"reject") => {
+                    types::Outcome::Error(AmqpError::not_allowed().description("rejected").into())
+                }
+                Some(b"modify") => types::Outcome::Modified(Modified {
+                    delivery_failed: Some(true),
+                    undeliverable_here: Some(true),
+                    message_annotations: None,
+                }),
+                _ => types::Outcome::Accept,
+            })
+        })))
+    }
+
+    let srv = ntex_amqp::testing::plain_server().finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(publish_link))
+            .finish(),
+    );
+
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    let mut session = sink.open_session().await.unwrap();
+    let link = session.open_sender("test").await.unwrap();
+
+    let res = link.deliver(Bytes::from_static(b"data")).await.unwrap();
+    assert!(matches!(res, types::DeliveryResult::Accepted));
+    assert!(!res.is_redeliverable());
+
+    let res = link.deliver(Bytes::from_static(b"release")).await.unwrap();
+    assert!(matches!(res, types::DeliveryResult::Released));
+    assert!(res.is_redeliverable());
+
+    let res = link.deliver(Bytes::from_static(b"reject")).await.unwrap();
+    if let types::DeliveryResult::Rejected(Some(ref err)) = res {
+        assert_eq!(err.description.as_ref().unwrap().as_ref(), "rejected");
+    } else {
+        panic!("unexpected result: {:?}", res);
+    }
+    assert!(!res.is_redeliverable());
+
+    let res = link.deliver(Bytes::from_static(b"modify")).await.unwrap();
+    assert!(matches!(
+        res,
+        types::DeliveryResult::Modified {
+            delivery_failed: true,
+            undeliverable_here: true,
+            message_annotations: None,
+        }
+    ));
+    assert!(!res.is_redeliverable());
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_deliver_rejected() -> std::io::Result<()> {
+    use ntex_amqp::error::{AmqpError, AmqpProtocolError};
+    use ntex_amqp_codec::protocol::{self, ErrorCondition};
+
+    let srv = ntex_amqp::testing::plain_server().finish(
+        server::Router::<()>::new()
+            .service(
+                "test",
+                fn_factory_with_config(|_: types::Link<()>| async {
+                    Ok::<_, LinkError>(fn_service(|_: types::Transfer<()>| {
+                        Ready::<_, LinkError>::Ok(types::Outcome::Error(
+                            AmqpError::not_allowed().description("rejected").into(),
+                        ))
+                    }))
+                }),
+            )
+            .finish(),
+    );
+
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    let mut session = sink.open_session().await.unwrap();
+    let link = session.open_sender("test").await.unwrap();
+
+    let res = link.deliver(Bytes::from_static(b"data")).await.unwrap();
+    let err = res.into_result().unwrap_err();
+    assert!(matches!(err, AmqpProtocolError::Rejected(Some(_))));
+    let err = err.remote_error().unwrap();
+    assert_eq!(
+        err.condition,
+        ErrorCondition::AmqpError(protocol::AmqpError::NotAllowed)
+    );
+    assert_eq!(err.description.as_ref().unwrap().as_ref(), "rejected");
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_on_connected() -> std::io::Result<()> {
+    use std::{cell::Cell, rc::Rc};
+
+    let connected = Rc::new(Cell::new(false));
+    let connected2 = connected.clone();
+
+    let srv = ntex_amqp::testing::plain_server()
+        .on_connected(move |_: &mut (), _: &ntex_amqp::Connection| connected2.set(true))
+        .finish(
+            server::Router::<()>::new()
+                .service("test", fn_factory_with_config(accept_link))
+                .finish(),
+        );
+
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    assert!(connected.get());
+    drop(client);
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_modify_open() -> std::io::Result<()> {
+    use ntex::service::ServiceFactory;
+    use ntex_amqp_codec::protocol::{Fields, Frame, ProtocolId};
+    use ntex_amqp_codec::types::{Symbol, Variant};
+    use ntex_amqp_codec::{AmqpCodec, AmqpFrame, ProtocolIdCodec};
+
+    let srv = server::Server::new(|conn: server::Handshake<_>| async move {
+        match conn {
+            server::Handshake::Amqp(conn) => {
+                let conn = conn.open().await.unwrap();
+                let container_id = conn.frame().container_id.clone();
+                Ok(conn.ack(()).modify_open(move |open| {
+                    let mut props = Fields::default();
+                    props.insert(
+                        Symbol::from_static("client-container-id"),
+                        Variant::from(container_id),
+                    );
+                    open.properties = Some(props);
+                }))
+            }
+            server::Handshake::Sasl(_) => Err(()),
+        }
+    })
+    .finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(accept_link))
+            .finish(),
+    )
+    .new_service(())
+    .await
+    .unwrap();
+
+    let (mut io, server_io) = ntex_amqp::testing::Io::pair();
+    ntex::rt::spawn(async move {
+        let _ = srv.call(server_io).await;
+    });
+
+    let state = ntex::framed::State::new();
+    state
+        .send(&mut io, &ProtocolIdCodec, ProtocolId::Amqp)
+        .await
+        .unwrap();
+    state.next(&mut io, &ProtocolIdCodec).await.unwrap();
+
+    let codec = AmqpCodec::<AmqpFrame>::new();
+    let open = ntex_amqp::Configuration::default().to_open();
+    let container_id = open.container_id.clone();
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, open.into()))
+        .await
+        .unwrap();
+    let frame = state.next(&mut io, &codec).await.unwrap().unwrap();
+    match frame.performative() {
+        Frame::Open(open) => assert_eq!(
+            open.properties
+                .as_ref()
+                .unwrap()
+                .get(&Symbol::from_static("client-container-id")),
+            Some(&Variant::from(container_id))
+        ),
+        frame => panic!("Unexpected frame: {:?}", frame),
+    }
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_graceful_shutdown() -> std::io::Result<()> {
+    use ntex::service::ServiceFactory;
+    use ntex_amqp::testing::Io;
+    use std::rc::Rc;
+
+    let srv = ntex_amqp::testing::plain_server()
+        .shutdown_timeout(1000)
+        .finish(
+            server::Router::<()>::new()
+                .service("test", fn_factory_with_config(accept_link))
+                .finish(),
+        )
+        .new_service(())
+        .await
+        .unwrap();
+    let srv = Rc::new(srv);
+
+    let (io, server_io) = Io::pair();
+    let srv2 = srv.clone();
+    ntex::rt::spawn(async move {
+        let _ = srv2.call(server_io).await;
+    });
+
+    let client = client::Connector::<&'static str, ()>::new()
+        .negotiate(io)
+        .await
+        .unwrap();
+    let sink = client.sink();
+    let on_close = sink.on_close();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    // active connection gets closed
+    ntex::util::poll_fn(|cx| srv.poll_shutdown(cx, false)).await;
+    on_close.await;
+
+    // new connections are rejected
+    let (_io, server_io) = Io::pair();
+    assert!(srv.call(server_io).await.is_err());
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_scram_server_signature_mismatch() -> std::io::Result<()> {
+    use ntex_amqp_codec::protocol::{
+        ProtocolId, SaslChallenge, SaslCode, SaslFrameBody, SaslMechanisms, SaslOutcome, Symbols,
+    };
+    use ntex_amqp_codec::{types::Symbol, AmqpCodec, ProtocolIdCodec, SaslFrame};
+
+    let (io, mut server_io) = ntex_amqp::testing::Io::pair();
+
+    ntex::rt::spawn(async move {
+        let state = ntex::framed::State::new();
+        state.next(&mut server_io, &ProtocolIdCodec).await.unwrap();
+        state
+            .send(&mut server_io, &ProtocolIdCodec, ProtocolId::AmqpSasl)
+            .await
+            .unwrap();
+
+        let codec = AmqpCodec::<SaslFrame>::new();
+        let mechanisms = SaslMechanisms {
+            sasl_server_mechanisms: Symbols::from(vec![Symbol::from_static("SCRAM-SHA-256")]),
+        };
+        state
+            .send(&mut server_io, &codec, mechanisms.into())
+            .await
+            .unwrap();
+
+        // client-first-message: n,,n=user,r=<nonce>
+        let frame = state.next(&mut server_io, &codec).await.unwrap().unwrap();
+        let nonce = match frame.body {
+            SaslFrameBody::SaslInit(init) => {
+                let resp = init.initial_response.unwrap();
+                let resp = std::str::from_utf8(&resp).unwrap().to_string();
+                resp.split(",r=").nth(1).unwrap().to_string()
+            }
+            body => panic!("Unexpected frame: {:?}", body),
+        };
+
+        let challenge = SaslChallenge {
+            challenge: Bytes::from(format!(
+                "r={}server,s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096",
+                nonce
+            )),
+        };
+        state
+            .send(&mut server_io, &codec, challenge.into())
+            .await
+            .unwrap();
+        state.next(&mut server_io, &codec).await.unwrap().unwrap();
+
+        // outcome is ok, but server signature is wrong
+        let outcome = SaslOutcome {
+            code: SaslCode::Ok,
+            additional_data: Some(Bytes::from_static(
+                b"v=AAAATRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4=",
+            )),
+        };
+        state
+            .send(&mut server_io, &codec, outcome.into())
+            .await
+            .unwrap();
+        let _ = state.next(&mut server_io, &ProtocolIdCodec).await;
+    });
+
+    let res = client::Connector::<&'static str, ()>::new()
+        .negotiate_sasl(
+            io,
+            client::SaslAuth {
+                authz_id: "".into(),
+                authn_id: "user".into(),
+                password: "pencil".into(),
+            },
+        )
+        .await;
+    assert!(matches!(
+        res,
+        Err(client::ConnectError::Sasl(SaslCode::Auth))
+    ));
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_link_idle_timeout() -> std::io::Result<()> {
+    let mut config = ntex_amqp::Configuration::default();
+    config.link_idle_timeout(1);
+
+    let srv = ntex_amqp::testing::plain_server().config(config).finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(accept_link))
+            .finish(),
+    );
+
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    let mut sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    let mut session = sink.open_session().await.unwrap();
+    let link = session.open_sender("test").await.unwrap();
+    let start = std::time::Instant::now();
+
+    // link has credit but nothing gets sent
+    link.on_close().await;
+    assert!(start.elapsed() >= std::time::Duration::from_secs(1));
+    assert!(sink.is_opened());
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_link_address() -> std::io::Result<()> {
+    async fn address_link(
+        link: types::Link<()>,
+    ) -> Result<
+        Box<
+            dyn Service<
+                    Request = types::Transfer<()>,
+                    Response = types::Outcome,
+                    Error = LinkError,
+                    Future = Ready<types::Outcome, LinkError>,
+                > + 'static,
+        >,
+        LinkError,
+    > {
+        assert_eq!(link.address().map(|a| a.as_ref()), Some("test"));
+        assert!(link.target().is_some());
+        accept_link(link).await
+    }
+
+    let srv = ntex_amqp::testing::plain_server().finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(address_link))
+            .finish(),
+    );
+
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    let mut session = sink.open_session().await.unwrap();
+    let link = session.open_sender("test").await.unwrap();
+    let state = link.deliver(Bytes::from_static(b"data")).await.unwrap();
+    assert!(matches!(state, types::DeliveryResult::Accepted));
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_rogue_begin() -> std::io::Result<()> {
+    use ntex::service::ServiceFactory;
+    use ntex_amqp_codec::protocol::{Begin, ConnectionError, ErrorCondition, Frame, ProtocolId};
+    use ntex_amqp_codec::{AmqpCodec, AmqpFrame, ProtocolIdCodec};
+
+    let srv = ntex_amqp::testing::plain_server()
+        .finish(
+            server::Router::<()>::new()
+                .service("test", fn_factory_with_config(accept_link))
+                .finish(),
+        )
+        .new_service(())
+        .await
+        .unwrap();
+
+    let (mut io, server_io) = ntex_amqp::testing::Io::pair();
+    ntex::rt::spawn(async move {
+        let _ = srv.call(server_io).await;
+    });
+
+    let state = ntex::framed::State::new();
+    state
+        .send(&mut io, &ProtocolIdCodec, ProtocolId::Amqp)
+        .await
+        .unwrap();
+    state.next(&mut io, &ProtocolIdCodec).await.unwrap();
+
+    let codec = AmqpCodec::<AmqpFrame>::new();
+    let open = ntex_amqp::Configuration::default().to_open();
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, open.into()))
+        .await
+        .unwrap();
+    let _ = state.next(&mut io, &codec).await.unwrap().unwrap();
+
+    // begin frame replies to session that was never requested
+    let begin = Begin {
+        remote_channel: Some(5),
+        next_outgoing_id: 1,
+        incoming_window: 2048,
+        outgoing_window: 2048,
+        handle_max: 1024,
+        offered_capabilities: None,
+        desired_capabilities: None,
+        properties: None,
+    };
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, begin.into()))
+        .await
+        .unwrap();
+    let frame = state.next(&mut io, &codec).await.unwrap().unwrap();
+    match frame.performative() {
+        Frame::Close(close) => assert_eq!(
+            close.error.as_ref().unwrap().condition,
+            ErrorCondition::ConnectionError(ConnectionError::FramingError)
+        ),
+        frame => panic!("Unexpected frame: {:?}", frame),
+    }
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_connection_config() -> std::io::Result<()> {
+    let mut config = ntex_amqp::Configuration::default();
+    config.max_frame_size(16 * 1024).idle_timeout(30);
+
+    let srv = ntex_amqp::testing::plain_server().config(config).finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(accept_link))
+            .finish(),
+    );
+
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    let sink = client.sink();
+
+    let local = ntex_amqp::Configuration::default();
+    assert_eq!(sink.local_config().channel_max, local.channel_max);
+    assert_eq!(sink.local_config().max_frame_size, local.max_frame_size);
+    assert_eq!(sink.remote_config().max_frame_size, 16 * 1024);
+    assert_eq!(sink.remote_config().idle_time_out, 30_000);
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_sender_available() -> std::io::Result<()> {
+    use std::{cell::RefCell, rc::Rc};
+
+    let links = Rc::new(RefCell::new(Vec::new()));
+    let links2 = links.clone();
+
+    let srv = ntex_amqp::testing::plain_server().finish(
+        server::Router::<()>::new()
+            .service(
+                "test",
+                fn_factory_with_config(move |link: types::Link<()>| {
+                    links2.borrow_mut().push(link.receiver().clone());
+                    accept_link(link)
+                }),
+            )
+            .finish(),
+    );
+
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    let mut session = sink.open_session().await.unwrap();
+    let link = session.open_sender("test").await.unwrap();
+    link.set_available(5);
+    link.deliver(Bytes::from_static(b"data")).await.unwrap();
+
+    assert_eq!(links.borrow()[0].available(), 5);
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_sender_on_disposition() -> std::io::Result<()> {
+    use ntex_amqp_codec::protocol::DeliveryState;
+    use std::{cell::RefCell, rc::Rc};
+
+    let srv = ntex_amqp::testing::plain_server().finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(accept_link))
+            .finish(),
+    );
+
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    let mut session = sink.open_session().await.unwrap();
+    let link = session.open_sender("test").await.unwrap();
+
+    let settled = Rc::new(RefCell::new(Vec::new()));
+    let settled2 = settled.clone();
+    link.on_disposition(move |range, state| {
+        assert!(matches!(state, DeliveryState::Accepted(_)));
+        settled2.borrow_mut().extend(range);
+    });
+
+    for _ in 0..3 {
+        link.deliver(Bytes::from_static(b"data")).await.unwrap();
+    }
+    assert_eq!(settled.borrow().len(), 3);
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_drop_open_session() -> std::io::Result<()> {
+    use std::{future::Future, task::Poll};
+
+    let srv = ntex_amqp::testing::plain_server().finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(accept_link))
+            .finish(),
+    );
+
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    // send `Begin` frame and drop opener before remote reply
+    let mut fut = Box::pin(sink.open_session());
+    ntex::util::poll_fn(|cx| {
+        assert!(fut.as_mut().poll(cx).is_pending());
+        Poll::Ready(())
+    })
+    .await;
+    drop(fut);
+    assert!(sink.has_sessions());
+
+    for _ in 0..100 {
+        if !sink.has_sessions() {
+            break;
+        }
+        ntex::rt::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    assert!(!sink.has_sessions());
+
+    // channel is reusable
+    let mut session = sink.open_session().await.unwrap();
+    session.open_sender("test").await.unwrap();
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_connection_properties() -> std::io::Result<()> {
+    use std::{cell::RefCell, rc::Rc};
+
+    let product = Rc::new(RefCell::new(None));
+    let product2 = product.clone();
+
+    let mut config = ntex_amqp::Configuration::default();
+    config.product("server").version("1.0");
+
+    let srv = server::Server::new(move |conn: server::Handshake<_>| {
+        let product = product2.clone();
+        async move {
+            match conn {
+                server::Handshake::Amqp(conn) => {
+                    let conn = conn.open().await.unwrap();
+                    *product.borrow_mut() = conn.remote_config().get_product().map(String::from);
+                    Ok(conn.ack(()))
+                }
+                server::Handshake::Sasl(_) => Err(()),
+            }
+        }
+    })
+    .config(config)
+    .finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(accept_link))
+            .finish(),
+    );
+
+    let mut connector = client::Connector::<&'static str, ()>::new();
+    connector.property("product", "client");
+    let client = ntex_amqp::testing::connect_with(srv, &connector)
+        .await
+        .unwrap();
+    let sink = client.sink();
+
+    assert_eq!(product.borrow().as_deref(), Some("client"));
+    assert_eq!(sink.remote_config().get_product(), Some("server"));
+    assert_eq!(sink.remote_config().get_version(), Some("1.0"));
+    assert_eq!(sink.remote_config().get_platform(), None);
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_presettled_transfer() -> std::io::Result<()> {
+    use ntex::service::ServiceFactory;
+    use ntex_amqp_codec::protocol::{
+        Attach, Begin, Frame, ProtocolId, ReceiverSettleMode, Role, SenderSettleMode, Target,
+        TerminusDurability, TerminusExpiryPolicy, Transfer, TransferBody,
+    };
+    use ntex_amqp_codec::{AmqpCodec, AmqpFrame, ProtocolIdCodec};
+
+    let srv = ntex_amqp::testing::plain_server()
+        .finish(
+            server::Router::<()>::new()
+                .service("test", fn_factory_with_config(accept_link))
+                .finish(),
+        )
+        .new_service(())
+        .await
+        .unwrap();
+
+    let (mut io, server_io) = ntex_amqp::testing::Io::pair();
+    ntex::rt::spawn(async move {
+        let _ = srv.call(server_io).await;
+    });
+
+    let state = ntex::framed::State::new();
+    state
+        .send(&mut io, &ProtocolIdCodec, ProtocolId::Amqp)
+        .await
+        .unwrap();
+    state.next(&mut io, &ProtocolIdCodec).await.unwrap();
+
+    let codec = AmqpCodec::<AmqpFrame>::new();
+    let open = ntex_amqp::Configuration::default().to_open();
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, open.into()))
+        .await
+        .unwrap();
+    let _ = state.next(&mut io, &codec).await.unwrap().unwrap();
+
+    let begin = Begin {
+        remote_channel: None,
+        next_outgoing_id: 0,
+        incoming_window: std::u32::MAX,
+        outgoing_window: std::u32::MAX,
+        handle_max: std::u32::MAX,
+        offered_capabilities: None,
+        desired_capabilities: None,
+        properties: None,
+    };
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, begin.into()))
+        .await
+        .unwrap();
+    let _ = state.next(&mut io, &codec).await.unwrap().unwrap();
+
+    let attach = Attach {
+        name: "sender".into(),
+        handle: 0,
+        role: Role::Sender,
+        snd_settle_mode: SenderSettleMode::Mixed,
+        rcv_settle_mode: ReceiverSettleMode::First,
+        source: None,
+        target: Some(
+            Target {
+                address: Some("test".into()),
+                durable: TerminusDurability::None,
+                expiry_policy: TerminusExpiryPolicy::SessionEnd,
+                timeout: 0,
+                dynamic: false,
+                dynamic_node_properties: None,
+                capabilities: None,
+            }
+            .into(),
+        ),
+        unsettled: None,
+        incomplete_unsettled: false,
+        initial_delivery_count: Some(0),
+        max_message_size: None,
+        offered_capabilities: None,
+        desired_capabilities: None,
+        properties: None,
+    };
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, attach.into()))
+        .await
+        .unwrap();
+    let frame = state.next(&mut io, &codec).await.unwrap().unwrap();
+    assert!(matches!(frame.performative(), Frame::Attach(_)));
+
+    let transfer = |id: Option<u32>, settled: Option<bool>, more: bool| Transfer {
+        handle: 0,
+        delivery_id: id,
+        delivery_tag: id.map(|id| Bytes::from(id.to_be_bytes().to_vec())),
+        message_format: Some(0),
+        settled,
+        more,
+        rcv_settle_mode: None,
+        state: None,
+        resume: false,
+        aborted: false,
+        batchable: false,
+        body: Some(TransferBody::Data(Bytes::from_static(b"test"))),
+    };
+
+    // pre-settled delivery
+    state
+        .send(
+            &mut io,
+            &codec,
+            AmqpFrame::new(0, transfer(Some(0), Some(true), false).into()),
+        )
+        .await
+        .unwrap();
+
+    // multi-frame delivery settled with the last transfer
+    state
+        .send(
+            &mut io,
+            &codec,
+            AmqpFrame::new(0, transfer(Some(1), None, true).into()),
+        )
+        .await
+        .unwrap();
+    state
+        .send(
+            &mut io,
+            &codec,
+            AmqpFrame::new(0, transfer(None, Some(true), false).into()),
+        )
+        .await
+        .unwrap();
+
+    // unsettled delivery
+    state
+        .send(
+            &mut io,
+            &codec,
+            AmqpFrame::new(0, transfer(Some(2), Some(false), false).into()),
+        )
+        .await
+        .unwrap();
+
+    // only unsettled delivery gets disposition
+    loop {
+        let frame = state.next(&mut io, &codec).await.unwrap().unwrap();
+        match frame.performative() {
+            Frame::Flow(_) => continue,
+            Frame::Disposition(disp) => {
+                assert_eq!(disp.first, 2);
+                assert_eq!(disp.last, None);
+                break;
+            }
+            frame => panic!("Unexpected frame: {:?}", frame),
+        }
+    }
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_multi_frame_transfers_order() -> std::io::Result<()> {
+    use ntex_amqp_codec::protocol::{Begin, Flow, Frame, ProtocolId, Role};
+    use ntex_amqp_codec::{AmqpCodec, AmqpFrame, ProtocolIdCodec};
+
+    let (client_io, mut io) = ntex_amqp::testing::Io::pair();
+    ntex::rt::spawn(async move {
+        let client = client::Connector::<&'static str, ()>::new()
+            .negotiate(client_io)
+            .await
+            .unwrap();
+        let sink = client.sink();
+        ntex::rt::spawn(async move {
+            let _ = client.start_default().await;
+        });
+
+        let mut session = sink.open_session().await.unwrap();
+        let link1 = session.open_sender("test1").await.unwrap();
+        let link2 = session.open_sender("test2").await.unwrap();
+
+        // deliveries of both links are split to multiple frames
+        let d1 = link1.send(Bytes::from(vec![b'1'; 8 * 1024]));
+        let _d2 = link2.send(Bytes::from(vec![b'2'; 8 * 1024]));
+        let _d3 = link1.send(Bytes::from(vec![b'3'; 8 * 1024]));
+        let _ = d1.await;
+    });
+
+    let state = ntex::framed::State::new();
+    state.next(&mut io, &ProtocolIdCodec).await.unwrap();
+    state
+        .send(&mut io, &ProtocolIdCodec, ProtocolId::Amqp)
+        .await
+        .unwrap();
+
+    let codec = AmqpCodec::<AmqpFrame>::new();
+    let _ = state.next(&mut io, &codec).await.unwrap().unwrap();
+    let mut config = ntex_amqp::Configuration::default();
+    config.max_frame_size(1024);
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, config.to_open().into()))
+        .await
+        .unwrap();
+
+    let frame = state.next(&mut io, &codec).await.unwrap().unwrap();
+    let begin = Begin {
+        remote_channel: Some(frame.channel_id()),
+        next_outgoing_id: 1,
+        incoming_window: std::u32::MAX,
+        outgoing_window: std::u32::MAX,
+        handle_max: std::u32::MAX,
+        offered_capabilities: None,
+        desired_capabilities: None,
+        properties: None,
+    };
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, begin.into()))
+        .await
+        .unwrap();
+
+    // confirm both sender links and grant link credit
+    for _ in 0..2 {
+        let frame = state.next(&mut io, &codec).await.unwrap().unwrap();
+        let mut attach = match frame.performative() {
+            Frame::Attach(attach) => attach.clone(),
+            frame => panic!("Unexpected frame: {:?}", frame),
+        };
+        attach.role = Role::Receiver;
+        let handle = attach.handle;
+        state
+            .send(&mut io, &codec, AmqpFrame::new(0, attach.into()))
+            .await
+            .unwrap();
+
+        let flow = Flow {
+            next_incoming_id: None,
+            incoming_window: std::u32::MAX,
+            next_outgoing_id: 1,
+            outgoing_window: std::u32::MAX,
+            handle: Some(handle),
+            delivery_count: Some(0),
+            link_credit: Some(10),
+            available: None,
+            drain: false,
+            echo: false,
+            properties: None,
+        };
+        state
+            .send(&mut io, &codec, AmqpFrame::new(0, flow.into()))
+            .await
+            .unwrap();
+    }
+
+    // frames of incomplete delivery are not interleaved with other link's frames
+    let mut current = None;
+    let mut completed = 0;
+    while completed < 3 {
+        let frame = state.next(&mut io, &codec).await.unwrap().unwrap();
+        let transfer = match frame.performative() {
+            Frame::Transfer(transfer) => transfer,
+            _ => continue,
+        };
+        if let Some(handle) = current {
+            assert_eq!(transfer.handle, handle);
+        }
+        if transfer.more {
+            current = Some(transfer.handle);
+        } else {
+            current = None;
+            completed += 1;
+        }
+    }
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_client_control_service() -> std::io::Result<()> {
+    use std::{cell::RefCell, rc::Rc};
+
+    let srv = ntex_amqp::testing::plain_server().finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(accept_link))
+            .finish(),
+    );
+
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    let sink = client.sink();
+
+    let flows = Rc::new(RefCell::new(0));
+    let flows2 = flows.clone();
+    ntex::rt::spawn(async move {
+        let _ = client
+            .start(
+                fn_service(move |frame: ntex_amqp::ControlFrame| {
+                    if let ntex_amqp::ControlFrameKind::Flow(_, _) = frame.frame() {
+                        *flows2.borrow_mut() += 1;
+                    }
+                    Ready::<_, LinkError>::Ok(())
+                }),
+                fn_service(|_: types::Link<()>| {
+                    Ready::<_, LinkError>::Err(LinkError::force_detach())
+                }),
+            )
+            .await;
+    });
+
+    let mut session = sink.open_session().await.unwrap();
+    let link = session.open_sender("test").await.unwrap();
+    link.deliver(Bytes::from_static(b"data")).await.unwrap();
+    assert!(*flows.borrow() > 0);
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_sender_try_send() -> std::io::Result<()> {
+    use ntex_amqp::error::NoCredit;
+
+    let srv = ntex_amqp::testing::plain_server().finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(accept_link))
+            .finish(),
+    );
+
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    let mut session = sink.open_session().await.unwrap();
+    let link = session.open_sender("test").await.unwrap();
+
+    // router grants 50 credits
+    let mut deliveries = Vec::new();
+    for _ in 0..50 {
+        deliveries.push(link.try_send(Bytes::from_static(b"data")).unwrap());
+    }
+    assert_eq!(
+        link.try_send(Bytes::from_static(b"data")).err(),
+        Some(NoCredit)
+    );
+
+    // credit gets replenished by remote peer
+    for delivery in deliveries {
+        delivery.await.unwrap();
+    }
+    let delivery = link.try_send(Bytes::from_static(b"data")).unwrap();
+    assert!(delivery.await.is_ok());
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_rcv_settle_mode() -> std::io::Result<()> {
+    use ntex_amqp_codec::protocol::{DeliveryState, ReceiverSettleMode};
+
+    let srv = ntex_amqp::testing::plain_server().finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(accept_link))
+            .finish(),
+    );
+
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    let mut session = sink.open_session().await.unwrap();
+
+    // first mode, receiver settles delivery
+    let link = session.open_sender("test").await.unwrap();
+    let disp = link.send(Bytes::from_static(b"data")).await.unwrap();
+    assert!(disp.settled);
+
+    // second mode, sender settles delivery first
+    let link = session
+        .build_sender_link("test2", "test")
+        .with_frame(|frame| frame.rcv_settle_mode = ReceiverSettleMode::Second)
+        .open()
+        .await
+        .unwrap();
+    let disp = link.send(Bytes::from_static(b"data")).await.unwrap();
+    assert!(!disp.settled);
+    assert!(matches!(disp.state, Some(DeliveryState::Accepted(_))));
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_unattached_handle() -> std::io::Result<()> {
+    use ntex::service::ServiceFactory;
+    use ntex_amqp_codec::protocol::{
+        Begin, ErrorCondition, Frame, ProtocolId, SessionError, Transfer, TransferBody,
+    };
+    use ntex_amqp_codec::{AmqpCodec, AmqpFrame, ProtocolIdCodec};
+
+    let srv = ntex_amqp::testing::plain_server()
+        .finish(
+            server::Router::<()>::new()
+                .service("test", fn_factory_with_config(accept_link))
+                .finish(),
+        )
+        .new_service(())
+        .await
+        .unwrap();
+
+    let (mut io, server_io) = ntex_amqp::testing::Io::pair();
+    ntex::rt::spawn(async move {
+        let _ = srv.call(server_io).await;
+    });
+
+    let state = ntex::framed::State::new();
+    state
+        .send(&mut io, &ProtocolIdCodec, ProtocolId::Amqp)
+        .await
+        .unwrap();
+    state.next(&mut io, &ProtocolIdCodec).await.unwrap();
+
+    let codec = AmqpCodec::<AmqpFrame>::new();
+    let open = ntex_amqp::Configuration::default().to_open();
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, open.into()))
+        .await
+        .unwrap();
+    let _ = state.next(&mut io, &codec).await.unwrap().unwrap();
+
+    let begin = Begin {
+        remote_channel: None,
+        next_outgoing_id: 0,
+        incoming_window: std::u32::MAX,
+        outgoing_window: std::u32::MAX,
+        handle_max: std::u32::MAX,
+        offered_capabilities: None,
+        desired_capabilities: None,
+        properties: None,
+    };
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, begin.into()))
+        .await
+        .unwrap();
+    let _ = state.next(&mut io, &codec).await.unwrap().unwrap();
+
+    // transfer over link that was never attached
+    let transfer = Transfer {
+        handle: 7,
+        delivery_id: Some(0),
+        delivery_tag: Some(Bytes::from_static(b"tag")),
+        message_format: Some(0),
+        settled: None,
+        more: false,
+        rcv_settle_mode: None,
+        state: None,
+        resume: false,
+        aborted: false,
+        batchable: false,
+        body: Some(TransferBody::Data(Bytes::from_static(b"test"))),
+    };
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, transfer.into()))
+        .await
+        .unwrap();
+    let frame = state.next(&mut io, &codec).await.unwrap().unwrap();
+    match frame.performative() {
+        Frame::End(end) => assert_eq!(
+            end.error.as_ref().unwrap().condition,
+            ErrorCondition::SessionError(SessionError::UnattachedHandle)
+        ),
+        frame => panic!("Unexpected frame: {:?}", frame),
+    }
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_max_frame_size() -> std::io::Result<()> {
+    use ntex::service::ServiceFactory;
+    use ntex_amqp_codec::protocol::{
+        Begin, ConnectionError, ErrorCondition, Frame, ProtocolId, Transfer, TransferBody,
+    };
+    use ntex_amqp_codec::{AmqpCodec, AmqpFrame, ProtocolIdCodec};
+
+    let mut config = ntex_amqp::Configuration::default();
+    config.max_frame_size(1024);
+
+    let srv = ntex_amqp::testing::plain_server()
+        .config(config)
+        .finish(
+            server::Router::<()>::new()
+                .service("test", fn_factory_with_config(accept_link))
+                .finish(),
+        )
+        .new_service(())
+        .await
+        .unwrap();
+
+    let (mut io, server_io) = ntex_amqp::testing::Io::pair();
+    ntex::rt::spawn(async move {
+        let _ = srv.call(server_io).await;
+    });
+
+    let state = ntex::framed::State::new();
+    state
+        .send(&mut io, &ProtocolIdCodec, ProtocolId::Amqp)
+        .await
+        .unwrap();
+    state.next(&mut io, &ProtocolIdCodec).await.unwrap();
+
+    let codec = AmqpCodec::<AmqpFrame>::new();
+    let open = ntex_amqp::Configuration::default().to_open();
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, open.into()))
+        .await
+        .unwrap();
+    let frame = state.next(&mut io, &codec).await.unwrap().unwrap();
+    match frame.performative() {
+        Frame::Open(open) => assert_eq!(open.max_frame_size, 1024),
+        frame => panic!("Unexpected frame: {:?}", frame),
+    }
+
+    let begin = Begin {
+        remote_channel: None,
+        next_outgoing_id: 0,
+        incoming_window: std::u32::MAX,
+        outgoing_window: std::u32::MAX,
+        handle_max: std::u32::MAX,
+        offered_capabilities: None,
+        desired_capabilities: None,
+        properties: None,
+    };
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, begin.into()))
+        .await
+        .unwrap();
+    let _ = state.next(&mut io, &codec).await.unwrap().unwrap();
+
+    // frame is larger than advertised max frame size
+    let transfer = Transfer {
+        handle: 0,
+        delivery_id: Some(0),
+        delivery_tag: Some(Bytes::from_static(b"tag")),
+        message_format: Some(0),
+        settled: None,
+        more: false,
+        rcv_settle_mode: None,
+        state: None,
+        resume: false,
+        aborted: false,
+        batchable: false,
+        body: Some(TransferBody::Data(Bytes::from(vec![b'x'; 4096]))),
+    };
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, transfer.into()))
+        .await
+        .unwrap();
+    let frame = state.next(&mut io, &codec).await.unwrap().unwrap();
+    match frame.performative() {
+        Frame::Close(close) => assert_eq!(
+            close.error.as_ref().unwrap().condition,
+            ErrorCondition::ConnectionError(ConnectionError::FramingError)
+        ),
+        frame => panic!("Unexpected frame: {:?}", frame),
+    }
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_frames_after_local_close() -> std::io::Result<()> {
+    use ntex::service::ServiceFactory;
+    use ntex_amqp_codec::protocol::{Begin, Frame, ProtocolId};
+    use ntex_amqp_codec::{AmqpCodec, AmqpFrame, ProtocolIdCodec};
+
+    let srv = ntex_amqp::testing::plain_server()
+        .on_connected(|_, conn| {
+            let _ = conn.close();
+        })
+        .finish(
+            server::Router::<()>::new()
+                .service("test", fn_factory_with_config(accept_link))
+                .finish(),
+        )
+        .new_service(())
+        .await
+        .unwrap();
+
+    let (mut io, server_io) = ntex_amqp::testing::Io::pair();
+    ntex::rt::spawn(async move {
+        let _ = srv.call(server_io).await;
+    });
+
+    let state = ntex::framed::State::new();
+    state
+        .send(&mut io, &ProtocolIdCodec, ProtocolId::Amqp)
+        .await
+        .unwrap();
+    state.next(&mut io, &ProtocolIdCodec).await.unwrap();
+
+    // begin is sent before server closes connection
+    let codec = AmqpCodec::<AmqpFrame>::new();
+    let open = ntex_amqp::Configuration::default().to_open();
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, open.into()))
+        .await
+        .unwrap();
+    let begin = Begin {
+        remote_channel: None,
+        next_outgoing_id: 0,
+        incoming_window: std::u32::MAX,
+        outgoing_window: std::u32::MAX,
+        handle_max: std::u32::MAX,
+        offered_capabilities: None,
+        desired_capabilities: None,
+        properties: None,
+    };
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, begin.into()))
+        .await
+        .unwrap();
+
+    let frame = state.next(&mut io, &codec).await.unwrap().unwrap();
+    assert!(matches!(frame.performative(), Frame::Open(_)));
+    let frame = state.next(&mut io, &codec).await.unwrap().unwrap();
+    assert!(matches!(frame.performative(), Frame::Close(_)));
+
+    // begin is not processed by closing connection
+    if let Ok(Some(frame)) = state.next(&mut io, &codec).await {
+        panic!("Unexpected frame: {:?}", frame);
+    }
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_session_capacity() -> std::io::Result<()> {
+    let mut config = ntex_amqp::Configuration::default();
+    config.session_capacity(1);
+
+    let srv = ntex_amqp::testing::plain_server().config(config).finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(accept_link))
+            .finish(),
+    );
+
+    let mut connector = client::Connector::<&'static str, ()>::new();
+    connector.session_capacity(1);
+    let client = ntex_amqp::testing::connect_with(srv, &connector)
+        .await
+        .unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    // storage grows above preallocated capacity
+    let mut sessions = Vec::new();
+    for _ in 0..4 {
+        let mut session = sink.open_session().await.unwrap();
+        let link = session.open_sender("test").await.unwrap();
+        link.send(Bytes::from_static(b"data")).await.unwrap();
+        sessions.push(session);
+    }
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_credit_replenishment() -> std::io::Result<()> {
+    let srv = ntex_amqp::testing::plain_server().finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(accept_link))
+            .finish(),
+    );
+
+    let connector = client::Connector::<&'static str, ()>::new();
+    let (client, recorder) = ntex_amqp::testing::connect_recorded(srv, &connector)
+        .await
+        .unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    let mut session = sink.open_session().await.unwrap();
+    let link = session.open_sender("test").await.unwrap();
+    let handle = link.remote_handle();
+
+    // router grants 50 credits, and grants more once credit is exhausted
+    for _ in 0..50 {
+        link.send(Bytes::from_static(b"data")).await.unwrap();
+    }
+    assert_eq!(link.credit(), 50);
+    assert_eq!(link.delivery_count(), 50);
+
+    let flows = recorder.received.link_flows(handle);
+    assert_eq!(flows.first().unwrap().link_credit, Some(50));
+    assert_eq!(flows.first().unwrap().delivery_count, Some(0));
+    assert_eq!(flows.last().unwrap().link_credit, Some(50));
+    assert_eq!(flows.last().unwrap().delivery_count, Some(50));
+
+    let info = session.info();
+    assert_eq!(info.pending_transfers, 0);
+    assert_eq!(info.links[0].link_credit, 50);
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_handle_reuse() -> std::io::Result<()> {
+    use ntex::service::ServiceFactory;
+    use ntex_amqp::testing::Io;
+    use ntex_amqp_codec::protocol::{
+        Attach, Begin, DeliveryState, Detach, Frame, ProtocolId, ReceiverSettleMode, Role,
+        SenderSettleMode, Target, TerminusDurability, TerminusExpiryPolicy, Transfer, TransferBody,
+    };
+    use ntex_amqp_codec::{AmqpCodec, AmqpFrame, ProtocolIdCodec};
+
+    // next frame, flow frames are skipped
+    async fn next(state: &ntex::framed::State, io: &mut Io, codec: &AmqpCodec<AmqpFrame>) -> Frame {
+        loop {
+            let frame = state.next(io, codec).await.unwrap().unwrap();
+            match frame.into_parts().1 {
+                Frame::Flow(_) => continue,
+                frame => return frame,
+            }
+        }
+    }
+
+    let srv = ntex_amqp::testing::plain_server()
+        .finish(
+            server::Router::<()>::new()
+                .service("test", fn_factory_with_config(accept_link))
+                .finish(),
+        )
+        .new_service(())
+        .await
+        .unwrap();
+
+    let (mut io, server_io) = Io::pair();
+    ntex::rt::spawn(async move {
+        let _ = srv.call(server_io).await;
+    });
+
+    let state = ntex::framed::State::new();
+    state
+        .send(&mut io, &ProtocolIdCodec, ProtocolId::Amqp)
+        .await
+        .unwrap();
+    state.next(&mut io, &ProtocolIdCodec).await.unwrap();
+
+    let codec = AmqpCodec::<AmqpFrame>::new();
+    let open = ntex_amqp::Configuration::default().to_open();
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, open.into()))
+        .await
+        .unwrap();
+    let _ = state.next(&mut io, &codec).await.unwrap().unwrap();
+
+    let begin = Begin {
+        remote_channel: None,
+        next_outgoing_id: 0,
+        incoming_window: std::u32::MAX,
+        outgoing_window: std::u32::MAX,
+        handle_max: std::u32::MAX,
+        offered_capabilities: None,
+        desired_capabilities: None,
+        properties: None,
+    };
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, begin.into()))
+        .await
+        .unwrap();
+    let _ = state.next(&mut io, &codec).await.unwrap().unwrap();
+
+    let attach = |name: &'static str| Attach {
+        name: name.into(),
+        handle: 0,
+        role: Role::Sender,
+        snd_settle_mode: SenderSettleMode::Mixed,
+        rcv_settle_mode: ReceiverSettleMode::First,
+        source: None,
+        target: Some(
+            Target {
+                address: Some("test".into()),
+                durable: TerminusDurability::None,
+                expiry_policy: TerminusExpiryPolicy::SessionEnd,
+                timeout: 0,
+                dynamic: false,
+                dynamic_node_properties: None,
+                capabilities: None,
+            }
+            .into(),
+        ),
+        unsettled: None,
+        incomplete_unsettled: false,
+        initial_delivery_count: Some(0),
+        max_message_size: None,
+        offered_capabilities: None,
+        desired_capabilities: None,
+        properties: None,
+    };
+    let transfer = |id: u32| Transfer {
+        handle: 0,
+        delivery_id: Some(id),
+        delivery_tag: Some(Bytes::from(id.to_be_bytes().to_vec())),
+        message_format: Some(0),
+        settled: None,
+        more: false,
+        rcv_settle_mode: None,
+        state: None,
+        resume: false,
+        aborted: false,
+        batchable: false,
+        body: Some(TransferBody::Data(Bytes::from_static(b"test"))),
+    };
+
+    for (id, name) in [(0, "link1"), (1, "link2")].iter() {
+        // both links use handle 0
+        state
+            .send(&mut io, &codec, AmqpFrame::new(0, attach(name).into()))
+            .await
+            .unwrap();
+        match next(&state, &mut io, &codec).await {
+            Frame::Attach(attach) => assert_eq!(attach.name.as_ref(), *name),
+            frame => panic!("Unexpected frame: {:?}", frame),
+        }
+
+        state
+            .send(&mut io, &codec, AmqpFrame::new(0, transfer(*id).into()))
+            .await
+            .unwrap();
+        match next(&state, &mut io, &codec).await {
+            Frame::Disposition(disp) => {
+                assert_eq!(disp.first, *id);
+                assert!(matches!(disp.state, Some(DeliveryState::Accepted(_))));
+            }
+            frame => panic!("Unexpected frame: {:?}", frame),
+        }
+
+        let detach = Detach {
+            handle: 0,
+            closed: true,
+            error: None,
+        };
+        state
+            .send(&mut io, &codec, AmqpFrame::new(0, detach.into()))
+            .await
+            .unwrap();
+        match next(&state, &mut io, &codec).await {
+            Frame::Detach(detach) => assert!(detach.error.is_none()),
+            frame => panic!("Unexpected frame: {:?}", frame),
+        }
+    }
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_missed_deliveries() -> std::io::Result<()> {
+    use ntex::service::ServiceFactory;
+    use ntex_amqp::testing::Io;
+    use ntex_amqp_codec::protocol::{
+        Attach, Begin, DeliveryState, Frame, ProtocolId, ReceiverSettleMode, Role,
+        SenderSettleMode, Target, TerminusDurability, TerminusExpiryPolicy, Transfer, TransferBody,
+    };
+    use ntex_amqp_codec::{AmqpCodec, AmqpFrame, ProtocolIdCodec};
+
+    // next frame, flow frames are skipped
+    async fn next(state: &ntex::framed::State, io: &mut Io, codec: &AmqpCodec<AmqpFrame>) -> Frame {
+        loop {
+            let frame = state.next(io, codec).await.unwrap().unwrap();
+            match frame.into_parts().1 {
+                Frame::Flow(_) => continue,
+                frame => return frame,
+            }
+        }
+    }
+
+    let srv = ntex_amqp::testing::plain_server()
+        .finish(
+            server::Router::<()>::new()
+                .service(
+                    "test",
+                    fn_factory_with_config(|_: types::Link<()>| async {
+                        // accept transfer if body matches number of missed deliveries
+                        Ok::<_, LinkError>(fn_service(|tr: types::Transfer<()>| async move {
+                            let missed = tr.session().info().missed_deliveries.to_string();
+                            if tr.body().map(|b| b.as_ref()) == Some(missed.as_bytes()) {
+                                Ok::<_, LinkError>(types::Outcome::Accept)
+                            } else {
+                                Ok(types::Outcome::Reject)
+                            }
+                        }))
+                    }),
+                )
+                .finish(),
+        )
+        .new_service(())
+        .await
+        .unwrap();
+
+    let (mut io, server_io) = Io::pair();
+    ntex::rt::spawn(async move {
+        let _ = srv.call(server_io).await;
+    });
+
+    let state = ntex::framed::State::new();
+    state
+        .send(&mut io, &ProtocolIdCodec, ProtocolId::Amqp)
+        .await
+        .unwrap();
+    state.next(&mut io, &ProtocolIdCodec).await.unwrap();
+
+    let codec = AmqpCodec::<AmqpFrame>::new();
+    let open = ntex_amqp::Configuration::default().to_open();
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, open.into()))
+        .await
+        .unwrap();
+    let _ = state.next(&mut io, &codec).await.unwrap().unwrap();
+
+    let begin = Begin {
+        remote_channel: None,
+        next_outgoing_id: 0,
+        incoming_window: std::u32::MAX,
+        outgoing_window: std::u32::MAX,
+        handle_max: std::u32::MAX,
+        offered_capabilities: None,
+        desired_capabilities: None,
+        properties: None,
+    };
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, begin.into()))
+        .await
+        .unwrap();
+    let _ = state.next(&mut io, &codec).await.unwrap().unwrap();
+
+    let attach = |name: &'static str| Attach {
+        name: name.into(),
+        handle: 0,
+        role: Role::Sender,
+        snd_settle_mode: SenderSettleMode::Mixed,
+        rcv_settle_mode: ReceiverSettleMode::First,
+        source: None,
+        target: Some(
+            Target {
+                address: Some("test".into()),
+                durable: TerminusDurability::None,
+                expiry_policy: TerminusExpiryPolicy::SessionEnd,
+                timeout: 0,
+                dynamic: false,
+                dynamic_node_properties: None,
+                capabilities: None,
+            }
+            .into(),
+        ),
+        unsettled: None,
+        incomplete_unsettled: false,
+        initial_delivery_count: Some(0),
+        max_message_size: None,
+        offered_capabilities: None,
+        desired_capabilities: None,
+        properties: None,
+    };
+    let transfer = |id: u32, body: &'static [u8]| Transfer {
+        handle: 0,
+        delivery_id: Some(id),
+        delivery_tag: Some(Bytes::from(id.to_be_bytes().to_vec())),
+        message_format: Some(0),
+        settled: None,
+        more: false,
+        rcv_settle_mode: None,
+        state: None,
+        resume: false,
+        aborted: false,
+        batchable: false,
+        body: Some(TransferBody::Data(Bytes::from_static(body))),
+    };
+
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, attach("link").into()))
+        .await
+        .unwrap();
+    match next(&state, &mut io, &codec).await {
+        Frame::Attach(attach) => assert_eq!(attach.name.as_ref(), "link"),
+        frame => panic!("Unexpected frame: {:?}", frame),
+    }
+
+    // delivery ids 2, 3 and 4 are skipped
+    for (id, body) in [(0, b"0"), (1, b"0"), (5, b"3"), (6, b"3")].iter() {
+        state
+            .send(
+                &mut io,
+                &codec,
+                AmqpFrame::new(0, transfer(*id, *body).into()),
+            )
+            .await
+            .unwrap();
+        match next(&state, &mut io, &codec).await {
+            Frame::Disposition(disp) => {
+                assert_eq!(disp.first, *id);
+                assert!(matches!(disp.state, Some(DeliveryState::Accepted(_))));
+            }
+            frame => panic!("Unexpected frame: {:?}", frame),
+        }
+    }
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_connection_status() -> std::io::Result<()> {
+    use ntex_amqp::ConnectionStatus;
+    use ntex_amqp_codec::protocol::ProtocolId;
+
+    let srv = ntex_amqp::testing::plain_server().finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(accept_link))
+            .finish(),
+    );
+
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+    assert_eq!(sink.status(), ConnectionStatus::Open);
+    assert!(!sink.is_closing());
+    assert_eq!(sink.protocol(), ProtocolId::Amqp);
+    assert_eq!(sink.sasl_mechanism(), None);
+
+    let _ = sink.close().await;
+    assert_eq!(sink.status(), ConnectionStatus::Closing);
+    assert!(sink.is_closing());
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_no_remote_idle_timeout() -> std::io::Result<()> {
+    use ntex_amqp_codec::protocol::Frame;
+
+    // server does not advertise idle time-out
+    let mut config = ntex_amqp::Configuration::default();
+    config.idle_timeout(0);
+
+    let srv = ntex_amqp::testing::plain_server().config(config).finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(accept_link))
+            .finish(),
+    );
+
+    let mut connector = client::Connector::<&'static str, ()>::new();
+    connector.idle_timeout(1);
+    let (client, recorder) = ntex_amqp::testing::connect_recorded(srv, &connector)
+        .await
+        .unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+    assert_eq!(sink.heartbeat_interval(), None);
+    assert_eq!(sink.idle_timeout(), Some(std::time::Duration::from_secs(1)));
+
+    ntex::rt::time::sleep(std::time::Duration::from_millis(2500)).await;
+
+    // client does not send heartbeats, server heartbeats keep connection open
+    let is_empty =
+        |frame: &ntex_amqp_codec::AmqpFrame| matches!(frame.performative(), Frame::Empty);
+    assert!(!recorder.sent.frames().iter().any(is_empty));
+    assert!(recorder.received.frames().iter().any(is_empty));
+    assert!(sink.clone().is_opened());
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_post_raw_frame() -> std::io::Result<()> {
+    use ntex_amqp::ConnectionStatus;
+    use ntex_amqp_codec::protocol::{Close, Frame};
+
+    let srv = ntex_amqp::testing::plain_server().finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(accept_link))
+            .finish(),
+    );
+
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    // local state is not updated, server replies with `Close`
+    sink.post_raw_frame(0, Frame::Close(Close { error: None }));
+    assert_eq!(sink.status(), ConnectionStatus::Open);
+
+    let on_close = sink.on_close();
+    ntex::rt::time::timeout(std::time::Duration::from_secs(1), on_close)
+        .await
+        .unwrap();
+    assert_eq!(sink.status(), ConnectionStatus::RemoteClosed);
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_remote_handle_max() -> std::io::Result<()> {
+    use ntex_amqp::error::AmqpProtocolError;
+
+    let mut config = ntex_amqp::Configuration::default();
+    config.handle_max(1);
+
+    let srv = ntex_amqp::testing::plain_server().config(config).finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(accept_link))
+            .finish(),
+    );
+
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    // handles 0 and 1 are accepted by remote peer
+    let mut session = sink.open_session().await.unwrap();
+    let link1 = session.open_sender("test").await.unwrap();
+    let link2 = session.open_sender("test").await.unwrap();
+    assert!(matches!(
+        session.open_sender("test").await,
+        Err(AmqpProtocolError::TooManyLinks(1))
+    ));
+
+    // closed link releases its handle
+    link1.close().await.unwrap();
+    let link3 = session.open_sender("test").await.unwrap();
+    link2.send(Bytes::from_static(b"data")).await.unwrap();
+    link3.send(Bytes::from_static(b"data")).await.unwrap();
+    assert!(sink.clone().is_opened());
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_handshake_deadline() -> std::io::Result<()> {
+    use ntex::service::ServiceFactory;
+    use ntex_amqp_codec::protocol::{ProtocolId, SaslInit, SaslMechanism};
+    use ntex_amqp_codec::{AmqpCodec, ProtocolIdCodec, SaslFrame};
+
+    let srv = ntex_amqp::testing::plain_server()
+        .sasl_authenticator(PlainAuth)
+        .handshake_timeout(300)
+        .finish(
+            server::Router::<()>::new()
+                .service("test", fn_factory_with_config(accept_link))
+                .finish(),
+        )
+        .new_service(())
+        .await
+        .unwrap();
+
+    // every handshake step is shorter than timeout, but whole handshake is not
+    let (mut io, server_io) = ntex_amqp::testing::Io::pair();
+    ntex::rt::spawn(async move {
+        let delay = std::time::Duration::from_millis(200);
+        let state = ntex::framed::State::new();
+        let codec = AmqpCodec::<SaslFrame>::new();
+        state
+            .send(&mut io, &ProtocolIdCodec, ProtocolId::AmqpSasl)
+            .await
+            .unwrap();
+        let _ = state.next(&mut io, &ProtocolIdCodec).await;
+        let _ = state.next(&mut io, &codec).await;
+
+        ntex::rt::time::sleep(delay).await;
+        let init = SaslInit {
+            hostname: None,
+            mechanism: SaslMechanism::Plain.into(),
+            initial_response: Some(SaslInit::prepare_response("", "user1", "password1")),
+        };
+        let _ = state.send(&mut io, &codec, init.into()).await;
+        let _ = state.next(&mut io, &codec).await;
+
+        ntex::rt::time::sleep(delay).await;
+        let _ = state
+            .send(&mut io, &ProtocolIdCodec, ProtocolId::Amqp)
+            .await;
+        ntex::rt::time::sleep(delay).await;
+    });
+
+    let start = std::time::Instant::now();
+    let res = srv.call(server_io).await;
+    assert!(matches!(
+        res,
+        Err(server::ServerError::Handshake(
+            server::HandshakeError::Timeout
+        ))
+    ));
+    assert!(start.elapsed() < std::time::Duration::from_millis(400));
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_heartbeat_skipped_on_traffic() -> std::io::Result<()> {
+    use ntex_amqp_codec::protocol::Frame;
+
+    // client heartbeat interval is 1 second
+    let mut config = ntex_amqp::Configuration::default();
+    config.idle_timeout(2);
+
+    let srv = ntex_amqp::testing::plain_server().config(config).finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(accept_link))
+            .finish(),
+    );
+
+    let connector = client::Connector::<&'static str, ()>::new();
+    let (client, recorder) = ntex_amqp::testing::connect_recorded(srv, &connector)
+        .await
+        .unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+    assert_eq!(
+        sink.heartbeat_interval(),
+        Some(std::time::Duration::from_secs(1))
+    );
+
+    let mut session = sink.open_session().await.unwrap();
+    let link = session
+        .build_sender_link("test", "test")
+        .open()
+        .await
+        .unwrap();
+
+    // transfers flow continuously, heartbeats are not needed
+    let is_empty =
+        |frame: &ntex_amqp_codec::AmqpFrame| matches!(frame.performative(), Frame::Empty);
+    for _ in 0..15 {
+        link.send(Bytes::from(b"test".as_ref())).await.unwrap();
+        ntex::rt::time::sleep(std::time::Duration::from_millis(200)).await;
+    }
+    assert!(!recorder.sent.frames().iter().any(is_empty));
+
+    // connection is idle
+    ntex::rt::time::sleep(std::time::Duration::from_millis(2500)).await;
+    assert!(recorder.sent.frames().iter().any(is_empty));
+    assert!(sink.clone().is_opened());
+
+    Ok(())
+}
+
+async fn durable_link(
+    link: types::Link<()>,
+) -> Result<
+    Box<
+        dyn Service<
+                Request = types::Transfer<()>,
+                Response = types::Outcome,
+                Error = LinkError,
+                Future = Ready<types::Outcome, LinkError>,
+            > + 'static,
+    >,
+    LinkError,
+> {
+    use ntex_amqp_codec::protocol::{TerminusDurability, TerminusExpiryPolicy};
+
+    if link.durable() != TerminusDurability::UnsettledState
+        || link.expiry_policy() != TerminusExpiryPolicy::Never
+        || link.expiry_timeout() != 60
+        || link
+            .target()
+            .and_then(|t| t.capabilities.as_ref())
+            .is_none()
+    {
+        return Err(LinkError::force_detach().description("durable link is required"));
+    }
+    accept_link(link).await
+}
+
+#[ntex::test]
+async fn test_durable_link() -> std::io::Result<()> {
+    use ntex_amqp::error::AmqpProtocolError;
+    use ntex_amqp_codec::protocol::{Symbols, TerminusDurability, TerminusExpiryPolicy};
+    use ntex_amqp_codec::types::Symbol;
+
+    let srv = ntex_amqp::testing::plain_server().finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(durable_link))
+            .finish(),
+    );
+
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+    let mut session = sink.open_session().await.unwrap();
+
+    match session.open_sender("test").await {
+        Err(AmqpProtocolError::LinkDetached(Some(_))) => (),
+        res => panic!("Unexpected result: {:?}", res),
+    }
+
+    let link = session
+        .build_sender_link("durable", "test")
+        .durable(TerminusDurability::UnsettledState)
+        .expiry_policy(TerminusExpiryPolicy::Never)
+        .expiry_timeout(60)
+        .capabilities(Symbols::from(vec![Symbol::from_static("topic")]))
+        .open()
+        .await
+        .unwrap();
+    link.send(Bytes::from_static(b"data")).await.unwrap();
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_suspended_link() -> std::io::Result<()> {
+    use ntex::service::ServiceFactory;
+    use ntex_amqp_codec::protocol::{
+        Attach, Begin, Detach, Frame, ProtocolId, ReceiverSettleMode, Role, SenderSettleMode,
+        Target, TerminusDurability, TerminusExpiryPolicy, Transfer, TransferBody,
+    };
+    use ntex_amqp_codec::{AmqpCodec, AmqpFrame, ProtocolIdCodec};
+
+    let srv = ntex_amqp::testing::plain_server()
+        .finish(
+            server::Router::<()>::new()
+                .service("test", fn_factory_with_config(accept_link))
+                .finish(),
+        )
+        .new_service(())
+        .await
+        .unwrap();
+
+    let (mut io, server_io) = ntex_amqp::testing::Io::pair();
+    ntex::rt::spawn(async move {
+        let _ = srv.call(server_io).await;
+    });
+
+    let state = ntex::framed::State::new();
+    state
+        .send(&mut io, &ProtocolIdCodec, ProtocolId::Amqp)
+        .await
+        .unwrap();
+    state.next(&mut io, &ProtocolIdCodec).await.unwrap();
+
+    let codec = AmqpCodec::<AmqpFrame>::new();
+    let open = ntex_amqp::Configuration::default().to_open();
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, open.into()))
+        .await
+        .unwrap();
+    let _ = state.next(&mut io, &codec).await.unwrap().unwrap();
+
+    let begin = Begin {
+        remote_channel: None,
+        next_outgoing_id: 0,
+        incoming_window: std::u32::MAX,
+        outgoing_window: std::u32::MAX,
+        handle_max: std::u32::MAX,
+        offered_capabilities: None,
+        desired_capabilities: None,
+        properties: None,
+    };
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, begin.into()))
+        .await
+        .unwrap();
+    let _ = state.next(&mut io, &codec).await.unwrap().unwrap();
+
+    let attach = |handle| Attach {
+        name: "sender".into(),
+        handle,
+        role: Role::Sender,
+        snd_settle_mode: SenderSettleMode::Mixed,
+        rcv_settle_mode: ReceiverSettleMode::First,
+        source: None,
+        target: Some(
+            Target {
+                address: Some("test".into()),
+                durable: TerminusDurability::None,
+                expiry_policy: TerminusExpiryPolicy::SessionEnd,
+                timeout: 0,
+                dynamic: false,
+                dynamic_node_properties: None,
+                capabilities: None,
+            }
+            .into(),
+        ),
+        unsettled: None,
+        incomplete_unsettled: false,
+        initial_delivery_count: Some(0),
+        max_message_size: None,
+        offered_capabilities: None,
+        desired_capabilities: None,
+        properties: None,
+    };
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, attach(0).into()))
+        .await
+        .unwrap();
+    let frame = state.next(&mut io, &codec).await.unwrap().unwrap();
+    let local_handle = match frame.performative() {
+        Frame::Attach(attach) => attach.handle,
+        frame => panic!("Unexpected frame: {:?}", frame),
+    };
+
+    // suspend link
+    let detach = Detach {
+        handle: 0,
+        closed: false,
+        error: None,
+    };
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, detach.into()))
+        .await
+        .unwrap();
+    loop {
+        let frame = state.next(&mut io, &codec).await.unwrap().unwrap();
+        match frame.performative() {
+            Frame::Flow(_) => continue,
+            Frame::Detach(detach) => {
+                assert_eq!(detach.handle, local_handle);
+                assert!(!detach.closed);
+                break;
+            }
+            frame => panic!("Unexpected frame: {:?}", frame),
+        }
+    }
+
+    // resume link with new handle
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, attach(3).into()))
+        .await
+        .unwrap();
+    let frame = state.next(&mut io, &codec).await.unwrap().unwrap();
+    match frame.performative() {
+        Frame::Attach(attach) => {
+            assert_eq!(attach.handle, local_handle);
+            assert_eq!(attach.role, Role::Receiver);
+        }
+        frame => panic!("Unexpected frame: {:?}", frame),
+    }
+
+    let transfer = Transfer {
+        handle: 3,
+        delivery_id: Some(0),
+        delivery_tag: Some(Bytes::from_static(b"tag")),
+        message_format: Some(0),
+        settled: Some(false),
+        more: false,
+        rcv_settle_mode: None,
+        state: None,
+        resume: false,
+        aborted: false,
+        batchable: false,
+        body: Some(TransferBody::Data(Bytes::from_static(b"test"))),
+    };
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, transfer.into()))
+        .await
+        .unwrap();
+
+    // link handler receives transfers of resumed link
+    loop {
+        let frame = state.next(&mut io, &codec).await.unwrap().unwrap();
+        match frame.performative() {
+            Frame::Flow(_) => continue,
+            Frame::Disposition(disp) => {
+                assert_eq!(disp.first, 0);
+                break;
+            }
+            frame => panic!("Unexpected frame: {:?}", frame),
+        }
+    }
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_suspended_link_expiry() -> std::io::Result<()> {
+    use ntex::service::ServiceFactory;
+    use ntex_amqp_codec::protocol::{
+        AmqpError, Attach, Begin, Detach, ErrorCondition, Frame, ProtocolId, ReceiverSettleMode,
+        Role, SenderSettleMode, Target, TerminusDurability, TerminusExpiryPolicy,
+    };
+    use ntex_amqp_codec::{AmqpCodec, AmqpFrame, ProtocolIdCodec};
+    use std::{cell::RefCell, rc::Rc};
+
+    let links = Rc::new(RefCell::new(Vec::new()));
+    let links2 = links.clone();
+
+    let srv = ntex_amqp::testing::plain_server()
+        .finish(
+            server::Router::<()>::new()
+                .service(
+                    "test",
+                    fn_factory_with_config(move |link: types::Link<()>| {
+                        links2.borrow_mut().push(link.receiver().clone());
+                        accept_link(link)
+                    }),
+                )
+                .finish(),
+        )
+        .new_service(())
+        .await
+        .unwrap();
+
+    let (mut io, server_io) = ntex_amqp::testing::Io::pair();
+    ntex::rt::spawn(async move {
+        let _ = srv.call(server_io).await;
+    });
+
+    let state = ntex::framed::State::new();
+    state
+        .send(&mut io, &ProtocolIdCodec, ProtocolId::Amqp)
+        .await
+        .unwrap();
+    state.next(&mut io, &ProtocolIdCodec).await.unwrap();
+
+    let codec = AmqpCodec::<AmqpFrame>::new();
+    let open = ntex_amqp::Configuration::default().to_open();
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, open.into()))
+        .await
+        .unwrap();
+    let _ = state.next(&mut io, &codec).await.unwrap().unwrap();
+
+    let begin = Begin {
+        remote_channel: None,
+        next_outgoing_id: 0,
+        incoming_window: std::u32::MAX,
+        outgoing_window: std::u32::MAX,
+        handle_max: std::u32::MAX,
+        offered_capabilities: None,
+        desired_capabilities: None,
+        properties: None,
+    };
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, begin.into()))
+        .await
+        .unwrap();
+    let _ = state.next(&mut io, &codec).await.unwrap().unwrap();
+
+    let attach = |handle, role| Attach {
+        name: "sender".into(),
+        handle,
+        role,
+        snd_settle_mode: SenderSettleMode::Mixed,
+        rcv_settle_mode: ReceiverSettleMode::First,
+        source: None,
+        target: Some(
+            Target {
+                address: Some("test".into()),
+                durable: TerminusDurability::None,
+                expiry_policy: TerminusExpiryPolicy::SessionEnd,
+                timeout: 1,
+                dynamic: false,
+                dynamic_node_properties: None,
+                capabilities: None,
+            }
+            .into(),
+        ),
+        unsettled: None,
+        incomplete_unsettled: false,
+        initial_delivery_count: Some(0),
+        max_message_size: None,
+        offered_capabilities: None,
+        desired_capabilities: None,
+        properties: None,
+    };
+    state
+        .send(
+            &mut io,
+            &codec,
+            AmqpFrame::new(0, attach(0, Role::Sender).into()),
+        )
+        .await
+        .unwrap();
+    let _ = state.next(&mut io, &codec).await.unwrap().unwrap();
+
+    // suspend link
+    let detach = Detach {
+        handle: 0,
+        closed: false,
+        error: None,
+    };
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, detach.into()))
+        .await
+        .unwrap();
+    loop {
+        let frame = state.next(&mut io, &codec).await.unwrap().unwrap();
+        match frame.performative() {
+            Frame::Flow(_) => continue,
+            Frame::Detach(detach) => {
+                assert!(!detach.closed);
+                break;
+            }
+            frame => panic!("Unexpected frame: {:?}", frame),
+        }
+    }
+
+    // resume with the same role as local link is refused
+    state
+        .send(
+            &mut io,
+            &codec,
+            AmqpFrame::new(0, attach(3, Role::Receiver).into()),
+        )
+        .await
+        .unwrap();
+    let frame = state.next(&mut io, &codec).await.unwrap().unwrap();
+    match frame.performative() {
+        Frame::Detach(detach) => {
+            assert_eq!(detach.handle, 3);
+            assert!(detach.closed);
+            assert_eq!(
+                detach.error.as_ref().unwrap().condition,
+                ErrorCondition::AmqpError(AmqpError::InvalidField)
+            );
+        }
+        frame => panic!("Unexpected frame: {:?}", frame),
+    }
+    assert_eq!(links.borrow()[0].session().info().links.len(), 1);
+
+    // link is not resumed within terminus time-out
+    ntex::rt::time::sleep(std::time::Duration::from_millis(2500)).await;
+    assert!(links.borrow()[0].session().info().links.is_empty());
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_delivery_tag() -> std::io::Result<()> {
+    use ntex_amqp::DeliveryTag;
+    use std::{cell::RefCell, rc::Rc};
+
+    let tags = Rc::new(RefCell::new(Vec::new()));
+    let tags2 = tags.clone();
+
+    let srv = ntex_amqp::testing::plain_server().finish(
+        server::Router::<()>::new()
+            .service(
+                "test",
+                fn_factory_with_config(move |_: types::Link<()>| {
+                    let tags = tags2.clone();
+                    async move {
+                        Ok::<_, LinkError>(fn_service(move |tr: types::Transfer<()>| {
+                            tags.borrow_mut()
+                                .push(tr.frame().delivery_tag.clone().unwrap());
+                            Ready::<_, LinkError>::Ok(types::Outcome::Accept)
+                        }))
+                    }
+                }),
+            )
+            .finish(),
+    );
+
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    let mut session = sink.open_session().await.unwrap();
+    let link = session.open_sender("test").await.unwrap();
+
+    link.send(Bytes::from_static(b"data")).await.unwrap();
+    let (tag, delivery) = link.send_tagged(Bytes::from_static(b"data"));
+    assert_eq!(tag, Bytes::from_static(&[1, 0, 0, 0]));
+    delivery.await.unwrap();
+
+    link.set_delivery_tag(DeliveryTag::Uuid);
+    let (tag, delivery) = link.send_tagged(Bytes::from_static(b"data"));
+    assert_eq!(tag.len(), 16);
+    delivery.await.unwrap();
+
+    let tags = tags.borrow();
+    assert_eq!(tags.len(), 3);
+    assert_eq!(tags[0], Bytes::from_static(&[0, 0, 0, 0]));
+    assert_eq!(tags[1], Bytes::from_static(&[1, 0, 0, 0]));
+    assert_eq!(tags[2], tag);
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_heartbeat_sub_second() -> std::io::Result<()> {
+    use ntex::service::ServiceFactory;
+    use ntex_amqp_codec::protocol::{Frame, ProtocolId};
+    use ntex_amqp_codec::{AmqpCodec, AmqpFrame, ProtocolIdCodec};
+
+    let srv = ntex_amqp::testing::plain_server()
+        .finish(
+            server::Router::<()>::new()
+                .service("test", fn_factory_with_config(accept_link))
+                .finish(),
+        )
+        .new_service(())
+        .await
+        .unwrap();
+
+    let (mut io, server_io) = ntex_amqp::testing::Io::pair();
+    ntex::rt::spawn(async move {
+        let _ = srv.call(server_io).await;
+    });
+
+    let state = ntex::framed::State::new();
+    state
+        .send(&mut io, &ProtocolIdCodec, ProtocolId::Amqp)
+        .await
+        .unwrap();
+    state.next(&mut io, &ProtocolIdCodec).await.unwrap();
+
+    // client requires frames within 800 millis
+    let codec = AmqpCodec::<AmqpFrame>::new();
+    let mut config = ntex_amqp::Configuration::default();
+    config.idle_time_out = 800;
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, config.to_open().into()))
+        .await
+        .unwrap();
+    let frame = state.next(&mut io, &codec).await.unwrap().unwrap();
+    assert!(matches!(frame.performative(), Frame::Open(_)));
+
+    // empty frames are sent at half of remote idle time-out
+    for _ in 0..3 {
+        let frame = ntex::rt::time::timeout(
+            std::time::Duration::from_millis(500),
+            state.next(&mut io, &codec),
+        )
+        .await
+        .expect("heartbeat is not sent")
+        .unwrap()
+        .unwrap();
+        assert!(matches!(frame.performative(), Frame::Empty));
+    }
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_invalid_config() -> std::io::Result<()> {
+    use ntex::service::ServiceFactory;
+    use ntex_amqp::error::ConfigError;
+
+    let mut configs = Vec::new();
+    let mut config = ntex_amqp::Configuration::default();
+    config.channel_max = 0;
+    configs.push((config, ConfigError::ZeroChannelMax));
+    let mut config = ntex_amqp::Configuration::default();
+    config.max_frame_size(100);
+    configs.push((config, ConfigError::MaxFrameSizeTooSmall(100)));
+    let mut config = ntex_amqp::Configuration::default();
+    config.idle_time_out = 500;
+    configs.push((config, ConfigError::IdleTimeoutTooSmall(500)));
+
+    for (config, expected) in configs {
+        let srv = ntex_amqp::testing::plain_server()
+            .config(config)
+            .finish(
+                server::Router::<()>::new()
+                    .service("test", fn_factory_with_config(accept_link))
+                    .finish(),
+            )
+            .new_service(())
+            .await
+            .unwrap();
+
+        let (_io, server_io) = ntex_amqp::testing::Io::pair();
+        match srv.call(server_io).await {
+            Err(server::ServerError::Config(err)) => assert_eq!(err, expected),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    Ok(())
+}
+
+/// Transfer states and discharges seen by test transaction coordinator
+#[derive(Default)]
+struct TxnLog {
+    states: Vec<Option<ntex_amqp_codec::protocol::DeliveryState>>,
+    discharges: Vec<(Bytes, bool)>,
+}
+
+/// Link service with transaction coordinator, every declared transaction gets id `txn-1`
+async fn txn_link(
+    mut link: types::Link<()>,
+    reject_declare: bool,
+    log: std::rc::Rc<std::cell::RefCell<TxnLog>>,
+) -> Result<(), LinkError> {
+    use ntex_amqp::error::AmqpError;
+    use ntex_amqp_codec::protocol::{
+        Accepted, Declare, Declared, DeliveryState, Discharge, Rejected, TargetTerminus,
+    };
+    use ntex_amqp_codec::Decode;
+
+    let coordinator = matches!(link.frame().target(), Some(TargetTerminus::Coordinator(_)));
+    link.receiver_mut().open();
+    link.link_credit(50);
+
+    let mut deliveries = link.receiver().deliveries();
+    while let Some(Ok(delivery)) = ntex::util::next(&mut deliveries).await {
+        if !coordinator {
+            log.borrow_mut().states.push(delivery.frame().state.clone());
+            delivery.accept();
+            continue;
+        }
+
+        // control messages are encoded as amqp-value sections
+        let body = delivery.body().unwrap().slice(3..);
+        if Declare::decode(&body).is_ok() {
+            if reject_declare {
+                delivery.settle_with_state(DeliveryState::Rejected(Rejected {
+                    error: Some(AmqpError::not_implemented().into()),
+                }));
+            } else {
+                delivery.settle_with_state(DeliveryState::Declared(Declared {
+                    txn_id: Bytes::from_static(b"txn-1"),
+                }));
+            }
+        } else {
+            let (_, discharge) = Discharge::decode(&body).unwrap();
+            log.borrow_mut()
+                .discharges
+                .push((discharge.txn_id, discharge.fail.unwrap_or(false)));
+            delivery.settle_with_state(DeliveryState::Accepted(Accepted {}));
+        }
+    }
+    Ok(())
+}
+
+/// Open session to server with test transaction coordinator
+///
+/// Server sends one message to every receiver link opened by client.
+async fn txn_session(
+    reject_declare: bool,
+    log: std::rc::Rc<std::cell::RefCell<TxnLog>>,
+) -> (ntex_amqp::Session, ntex_amqp::testing::Recorder) {
+    let control = fn_factory_with_config(|_: ntex_amqp::State<()>| {
+        Ready::<_, LinkError>::Ok(fn_service(|frame: ntex_amqp::ControlFrame| {
+            if let ntex_amqp::ControlFrameKind::AttachSender(_, ref link) = frame.frame() {
+                let link = link.clone();
+                ntex::rt::spawn(async move {
+                    let _ = link.send(Bytes::from_static(b"data")).await;
+                });
+            }
+            Ready::<_, LinkError>::Ok(())
+        }))
+    });
+    let srv = ntex_amqp::testing::plain_server()
+        .control(control)
+        .finish(fn_factory_with_config(move |_: ntex_amqp::State<()>| {
+            let log = log.clone();
+            Ready::<_, LinkError>::Ok(fn_service(move |link| {
+                txn_link(link, reject_declare, log.clone())
+            }))
+        }));
+
+    let (client, recorder) =
+        ntex_amqp::testing::connect_recorded(srv, &client::Connector::<&'static str, ()>::new())
+            .await
+            .unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+    (sink.open_session().await.unwrap(), recorder)
+}
+
+#[ntex::test]
+async fn test_transaction_commit() -> std::io::Result<()> {
+    use ntex_amqp_codec::protocol::{DeliveryState, TransactionalState};
+
+    let log = std::rc::Rc::new(std::cell::RefCell::new(TxnLog::default()));
+    let (mut session, _) = txn_session(false, log.clone()).await;
+
+    let coordinator = session.coordinator("coordinator").await.unwrap();
+    let txn = coordinator.declare().await.unwrap();
+    assert_eq!(txn.id(), &Bytes::from_static(b"txn-1"));
+
+    let link = session.open_sender("test").await.unwrap();
+    txn.send(&link, Bytes::from_static(b"data")).await.unwrap();
+    assert_eq!(
+        log.borrow().states,
+        vec![Some(DeliveryState::TransactionalState(
+            TransactionalState {
+                txn_id: Bytes::from_static(b"txn-1"),
+                outcome: None,
+            }
+        ))]
+    );
+
+    txn.commit().await.unwrap();
+    assert_eq!(
+        log.borrow().discharges,
+        vec![(Bytes::from_static(b"txn-1"), false)]
+    );
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_transaction_rollback() -> std::io::Result<()> {
+    let log = std::rc::Rc::new(std::cell::RefCell::new(TxnLog::default()));
+    let (mut session, _) = txn_session(false, log.clone()).await;
+
+    let coordinator = session.coordinator("coordinator").await.unwrap();
+    let txn = coordinator.declare().await.unwrap();
+    txn.rollback().await.unwrap();
+    assert_eq!(
+        log.borrow().discharges,
+        vec![(Bytes::from_static(b"txn-1"), true)]
+    );
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_transaction_declare_rejected() -> std::io::Result<()> {
+    use ntex_amqp::error::AmqpProtocolError;
+
+    let log = std::rc::Rc::new(std::cell::RefCell::new(TxnLog::default()));
+    let (mut session, _) = txn_session(true, log.clone()).await;
+
+    let coordinator = session.coordinator("coordinator").await.unwrap();
+    let err = coordinator.declare().await.unwrap_err();
+    assert!(matches!(err, AmqpProtocolError::Transaction(Some(_))));
+    assert!(log.borrow().discharges.is_empty());
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_transaction_acquire() -> std::io::Result<()> {
+    use ntex_amqp_codec::protocol::{
+        Accepted, DeliveryState, Frame, Outcome, Role, TransactionalState,
+    };
+
+    for fail in &[false, true] {
+        let log = std::rc::Rc::new(std::cell::RefCell::new(TxnLog::default()));
+        let (mut session, recorder) = txn_session(false, log.clone()).await;
+
+        let coordinator = session.coordinator("coordinator").await.unwrap();
+        let txn = coordinator.declare().await.unwrap();
+
+        let mut link = session.open_receiver("test").await.unwrap();
+        link.set_link_credit(10);
+        let transfer = ntex::util::next(&mut link).await.unwrap().unwrap();
+        let id = transfer.delivery_id.unwrap();
+
+        txn.acquire(&link, id, Outcome::Accepted(Accepted {}));
+        txn.discharge(*fail).await.unwrap();
+        ntex::rt::time::sleep(std::time::Duration::from_millis(50)).await;
+
+        let dispositions: Vec<_> = recorder
+            .sent
+            .frames()
+            .into_iter()
+            .filter_map(|frame| match frame.performative() {
+                Frame::Disposition(disp) if disp.role == Role::Receiver => Some(disp.clone()),
+                _ => None,
+            })
+            .collect();
+
+        // acquisition is sent unsettled with transactional state
+        assert!(!dispositions[0].settled);
+        assert_eq!(
+            dispositions[0].state,
+            Some(DeliveryState::TransactionalState(TransactionalState {
+                txn_id: Bytes::from_static(b"txn-1"),
+                outcome: Some(Outcome::Accepted(Accepted {})),
+            }))
+        );
+
+        if *fail {
+            // rolled back acquisition stays unsettled
+            assert_eq!(dispositions.len(), 1);
+        } else {
+            // committed acquisition is settled with plain outcome
+            assert_eq!(dispositions.len(), 2);
+            assert_eq!(dispositions[1].first, id);
+            assert!(dispositions[1].settled);
+            assert_eq!(
+                dispositions[1].state,
+                Some(DeliveryState::Accepted(Accepted {}))
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_write_budget() -> std::io::Result<()> {
+    let srv = ntex_amqp::testing::plain_server().finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(accept_link))
+            .finish(),
+    );
+
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    let mut session = sink.open_session().await.unwrap();
+    let link = session.open_sender("test").await.unwrap();
+
+    // transfers exceed write budget of single dispatcher poll
+    let deliveries: Vec<_> = (0..50)
+        .map(|_| link.send(Bytes::from(vec![b'x'; 16 * 1024])))
+        .collect();
+    assert!(session.info().pending_transfers > 0);
+
+    // held back transfers are written by dispatcher task
+    for delivery in deliveries {
+        delivery.await.unwrap();
+    }
+    assert_eq!(session.info().pending_transfers, 0);
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_min_max_frame_size() -> std::io::Result<()> {
+    use ntex_amqp_codec::{protocol::Frame, Encode};
+
+    let mut config = ntex_amqp::Configuration::default();
+    config.max_frame_size(512);
+
+    let srv = ntex_amqp::testing::plain_server().config(config).finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(accept_link))
+            .finish(),
+    );
+
+    let connector = client::Connector::<&'static str, ()>::new();
+    let (client, recorder) = ntex_amqp::testing::connect_recorded(srv, &connector)
+        .await
+        .unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    let mut session = sink.open_session().await.unwrap();
+    let link = session.open_sender("test").await.unwrap();
+    let state = link
+        .deliver(Bytes::from(vec![b'x'; 4 * 1024]))
+        .await
+        .unwrap();
+    assert!(matches!(state, types::DeliveryResult::Accepted));
+
+    // body is split to chunks that fit remote frame size
+    let transfers: Vec<_> = recorder
+        .sent
+        .frames()
+        .into_iter()
+        .filter(|f| matches!(f.performative(), Frame::Transfer(_)))
+        .collect();
+    assert!(transfers.len() > 8);
+    assert!(transfers.iter().all(|f| f.encoded_size() <= 512));
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_max_message_size() -> std::io::Result<()> {
+    use ntex_amqp_codec::protocol::{self, ErrorCondition, Frame};
+
+    async fn limited_link(
+        link: types::Link<()>,
+    ) -> Result<
+        Box<
+            dyn Service<
+                    Request = types::Transfer<()>,
+                    Response = types::Outcome,
+                    Error = LinkError,
+                    Future = Ready<types::Outcome, LinkError>,
+                > + 'static,
+        >,
+        LinkError,
+    > {
+        link.receiver().set_max_message_size(64 * 1024);
+        accept_link(link).await
+    }
+
+    let mut config = ntex_amqp::Configuration::default();
+    config.max_frame_size(16 * 1024);
+
+    let srv = ntex_amqp::testing::plain_server().config(config).finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(accept_link))
+            .service("limited", fn_factory_with_config(limited_link))
+            .finish(),
+    );
+
+    let connector = client::Connector::<&'static str, ()>::new();
+    let (client, recorder) = ntex_amqp::testing::connect_recorded(srv, &connector)
+        .await
+        .unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    let mut session = sink.open_session().await.unwrap();
+
+    // default limit is 256Kb
+    let link = session.open_sender("test").await.unwrap();
+    let state = link
+        .deliver(Bytes::from(vec![b'x'; 128 * 1024]))
+        .await
+        .unwrap();
+    assert!(matches!(state, types::DeliveryResult::Accepted));
+
+    // message larger than link's limit detaches link
+    let link = session.open_sender("limited").await.unwrap();
+    let on_close = link.on_close();
+    let _ = link.send(Bytes::from(vec![b'x'; 128 * 1024]));
+    on_close.await;
+
+    let detach = recorder
+        .received
+        .frames()
+        .into_iter()
+        .find_map(|frame| match frame.performative() {
+            Frame::Detach(detach) => Some(detach.clone()),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(
+        detach.error.unwrap().condition,
+        ErrorCondition::LinkError(protocol::LinkError::MessageSizeExceeded)
+    );
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_close_handshake() -> std::io::Result<()> {
+    use ntex_amqp::ConnectionStatus;
+    use ntex_amqp_codec::protocol::Frame;
+
+    let srv = ntex_amqp::testing::plain_server().finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(accept_link))
+            .finish(),
+    );
+
+    let connector = client::Connector::<&'static str, ()>::new();
+    let (client, recorder) = ntex_amqp::testing::connect_recorded(srv, &connector)
+        .await
+        .unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    // open connection sends `Close` and waits for reply
+    let on_close = sink.on_close();
+    sink.close().await.unwrap();
+    assert_eq!(sink.status(), ConnectionStatus::Closing);
+    ntex::rt::time::timeout(std::time::Duration::from_secs(1), on_close)
+        .await
+        .unwrap();
+
+    let is_close = |frame: &ntex_amqp_codec::AmqpFrame| match frame.performative() {
+        Frame::Close(close) => close.error.is_none(),
+        _ => false,
+    };
+    assert_eq!(
+        recorder
+            .sent
+            .frames()
+            .iter()
+            .filter(|f| is_close(f))
+            .count(),
+        1
+    );
+    assert_eq!(
+        recorder
+            .received
+            .frames()
+            .iter()
+            .filter(|f| is_close(f))
+            .count(),
+        1
+    );
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_remote_close_with_error() -> std::io::Result<()> {
+    use ntex_amqp::error::AmqpError;
+    use ntex_amqp_codec::protocol::{self, ErrorCondition};
+    use std::{cell::RefCell, rc::Rc};
+
+    let closed = Rc::new(RefCell::new(None));
+    let closed2 = closed.clone();
+
+    let srv = ntex_amqp::testing::plain_server()
+        .control(fn_factory_with_config(move |_: ntex_amqp::State<()>| {
+            let closed = closed2.clone();
+            Ready::<_, LinkError>::Ok(fn_service(move |frame: ntex_amqp::ControlFrame| {
+                if let ntex_amqp::ControlFrameKind::RemoteClose(close) = frame.frame() {
+                    *closed.borrow_mut() = Some(close.clone());
+                }
+                Ready::<_, LinkError>::Ok(())
+            }))
+        }))
+        .finish(
+            server::Router::<()>::new()
+                .service("test", fn_factory_with_config(accept_link))
+                .finish(),
+        );
+
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    let on_close = sink.on_close();
+    sink.close_with_error(AmqpError::internal_error().description("test"))
+        .await
+        .unwrap();
+    ntex::rt::time::timeout(std::time::Duration::from_secs(1), on_close)
+        .await
+        .unwrap();
+
+    let err = closed.borrow_mut().take().unwrap().error.unwrap();
+    assert_eq!(
+        err.condition,
+        ErrorCondition::AmqpError(protocol::AmqpError::InternalError)
+    );
+    assert_eq!(
+        err.description,
+        Some(ntex::util::ByteString::from_static("test"))
+    );
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_client_into_inner() -> std::io::Result<()> {
+    use ntex_amqp_codec::protocol::{Close, Frame};
+
+    let srv = ntex_amqp::testing::plain_server().finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(accept_link))
+            .finish(),
+    );
+
+    // client without sessions is deconstructed into working io
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    assert!(!client.sink().has_sessions());
+    let (mut io, state, codec) = match client.into_inner() {
+        Ok(inner) => inner,
+        Err(_) => panic!("Client has no sessions"),
+    };
+    state
+        .send(
+            &mut io,
+            &codec,
+            ntex_amqp_codec::AmqpFrame::new(0, Close { error: None }.into()),
+        )
+        .await
+        .unwrap();
+    let frame = state.next(&mut io, &codec).await.unwrap().unwrap();
+    assert!(matches!(frame.performative(), Frame::Close(_)));
+
+    // opening session keeps client
+    let srv = ntex_amqp::testing::plain_server().finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(accept_link))
+            .finish(),
+    );
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    let sink = client.sink();
+    let res =
+        ntex::rt::time::timeout(std::time::Duration::from_millis(50), sink.open_session()).await;
+    assert!(res.is_err());
+    assert!(sink.has_sessions());
+    let client = match client.into_inner() {
+        Ok(_) => panic!("Client has opening session"),
+        Err(client) => client,
+    };
+
+    // returned client is still usable
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+    let mut session = sink.open_session().await.unwrap();
+    session.open_sender("test").await.unwrap();
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_close_timeout() -> std::io::Result<()> {
+    use ntex::channel::oneshot;
+    use ntex_amqp_codec::protocol::{Frame, ProtocolId};
+    use ntex_amqp_codec::{AmqpCodec, AmqpFrame, ProtocolIdCodec};
+
+    let (client_io, mut io) = ntex_amqp::testing::Io::pair();
+    let (tx, rx) = oneshot::channel();
+    ntex::rt::spawn(async move {
+        let mut connector = client::Connector::<&'static str, ()>::new();
+        connector.close_timeout(1);
+        let _ = tx.send(connector.negotiate(client_io).await);
+    });
+
+    // peer completes open handshake, but never replies to `Close`
+    let state = ntex::framed::State::new();
+    state.next(&mut io, &ProtocolIdCodec).await.unwrap();
+    state
+        .send(&mut io, &ProtocolIdCodec, ProtocolId::Amqp)
+        .await
+        .unwrap();
+
+    let codec = AmqpCodec::<AmqpFrame>::new();
+    let _ = state.next(&mut io, &codec).await.unwrap().unwrap();
+    let open = ntex_amqp::Configuration::default().to_open();
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, open.into()))
+        .await
+        .unwrap();
+
+    let client = rx.await.unwrap().unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    let on_close = sink.on_close();
+    sink.close().await.unwrap();
+    loop {
+        let frame = state.next(&mut io, &codec).await.unwrap().unwrap();
+        match frame.performative() {
+            Frame::Empty => continue,
+            Frame::Close(_) => break,
+            frame => panic!("Unexpected frame: {:?}", frame),
+        }
+    }
+
+    // connection waits for remote `Close` within close timeout
+    ntex::rt::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert!(!io.is_closed());
+
+    // and gets dropped after timeout
+    ntex::rt::time::timeout(std::time::Duration::from_secs(2), on_close)
+        .await
+        .unwrap();
+    ntex::rt::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert!(io.is_closed());
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_session_window_wrap() -> std::io::Result<()> {
+    use ntex::channel::oneshot;
+    use ntex_amqp_codec::protocol::{Begin, Flow, Frame, ProtocolId, Role};
+    use ntex_amqp_codec::{AmqpCodec, AmqpFrame, ProtocolIdCodec};
+
+    let (client_io, mut io) = ntex_amqp::testing::Io::pair();
+    let (tx, rx) = oneshot::channel();
+    ntex::rt::spawn(async move {
+        let connector = client::Connector::<&'static str, ()>::new();
+        let _ = tx.send(connector.negotiate(client_io).await);
+    });
+
+    let state = ntex::framed::State::new();
+    state.next(&mut io, &ProtocolIdCodec).await.unwrap();
+    state
+        .send(&mut io, &ProtocolIdCodec, ProtocolId::Amqp)
+        .await
+        .unwrap();
+
+    let codec = AmqpCodec::<AmqpFrame>::new();
+    let _ = state.next(&mut io, &codec).await.unwrap().unwrap();
+    let open = ntex_amqp::Configuration::default().to_open();
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, open.into()))
+        .await
+        .unwrap();
+
+    let client = rx.await.unwrap().unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    let (tx, rx) = oneshot::channel();
+    ntex::rt::spawn(async move {
+        let mut session = sink.open_session().await.unwrap();
+        let _ = tx.send(session.open_sender("test").await.unwrap());
+    });
+
+    let _ = state.next(&mut io, &codec).await.unwrap().unwrap();
+    let begin = Begin {
+        remote_channel: Some(0),
+        next_outgoing_id: 0,
+        incoming_window: std::u32::MAX,
+        outgoing_window: std::u32::MAX,
+        handle_max: std::u32::MAX,
+        offered_capabilities: None,
+        desired_capabilities: None,
+        properties: None,
+    };
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, begin.into()))
+        .await
+        .unwrap();
+
+    let frame = state.next(&mut io, &codec).await.unwrap().unwrap();
+    let mut attach = match frame.performative() {
+        Frame::Attach(attach) => attach.clone(),
+        frame => panic!("Unexpected frame: {:?}", frame),
+    };
+    attach.role = Role::Receiver;
+    attach.initial_delivery_count = None;
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, attach.into()))
+        .await
+        .unwrap();
+    let link = rx.await.unwrap();
+
+    // window of remote peer wraps over u32::MAX, 3 transfers are allowed
+    let flow = Flow {
+        next_incoming_id: Some(std::u32::MAX - 1),
+        incoming_window: 5,
+        next_outgoing_id: 0,
+        outgoing_window: std::u32::MAX,
+        handle: Some(0),
+        delivery_count: Some(0),
+        link_credit: Some(10),
+        available: None,
+        drain: false,
+        echo: false,
+        properties: None,
+    };
+    state
+        .send(&mut io, &codec, AmqpFrame::new(0, flow.into()))
+        .await
+        .unwrap();
+    ntex::rt::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert_eq!(link.session().info().remote_incoming_window, 3);
+
+    for _ in 0..5 {
+        let fut = link.send(Bytes::from_static(b"test"));
+        ntex::rt::spawn(async move {
+            let _ = fut.await;
+        });
+    }
+    let info = link.session().info();
+    assert_eq!(info.remote_incoming_window, 0);
+    assert_eq!(info.pending_transfers, 2);
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_sasl_failure_delay() -> std::io::Result<()> {
+    use ntex::service::ServiceFactory;
+    use ntex_amqp_codec::protocol::{SaslCode, SaslInit, SaslMechanism};
+
+    struct UserAuth;
+
+    impl server::SaslAuthenticator for UserAuth {
+        type Future = Ready<server::Identity, SaslCode>;
+
+        fn mechanisms(&self) -> Vec<SaslMechanism> {
+            vec![SaslMechanism::Plain]
+        }
+
+        fn authenticate(&self, _: SaslMechanism, _: &SaslInit) -> Self::Future {
+            // reason that must not be revealed to the client
+            Ready::Err(SaslCode::SysPerm)
+        }
+    }
+
+    let srv = ntex_amqp::testing::plain_server()
+        .sasl_authenticator(UserAuth)
+        .sasl_failure_delay(200)
+        .finish(
+            server::Router::<()>::new()
+                .service("test", fn_factory_with_config(server))
+                .finish(),
+        )
+        .new_service(())
+        .await
+        .unwrap();
+
+    let (io, server_io) = ntex_amqp::testing::Io::pair();
+    ntex::rt::spawn(async move {
+        let _ = srv.call(server_io).await;
+    });
+
+    let start = std::time::Instant::now();
+    let res = client::Connector::<&'static str, ()>::new()
+        .negotiate_sasl(
+            io,
+            client::SaslAuth {
+                authz_id: "".into(),
+                authn_id: "nobody".into(),
+                password: "password".into(),
+            },
+        )
+        .await;
+    assert!(matches!(
+        res,
+        Err(client::ConnectError::Sasl(SaslCode::Auth))
+    ));
+    assert!(start.elapsed() >= std::time::Duration::from_millis(200));
+
+    Ok(())
+}
+
+#[ntex::test]
+async fn test_link_flow_state() -> std::io::Result<()> {
+    let srv = ntex_amqp::testing::plain_server().finish(
+        server::Router::<()>::new()
+            .service("test", fn_factory_with_config(accept_link))
+            .finish(),
+    );
+
+    let client = ntex_amqp::testing::connect(srv).await.unwrap();
+    let sink = client.sink();
+    ntex::rt::spawn(async move {
+        let _ = client.start_default().await;
+    });
+
+    let mut session = sink.open_session().await.unwrap();
+    let link = session.open_sender("test").await.unwrap();
+    link.deliver(Bytes::from_static(b"data")).await.unwrap();
+
+    // router grants 50 credits
+    assert_eq!(link.credit(), 49);
+    assert_eq!(link.delivery_count(), 1);
+
+    let info = session.info();
+    assert_eq!(info.links.len(), 1);
+    assert_eq!(info.links[0].link_credit, 49);
+    assert_eq!(info.links[0].delivery_count, 1);
+
+    Ok(())
+}